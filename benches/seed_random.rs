@@ -0,0 +1,49 @@
+//! Compares the integer implementation of seedrandom.js with the floating
+//! point reference implementation. Run with `cargo bench --features bench`.
+
+extern crate speedrun_bingo;
+
+use speedrun_bingo::{FloatSeedRandom, SeedRandom};
+use std::time::{Duration, Instant};
+
+const SEEDS: u32 = 2_000;
+const DRAWS: usize = 25;
+
+fn nanos(duration: Duration) -> u64 {
+    duration.as_secs() * 1_000_000_000 + duration.subsec_nanos() as u64
+}
+
+fn bench<F: FnMut(&str) -> f64>(name: &str, mut generate: F) {
+    let seeds = (0..SEEDS).map(|s| s.to_string()).collect::<Vec<_>>();
+    let mut checksum = 0.0;
+
+    // Warm up the caches before measuring.
+    for seed in &seeds {
+        checksum += generate(seed);
+    }
+
+    let start = Instant::now();
+    for seed in &seeds {
+        checksum += generate(seed);
+    }
+    let elapsed = nanos(start.elapsed());
+
+    println!(
+        "{:<8} {:>8} ns per seed ({} draws), checksum {}",
+        name,
+        elapsed / SEEDS as u64,
+        DRAWS,
+        checksum
+    );
+}
+
+fn main() {
+    bench("integer", |seed| {
+        let mut random = SeedRandom::new(seed);
+        (0..DRAWS).map(|_| random.next_f64()).sum()
+    });
+    bench("float", |seed| {
+        let mut random = FloatSeedRandom::new(seed);
+        (0..DRAWS).map(|_| random.next_f64()).sum()
+    });
+}
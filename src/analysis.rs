@@ -0,0 +1,639 @@
+use arrayvec::ArrayVec;
+use bingo::MAX_CELLS;
+use difficulty::DifficultyMapping;
+use generator::{self, GenerateError, Observer};
+use serde_json::{self, Result as JsonResult};
+use std::cmp::Reverse;
+use std::fmt::{self, Write};
+use std::ops::Range;
+use std::{ptr, thread};
+use {Constraints, Goal, Line, Template};
+
+/// How the goals, their types and the synergy of the lines are distributed
+/// over the boards of a range of seeds, which shows how balanced a template
+/// is.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Analysis<'a> {
+    /// The width and height of the boards.
+    pub size: usize,
+    /// How many boards got generated.
+    pub boards: u64,
+    /// Every goal of the template, tier by tier.
+    pub goals: Vec<GoalStats<'a>>,
+    /// Every type of the template, starting with the most common one.
+    pub types: Vec<TypeStats<'a>>,
+    /// Every line of the boards, in the order of `Line::all`.
+    pub lines: Vec<LineStats>,
+    /// The goals the generator considered for a cell, but never picked,
+    /// because another goal always had less synergy.
+    pub excluded: Vec<&'a str>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GoalStats<'a> {
+    pub name: &'a str,
+    pub tier: usize,
+    /// How many boards the goal is on.
+    pub appearances: u64,
+    /// How often the goal is in each cell, row by row.
+    pub positions: Vec<u64>,
+    /// How often the generator considered the goal for a cell, whether it
+    /// got picked or not.
+    pub considered: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TypeStats<'a> {
+    pub name: &'a str,
+    /// How many goals with the type there are on all the boards together.
+    pub appearances: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LineStats {
+    pub line: Line,
+    /// How many boards the line has each amount of synergy on, starting at
+    /// no synergy.
+    pub synergy: Vec<u64>,
+}
+
+impl LineStats {
+    /// The average synergy of the line.
+    pub fn mean(&self) -> f64 {
+        let boards = self.synergy.iter().sum::<u64>();
+        if boards == 0 {
+            return 0.0;
+        }
+        let total = self
+            .synergy
+            .iter()
+            .enumerate()
+            .map(|(synergy, &count)| synergy as u64 * count)
+            .sum::<u64>();
+        total as f64 / boards as f64
+    }
+
+    /// The highest synergy the line has on any board.
+    pub fn max(&self) -> usize {
+        self.synergy
+            .iter()
+            .rposition(|&count| count > 0)
+            .unwrap_or(0)
+    }
+}
+
+/// The counts one thread collects, which get added up afterwards.
+struct Counts {
+    boards: u64,
+    /// The index of the first goal of each tier among all the goals.
+    offsets: Vec<usize>,
+    appearances: Vec<u64>,
+    /// The positions of every goal, one goal after the other.
+    positions: Vec<u64>,
+    considered: Vec<u64>,
+    lines: Vec<Vec<u64>>,
+}
+
+impl Counts {
+    fn new(template: &Template, size: usize) -> Self {
+        let mut offsets = Vec::new();
+        let mut count = 0;
+        for tier in &template.0 {
+            offsets.push(count);
+            count += tier.len();
+        }
+        Counts {
+            boards: 0,
+            offsets: offsets,
+            appearances: vec![0; count],
+            positions: vec![0; count * size * size],
+            considered: vec![0; count],
+            lines: vec![Vec::new(); 2 * size + 2],
+        }
+    }
+
+    fn add(&mut self, other: Counts) {
+        self.boards += other.boards;
+        for (count, other) in self.appearances.iter_mut().zip(other.appearances) {
+            *count += other;
+        }
+        for (count, other) in self.positions.iter_mut().zip(other.positions) {
+            *count += other;
+        }
+        for (count, other) in self.considered.iter_mut().zip(other.considered) {
+            *count += other;
+        }
+        for (line, other) in self.lines.iter_mut().zip(other.lines) {
+            if line.len() < other.len() {
+                line.resize(other.len(), 0);
+            }
+            for (count, other) in line.iter_mut().zip(other) {
+                *count += other;
+            }
+        }
+    }
+}
+
+/// Counts the goals the generator considers for the cells.
+struct Counter<'t, 'a: 't> {
+    template: &'a Template,
+    counts: &'t mut Counts,
+    /// The tier of each cell.
+    tiers: ArrayVec<[usize; MAX_CELLS]>,
+}
+
+impl<'t, 'a> Counter<'t, 'a> {
+    /// The index of the goal of the tier among all the goals.
+    fn index(&self, tier: usize, goal: &Goal) -> usize {
+        let index = self.template.0[tier]
+            .iter()
+            .position(|g| ptr::eq(g, goal))
+            .unwrap();
+        self.counts.offsets[tier] + index
+    }
+}
+
+impl<'t, 'a> Observer<'a> for Counter<'t, 'a> {
+    fn cell(&mut self, _index: usize, _level: usize, tier: usize, _offset: usize) {
+        self.tiers.push(tier);
+    }
+
+    fn candidate(&mut self, goal: &'a Goal, _synergy: usize) {
+        let index = self.index(*self.tiers.last().unwrap(), goal);
+        self.counts.considered[index] += 1;
+    }
+}
+
+/// Generates the boards of the seeds and counts what's on them.
+fn count<M: DifficultyMapping>(
+    template: &Template,
+    mode: &M,
+    size: usize,
+    seeds: Range<u32>,
+) -> Result<Counts, (u32, GenerateError)> {
+    let mut counts = Counts::new(template, size);
+    for seed in seeds {
+        let mut counter = Counter {
+            template: template,
+            counts: &mut counts,
+            tiers: ArrayVec::new(),
+        };
+        let goals = generator::generate_goals(
+            seed.into(),
+            mode,
+            size,
+            &Constraints::default(),
+            template,
+            &mut counter,
+        )
+        .map_err(|e| (seed, e))?;
+        for (i, goal) in goals.iter().enumerate() {
+            let index = counter.index(counter.tiers[i], goal);
+            counter.counts.appearances[index] += 1;
+            counter.counts.positions[index * size * size + i] += 1;
+        }
+
+        for (line, counts) in Line::all(size).zip(&mut counts.lines) {
+            let synergy = generator::line_synergy(size, line, &goals);
+            if counts.len() <= synergy {
+                counts.resize(synergy + 1, 0);
+            }
+            counts[synergy] += 1;
+        }
+        counts.boards += 1;
+    }
+    Ok(counts)
+}
+
+impl<'a> Analysis<'a> {
+    /// Generates the boards of all the seeds in the range and analyzes them.
+    /// The boards are generated on one thread per core.
+    pub fn new<M>(
+        template: &'a Template,
+        mode: M,
+        size: usize,
+        seeds: Range<u32>,
+    ) -> Result<Self, GenerateError>
+    where
+        M: DifficultyMapping + Sync,
+    {
+        Analysis::with_threads(template, mode, size, seeds, 0)
+    }
+
+    /// Like `new`, but the boards are generated on the amount of threads. If
+    /// this is 0, there is one thread per core.
+    pub fn with_threads<M>(
+        template: &'a Template,
+        mode: M,
+        size: usize,
+        seeds: Range<u32>,
+        threads: usize,
+    ) -> Result<Self, GenerateError>
+    where
+        M: DifficultyMapping + Sync,
+    {
+        let threads = match threads {
+            0 => thread::available_parallelism().map_or(1, |n| n.get()),
+            threads => threads,
+        } as u64;
+        let len = u64::from(seeds.end.saturating_sub(seeds.start));
+        let chunk_len = ((len + threads - 1) / threads).max(1);
+
+        let results = thread::scope(|scope| {
+            let mut handles = Vec::new();
+            let mut start = u64::from(seeds.start);
+            while start < u64::from(seeds.end) {
+                let end = u64::from(seeds.end).min(start + chunk_len);
+                let chunk = start as u32..end as u32;
+                let mode = &mode;
+                handles.push(scope.spawn(move || count(template, mode, size, chunk)));
+                start = end;
+            }
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        // The chunks are in order, so the first error is the one of the
+        // lowest seed.
+        let mut counts = Counts::new(template, size);
+        for result in results {
+            counts.add(result.map_err(|(_, e)| e)?);
+        }
+        Ok(Analysis::from_counts(template, size, counts))
+    }
+
+    fn from_counts(template: &'a Template, size: usize, counts: Counts) -> Self {
+        let cells = size * size;
+        let goals = template
+            .0
+            .iter()
+            .enumerate()
+            .flat_map(|(tier, goals)| goals.iter().map(move |goal| (tier, goal)))
+            .enumerate()
+            .map(|(i, (tier, goal))| GoalStats {
+                name: &goal.name,
+                tier: tier,
+                appearances: counts.appearances[i],
+                positions: counts.positions[i * cells..(i + 1) * cells].to_vec(),
+                considered: counts.considered[i],
+            })
+            .collect::<Vec<_>>();
+
+        let mut types = Vec::<TypeStats>::new();
+        for (stats, goal) in goals
+            .iter()
+            .zip(template.0.iter().flat_map(|tier| tier.iter()))
+        {
+            for goal_type in &goal.types {
+                match types.iter().position(|t| t.name == &**goal_type) {
+                    Some(i) => types[i].appearances += stats.appearances,
+                    None => types.push(TypeStats {
+                        name: goal_type,
+                        appearances: stats.appearances,
+                    }),
+                }
+            }
+        }
+        types.sort_by(|a, b| b.appearances.cmp(&a.appearances).then(a.name.cmp(b.name)));
+
+        let lines = Line::all(size)
+            .zip(counts.lines)
+            .map(|(line, synergy)| LineStats {
+                line: line,
+                synergy: synergy,
+            })
+            .collect();
+
+        let excluded = goals
+            .iter()
+            .filter(|g| g.appearances == 0 && g.considered > 0)
+            .map(|g| g.name)
+            .collect();
+
+        Analysis {
+            size: size,
+            boards: counts.boards,
+            goals: goals,
+            types: types,
+            lines: lines,
+            excluded: excluded,
+        }
+    }
+
+    pub fn to_json_string(&self) -> JsonResult<String> {
+        serde_json::to_string(self)
+    }
+
+    pub fn to_json_string_pretty(&self) -> JsonResult<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// One row per goal with how often it appears, both as a count and as
+    /// the share of the boards.
+    pub fn goals_csv(&self) -> String {
+        let mut csv = String::from("goal,tier,appearances,frequency,considered\n");
+        for goal in &self.goals {
+            push_field(&mut csv, goal.name);
+            writeln!(
+                csv,
+                ",{},{},{},{}",
+                goal.tier,
+                goal.appearances,
+                self.frequency(goal.appearances),
+                goal.considered
+            )
+            .unwrap();
+        }
+        csv
+    }
+
+    /// One row per goal with how often it appears in each cell, where the
+    /// columns are named after the cells' column and row, starting at 0.
+    pub fn positions_csv(&self) -> String {
+        let mut csv = String::from("goal");
+        for i in 0..self.size * self.size {
+            write!(csv, ",x{}y{}", i % self.size, i / self.size).unwrap();
+        }
+        csv.push('\n');
+        for goal in &self.goals {
+            push_field(&mut csv, goal.name);
+            for count in &goal.positions {
+                write!(csv, ",{}", count).unwrap();
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// One row per type with how many goals of the type there are, in total
+    /// and per board.
+    pub fn types_csv(&self) -> String {
+        let mut csv = String::from("type,appearances,per_board\n");
+        for goal_type in &self.types {
+            push_field(&mut csv, goal_type.name);
+            writeln!(
+                csv,
+                ",{},{}",
+                goal_type.appearances,
+                self.frequency(goal_type.appearances)
+            )
+            .unwrap();
+        }
+        csv
+    }
+
+    /// One row per line and amount of synergy with how many boards the line
+    /// has that synergy on.
+    pub fn lines_csv(&self) -> String {
+        let mut csv = String::from("line,synergy,boards\n");
+        for line in &self.lines {
+            for (synergy, count) in line.synergy.iter().enumerate() {
+                writeln!(csv, "{},{},{}", LineName(line.line), synergy, count).unwrap();
+            }
+        }
+        csv
+    }
+
+    fn frequency(&self, count: u64) -> f64 {
+        if self.boards == 0 {
+            0.0
+        } else {
+            count as f64 / self.boards as f64
+        }
+    }
+}
+
+impl<'a> fmt::Display for Analysis<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} boards", self.boards)?;
+
+        let mut goals = self.goals.iter().collect::<Vec<_>>();
+        goals.sort_by_key(|g| Reverse(g.appearances));
+        writeln!(f, "\nMost common goals:")?;
+        for goal in goals.iter().take(10) {
+            let frequency = self.frequency(goal.appearances) * 100.0;
+            writeln!(f, "  {:6.2}%  {}", frequency, goal.name)?;
+        }
+        writeln!(f, "\nLeast common goals:")?;
+        for goal in goals.iter().rev().take(10) {
+            let frequency = self.frequency(goal.appearances) * 100.0;
+            writeln!(f, "  {:6.2}%  {}", frequency, goal.name)?;
+        }
+
+        writeln!(f, "\nTypes per board:")?;
+        for goal_type in &self.types {
+            let per_board = self.frequency(goal_type.appearances);
+            writeln!(f, "  {:6.2}  {}", per_board, goal_type.name)?;
+        }
+
+        writeln!(f, "\nLine synergy:")?;
+        for line in &self.lines {
+            let name = LineName(line.line).to_string();
+            writeln!(
+                f,
+                "  {:14}  mean {:5.2}  max {}",
+                name,
+                line.mean(),
+                line.max()
+            )?;
+        }
+
+        if !self.excluded.is_empty() {
+            writeln!(f, "\nNever picked because of synergy:")?;
+            for name in &self.excluded {
+                writeln!(f, "  {}", name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes a line like `row 1` or `column 3`, counting from 1 like players do.
+struct LineName(Line);
+
+impl fmt::Display for LineName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Line::Row(y) => write!(f, "row {}", y + 1),
+            Line::Column(x) => write!(f, "column {}", x + 1),
+            Line::TopLeftToBottomRight => f.write_str("tl-br"),
+            Line::BottomLeftToTopRight => f.write_str("bl-tr"),
+        }
+    }
+}
+
+/// Writes the field, quoted if it contains any characters that need quoting.
+fn push_field(csv: &mut String, field: &str) {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        csv.push('"');
+        csv.push_str(&field.replace('"', "\"\""));
+        csv.push('"');
+    } else {
+        csv.push_str(field);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Mode;
+
+    fn sm64() -> Template {
+        Template::from_json_str(include_str!("templates/sm64.json")).unwrap()
+    }
+
+    #[test]
+    fn counts() {
+        let template = sm64();
+        let analysis = Analysis::new(&template, Mode::Normal, 5, 0..200).unwrap();
+        assert_eq!(analysis.boards, 200);
+        assert_eq!(analysis.lines.len(), 12);
+        assert_eq!(
+            analysis.goals.len(),
+            template.0.iter().map(|tier| tier.len()).sum::<usize>()
+        );
+        let cells = analysis.goals.iter().map(|g| g.appearances).sum::<u64>();
+        assert_eq!(cells, 200 * 25);
+        for goal in &analysis.goals {
+            assert_eq!(goal.positions.iter().sum::<u64>(), goal.appearances);
+            assert!(goal.considered >= goal.appearances);
+        }
+        for line in &analysis.lines {
+            assert_eq!(line.synergy.iter().sum::<u64>(), 200);
+        }
+        let types = analysis
+            .types
+            .iter()
+            .map(|t| t.appearances)
+            .collect::<Vec<_>>();
+        assert!(types.windows(2).all(|w| w[0] >= w[1]));
+
+        // The counts match the boards generated one by one.
+        let board = template.generate(123, Mode::Normal);
+        let single = Analysis::new(&template, Mode::Normal, 5, 123..124).unwrap();
+        for (i, &name) in board.cells.iter().enumerate() {
+            let goal = single.goals.iter().find(|g| g.name == name).unwrap();
+            assert_eq!(goal.positions[i], 1);
+        }
+    }
+
+    #[test]
+    fn excluded() {
+        let template = Template::from_json_str(
+            r#"[
+                [{ "name": "A", "types": ["WF"] }, { "name": "B", "types": ["WF"] }],
+                [{ "name": "C", "types": ["BOB"] }]
+            ]"#,
+        )
+        .unwrap();
+        // A got considered, but always lost against B. C never got
+        // considered, so it's missing for another reason.
+        let mut counts = Counts::new(&template, 1);
+        counts.boards = 5;
+        counts.appearances = vec![0, 5, 0];
+        counts.positions = vec![0, 5, 0];
+        counts.considered = vec![3, 5, 0];
+        let analysis = Analysis::from_counts(&template, 1, counts);
+        assert_eq!(analysis.excluded, ["A"]);
+        assert_eq!(analysis.goals[2].tier, 1);
+        assert_eq!(
+            analysis.types,
+            [
+                TypeStats {
+                    name: "WF",
+                    appearances: 5,
+                },
+                TypeStats {
+                    name: "BOB",
+                    appearances: 0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn starved() {
+        // The corners of 3x3 boards are always drawn from the tiers 2, 8, 13
+        // and 19, and the center from tier 11. A shares a type with the
+        // corners, so it always has more synergy in the center than B and is
+        // never picked, even though it gets considered.
+        let goal = |name: &str, types: &[&str]| Goal {
+            name: name.to_owned(),
+            types: types.iter().map(|&t| t.to_owned()).collect(),
+            special: false,
+            icon: None,
+        };
+        let template = Template(
+            (0..25)
+                .map(|tier| match tier {
+                    2 | 8 | 13 | 19 => vec![goal(&format!("Corner {}", tier), &["Stars"])],
+                    11 => vec![goal("A", &["Stars"]), goal("B", &[])],
+                    _ => vec![goal(&format!("Edge {}", tier), &[])],
+                })
+                .collect(),
+            None,
+        );
+        let analysis = Analysis::with_threads(&template, Mode::Normal, 3, 0..100, 2).unwrap();
+        assert_eq!(analysis.excluded, ["A"]);
+        let b = analysis.goals.iter().find(|g| g.name == "B").unwrap();
+        assert_eq!(b.appearances, 100);
+        assert_eq!(b.positions[4], 100);
+
+        // The amount of threads doesn't change the results.
+        assert_eq!(
+            Analysis::with_threads(&template, Mode::Normal, 3, 0..100, 1).unwrap(),
+            analysis
+        );
+        assert_eq!(
+            Analysis::with_threads(&template, Mode::Normal, 3, 0..100, 7).unwrap(),
+            analysis
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let mut template = sm64();
+        template.0.truncate(20);
+        assert_eq!(
+            Analysis::new(&template, Mode::Normal, 5, 0..100).err(),
+            template.try_generate(0, Mode::Normal).err()
+        );
+    }
+
+    #[test]
+    fn export() {
+        let template =
+            Template::from_json_str(r#"[[{ "name": "Coins, \"100\"", "types": ["coins"] }]]"#)
+                .unwrap();
+        let analysis = Analysis::new(&template, Mode::Short, 1, 0..4).unwrap();
+        assert_eq!(
+            analysis.goals_csv(),
+            "goal,tier,appearances,frequency,considered\n\"Coins, \"\"100\"\"\",0,4,1,4\n"
+        );
+        assert_eq!(
+            analysis.positions_csv(),
+            "goal,x0y0\n\"Coins, \"\"100\"\"\",4\n"
+        );
+        assert_eq!(
+            analysis.types_csv(),
+            "type,appearances,per_board\ncoins,4,1\n"
+        );
+        assert_eq!(
+            analysis.lines_csv(),
+            "line,synergy,boards\nrow 1,0,4\ncolumn 1,0,4\ntl-br,0,4\nbl-tr,0,4\n"
+        );
+        assert_eq!(
+            analysis.to_json_string().unwrap(),
+            concat!(
+                r#"{"size":1,"boards":4,"goals":[{"name":"Coins, \"100\"","tier":0,"#,
+                r#""appearances":4,"positions":[4],"considered":4}],"#,
+                r#""types":[{"name":"coins","appearances":4}],"lines":["#,
+                r#"{"line":{"Row":0},"synergy":[4]},{"line":{"Column":0},"synergy":[4]},"#,
+                r#"{"line":"TopLeftToBottomRight","synergy":[4]},"#,
+                r#"{"line":"BottomLeftToTopRight","synergy":[4]}],"excluded":[]}"#
+            )
+        );
+    }
+}
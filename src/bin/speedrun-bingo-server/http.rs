@@ -0,0 +1,254 @@
+//! Just enough of HTTP/1.1 to serve boards. Every connection carries a single
+//! request without a body and is closed after the response.
+
+use std::io::{self, BufRead, Write};
+use std::sync::Arc;
+
+/// The most bytes the request line and the headers may take up together.
+const MAX_HEAD_LEN: u64 = 16 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub method: String,
+    /// The path and the query as they are sent.
+    pub target: String,
+    /// The percent-decoded segments of the path.
+    pub path: Vec<String>,
+    /// The percent-decoded parameters of the query.
+    pub query: Vec<(String, String)>,
+    pub headers: Vec<(String, String)>,
+}
+
+impl Request {
+    pub fn query(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|param| param.0 == name)
+            .map(|param| &*param.1)
+    }
+
+    /// Looks up a header, ignoring the case of its name.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|header| header.0.eq_ignore_ascii_case(name))
+            .map(|header| &*header.1)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    /// The body, which is shared with the cache.
+    pub body: Arc<Vec<u8>>,
+}
+
+impl Response {
+    pub fn new(status: u16, content_type: &'static str, body: Vec<u8>) -> Self {
+        Response {
+            status: status,
+            content_type: content_type,
+            body: Arc::new(body),
+        }
+    }
+
+    /// A response with a plain text message, which is how errors are reported.
+    pub fn text(status: u16, message: &str) -> Self {
+        Response::new(status, "text/plain; charset=utf-8", message.into())
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Reads the request line and the headers. The body is never read, as none of
+/// the routes accept one.
+pub fn read_request<R: BufRead>(reader: R) -> io::Result<Request> {
+    let mut lines = reader.take(MAX_HEAD_LEN).lines();
+    let mut next_line = || match lines.next() {
+        Some(line) => line.map(|line| line.trim_end_matches('\r').to_owned()),
+        None => Err(invalid("The request ended early")),
+    };
+
+    let request_line = next_line()?;
+    let mut parts = request_line.split(' ');
+    let (method, target) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(target), Some(version), None)
+            if !method.is_empty() && target.starts_with('/') && version.starts_with("HTTP/") =>
+        {
+            (method, target)
+        }
+        _ => return Err(invalid("The request line is malformed")),
+    };
+
+    let (path, query) = match target.find('?') {
+        Some(i) => (&target[..i], &target[i + 1..]),
+        None => (target, ""),
+    };
+    let path = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| decode(segment, false))
+        .collect::<Option<_>>()
+        .ok_or_else(|| invalid("The path is not percent-encoded correctly"))?;
+    let query = query
+        .split('&')
+        .filter(|param| !param.is_empty())
+        .map(|param| {
+            let (name, value) = match param.find('=') {
+                Some(i) => (&param[..i], &param[i + 1..]),
+                None => (param, ""),
+            };
+            Some((decode(name, true)?, decode(value, true)?))
+        })
+        .collect::<Option<_>>()
+        .ok_or_else(|| invalid("The query is not percent-encoded correctly"))?;
+
+    let mut headers = Vec::new();
+    loop {
+        let line = next_line()?;
+        if line.is_empty() {
+            break;
+        }
+        match line.find(':') {
+            Some(i) => headers.push((line[..i].to_owned(), line[i + 1..].trim().to_owned())),
+            None => return Err(invalid("A header is malformed")),
+        }
+    }
+
+    Ok(Request {
+        method: method.to_owned(),
+        target: target.to_owned(),
+        path: path,
+        query: query,
+        headers: headers,
+    })
+}
+
+pub fn write_response<W: Write>(mut writer: W, response: &Response) -> io::Result<()> {
+    write!(
+        writer,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        reason(response.status),
+        response.content_type,
+        response.body.len()
+    )?;
+    writer.write_all(&response.body)?;
+    writer.flush()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        500 => "Internal Server Error",
+        _ => "",
+    }
+}
+
+/// Decodes the percent-encoded text, which also encodes spaces as `+` in
+/// queries. Returns `None` if the encoding or the UTF-8 is invalid.
+pub fn decode(text: &str, plus_as_space: bool) -> Option<String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.bytes();
+    while let Some(b) = rest.next() {
+        bytes.push(match b {
+            b'%' => {
+                let high = (rest.next()? as char).to_digit(16)?;
+                let low = (rest.next()? as char).to_digit(16)?;
+                (high * 16 + low) as u8
+            }
+            b'+' if plus_as_space => b' ',
+            b => b,
+        });
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Percent-encodes the text so it can be used as a path segment or a query
+/// value.
+pub fn encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for b in text.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(b as char)
+            }
+            b => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request() {
+        let request = read_request(
+            &b"GET /boards/sm64/Final%20Race.svg?mode=long&x=a+b%2B HTTP/1.1\r\n\
+               Host: localhost\r\nACCEPT:  text/html \r\n\r\nignored body"[..],
+        )
+        .unwrap();
+        assert_eq!(request.method, "GET");
+        assert_eq!(
+            request.target,
+            "/boards/sm64/Final%20Race.svg?mode=long&x=a+b%2B"
+        );
+        assert_eq!(request.path, ["boards", "sm64", "Final Race.svg"]);
+        assert_eq!(request.query("mode"), Some("long"));
+        assert_eq!(request.query("x"), Some("a b+"));
+        assert_eq!(request.query("seed"), None);
+        assert_eq!(request.header("Accept"), Some("text/html"));
+        assert_eq!(request.header("host"), Some("localhost"));
+    }
+
+    #[test]
+    fn invalid_requests() {
+        for request in &[
+            &b""[..],
+            b"GET / HTTP/1.1\r\n",
+            b"GET /\r\n\r\n",
+            b"GET boards HTTP/1.1\r\n\r\n",
+            b"GET /%ff HTTP/1.1\r\n\r\n",
+            b"GET /?mode=%4 HTTP/1.1\r\n\r\n",
+            b"GET / HTTP/1.1\r\nHost\r\n\r\n",
+        ] {
+            let error = read_request(*request).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+        let long = format!(
+            "GET /{} HTTP/1.1\r\n\r\n",
+            "a".repeat(MAX_HEAD_LEN as usize)
+        );
+        assert!(read_request(long.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn response() {
+        let mut written = Vec::new();
+        write_response(&mut written, &Response::text(404, "Unknown template")).unwrap();
+        assert_eq!(
+            String::from_utf8(written).unwrap(),
+            "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain; charset=utf-8\r\n\
+             Content-Length: 16\r\nConnection: close\r\n\r\nUnknown template"
+        );
+    }
+
+    #[test]
+    fn encoding() {
+        assert_eq!(encode("Final Race/2 ★"), "Final%20Race%2F2%20%E2%98%85");
+        assert_eq!(
+            decode("Final%20Race%2F2%20%E2%98%85", false).unwrap(),
+            "Final Race/2 ★"
+        );
+        assert_eq!(decode("a+b", false).unwrap(), "a+b");
+        assert_eq!(decode("%e2%98", false), None);
+    }
+}
@@ -0,0 +1,160 @@
+extern crate image;
+extern crate serde_json;
+extern crate speedrun_bingo;
+
+#[path = "../speedrun-bingo/args.rs"]
+mod args;
+mod http;
+mod rooms;
+mod service;
+mod websocket;
+
+use args::Options;
+use rooms::Rooms;
+use service::Service;
+use std::io::BufReader;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use std::{env, fs, process, thread};
+
+const USAGE: &str = "\
+Usage: speedrun-bingo-server --templates <directory> --font <file> [--address <address>]
+
+Serves the boards of the templates in the directory, which are the JSON files
+in it, named after the files without their extension:
+
+  GET /boards                              The names of the templates
+  GET /boards/{template}/{seed}?mode=normal  A board as JSON or HTML
+  GET /boards/{template}/{seed}.png          A board as PNG, same for .svg,
+                                             .json and .html
+  GET /rooms/{room}?template=sm64&seed=5     A WebSocket to race in the room,
+                                             which is created by the first
+                                             player
+
+Options:
+  --templates <directory>  The directory with the templates
+  --font <file>            The TrueType font the images are drawn with
+  --address <address>      The address to listen on, defaults to 127.0.0.1:8080
+";
+
+/// How long a connection may take to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if args.is_empty() || args[0] == "help" || args[0] == "--help" || args[0] == "-h" {
+        print!("{}", USAGE);
+        return;
+    }
+    if let Err(e) = run(&args) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let options = Options::parse(args, &["templates", "font", "address"])?;
+    let font_path = options.require("font")?;
+    let font = fs::read(font_path).map_err(|e| format!("Can't read {}: {}", font_path, e))?;
+    let mut service = Service::new(font);
+    let count = load_templates(&mut service, Path::new(options.require("templates")?))?;
+    if count == 0 {
+        return Err("The directory doesn't contain any templates".into());
+    }
+
+    let address = options
+        .parse_value::<SocketAddr>("address")?
+        .unwrap_or_else(|| ([127, 0, 0, 1], 8080).into());
+    let listener =
+        TcpListener::bind(address).map_err(|e| format!("Can't listen on {}: {}", address, e))?;
+    eprintln!("Serving {} templates on http://{}", count, address);
+
+    listen(listener, Arc::new(Server::new(service)));
+    Ok(())
+}
+
+struct Server {
+    service: Service,
+    rooms: Rooms,
+}
+
+impl Server {
+    fn new(service: Service) -> Self {
+        Server {
+            service: service,
+            rooms: Rooms::default(),
+        }
+    }
+}
+
+/// Serves every connection on its own thread.
+fn listen(listener: TcpListener, server: Arc<Server>) {
+    let ticker = server.clone();
+    thread::spawn(move || ticker.rooms.tick());
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let server = server.clone();
+                thread::spawn(move || serve(&server, stream));
+            }
+            Err(e) => eprintln!("Can't accept a connection: {}", e),
+        }
+    }
+}
+
+/// Adds all the JSON files in the directory as templates and returns how many
+/// there are.
+fn load_templates(service: &mut Service, directory: &Path) -> Result<usize, String> {
+    let error = |e| format!("Can't read {}: {}", directory.display(), e);
+    let mut count = 0;
+    for entry in fs::read_dir(directory).map_err(&error)? {
+        let path = entry.map_err(&error)?.path();
+        if path.extension().and_then(|extension| extension.to_str()) != Some("json") {
+            continue;
+        }
+        let name = match path.file_stem().and_then(|name| name.to_str()) {
+            Some(name) => name.to_owned(),
+            None => continue,
+        };
+        let json = fs::read_to_string(&path)
+            .map_err(|e| format!("Can't read {}: {}", path.display(), e))?;
+        service.add_template(&name, &json)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn serve(server: &Server, stream: TcpStream) {
+    let response = match stream
+        .set_read_timeout(Some(READ_TIMEOUT))
+        .and_then(|_| http::read_request(BufReader::new(&stream)))
+    {
+        Ok(request) => {
+            let response = match request.path.first().map(|s| &**s) {
+                Some("rooms") if request.path.len() == 2 => {
+                    let name = &request.path[1];
+                    match server
+                        .rooms
+                        .connect(&server.service, &request, name, &stream)
+                    {
+                        Ok(()) => {
+                            eprintln!("{} {} closed", request.method, request.target);
+                            return;
+                        }
+                        Err(response) => response,
+                    }
+                }
+                _ => server.service.handle(&request),
+            };
+            eprintln!("{} {} {}", request.method, request.target, response.status);
+            response
+        }
+        Err(e) => http::Response::text(400, &e.to_string()),
+    };
+    if let Err(e) = http::write_response(&stream, &response) {
+        eprintln!("Can't send a response: {}", e);
+    }
+}
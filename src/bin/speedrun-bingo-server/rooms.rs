@@ -0,0 +1,411 @@
+//! Connects players to rooms over WebSockets. A player connects to
+//! `/rooms/{room}?template=sm64&seed=5&mode=normal`, which creates the room
+//! if it doesn't exist yet. The players then send commands as JSON, like
+//! `{"Join":{"name":"Alice","team":"Red"}}`, and every player receives the
+//! resulting updates. The room is closed once every player left.
+
+use http::{Request, Response};
+use serde_json::{self, Value};
+use service::{parse_mode, Service};
+use speedrun_bingo::{Command, PlayerId, Room, Seed, Update};
+use std::collections::HashMap;
+use std::net::{Shutdown, TcpStream};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use websocket::{self, Message, Receiver};
+
+/// How often the rooms check whether their countdowns ended.
+const TICK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What gets sent to a client.
+enum Frame {
+    Text(String),
+    Pong(Vec<u8>),
+    Close,
+}
+
+struct Client {
+    player: PlayerId,
+    frames: Sender<Frame>,
+}
+
+struct Entry {
+    room: Room,
+    clients: Vec<Client>,
+}
+
+impl Entry {
+    fn broadcast(&self, update: &Update) {
+        let json = serde_json::to_string(update).unwrap();
+        for client in &self.clients {
+            // Clients that disconnected are removed by their own thread.
+            let _ = client.frames.send(Frame::Text(json.clone()));
+        }
+    }
+
+    /// Sends the goals that became visible to the players of the teams they
+    /// are for.
+    fn reveal(&mut self, time: u64) {
+        for (team, update) in self.room.tick(time) {
+            let json = serde_json::to_string(&update).unwrap();
+            for client in &self.clients {
+                if self.room.player(client.player).map(|p| p.team) == team {
+                    let _ = client.frames.send(Frame::Text(json.clone()));
+                }
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Rooms {
+    rooms: Mutex<HashMap<String, Entry>>,
+    next_player: Mutex<PlayerId>,
+}
+
+/// The milliseconds since the Unix epoch, which are the times of the rooms.
+fn now() -> u64 {
+    let elapsed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis())
+}
+
+fn error(message: &str) -> String {
+    let mut json = serde_json::Map::new();
+    json.insert("Error".into(), message.into());
+    Value::Object(json).to_string()
+}
+
+impl Rooms {
+    /// Reveals the boards of the rooms whose countdowns ended, forever.
+    pub fn tick(&self) {
+        loop {
+            let time = now();
+            for entry in self.rooms.lock().unwrap().values_mut() {
+                entry.reveal(time);
+            }
+            thread::sleep(TICK_INTERVAL);
+        }
+    }
+
+    /// Upgrades the connection to a WebSocket and lets the player take part
+    /// in the room until the connection is closed. If the request isn't a
+    /// valid request for a room, the response is returned instead.
+    pub fn connect(
+        &self,
+        service: &Service,
+        request: &Request,
+        name: &str,
+        stream: &TcpStream,
+    ) -> Result<(), Response> {
+        let key = match (
+            request.header("Upgrade"),
+            request.header("Sec-WebSocket-Key"),
+        ) {
+            (Some(upgrade), Some(key)) if upgrade.eq_ignore_ascii_case("websocket") => key,
+            _ => {
+                return Err(Response::text(
+                    400,
+                    "Rooms can only be joined over WebSockets",
+                ))
+            }
+        };
+        let mut writer = stream
+            .try_clone()
+            .map_err(|e| Response::text(500, &e.to_string()))?;
+
+        let player = {
+            let mut next_player = self.next_player.lock().unwrap();
+            *next_player += 1;
+            *next_player
+        };
+        let (sender, frames) = channel();
+        {
+            let mut rooms = self.rooms.lock().unwrap();
+            if !rooms.contains_key(name) {
+                let room = create_room(service, request)?;
+                let entry = Entry {
+                    room: room,
+                    clients: Vec::new(),
+                };
+                rooms.insert(name.to_owned(), entry);
+            }
+            let entry = rooms.get_mut(name).unwrap();
+            let state = serde_json::to_string(&entry.room.state(player, now())).unwrap();
+            sender.send(Frame::Text(state)).unwrap();
+            entry.clients.push(Client {
+                player: player,
+                frames: sender.clone(),
+            });
+        }
+
+        // If the handshake fails, so does reading from the connection, which
+        // removes the player again.
+        let _ = websocket::write_handshake(stream, key);
+        thread::spawn(move || {
+            for frame in frames {
+                let result = match frame {
+                    Frame::Text(text) => {
+                        websocket::write_frame(&mut writer, websocket::TEXT, text.as_bytes(), None)
+                    }
+                    Frame::Pong(payload) => {
+                        websocket::write_frame(&mut writer, websocket::PONG, &payload, None)
+                    }
+                    Frame::Close => {
+                        let _ = websocket::write_frame(&mut writer, websocket::CLOSE, &[], None);
+                        break;
+                    }
+                };
+                if result.is_err() {
+                    break;
+                }
+            }
+            let _ = writer.shutdown(Shutdown::Both);
+        });
+
+        let _ = stream.set_read_timeout(None);
+        let mut receiver = Receiver::new(stream);
+        loop {
+            let text = match receiver.receive() {
+                Ok(Message::Text(text)) => text,
+                Ok(Message::Ping(payload)) => {
+                    let _ = sender.send(Frame::Pong(payload));
+                    continue;
+                }
+                Ok(Message::Pong) => continue,
+                Ok(Message::Close) | Err(_) => break,
+            };
+            let command = match serde_json::from_str::<Command>(&text) {
+                Ok(command) => command,
+                Err(e) => {
+                    let _ = sender.send(Frame::Text(error(&e.to_string())));
+                    continue;
+                }
+            };
+
+            let time = now();
+            let mut rooms = self.rooms.lock().unwrap();
+            let entry = rooms.get_mut(name).unwrap();
+            let is_join = matches!(command, Command::Join { .. });
+            match entry.room.handle(player, command, time) {
+                Ok(update) => {
+                    entry.broadcast(&update);
+                    // The team may already see more goals than the player
+                    // did without a team.
+                    if is_join {
+                        let state = entry.room.state(player, time);
+                        let _ = sender.send(Frame::Text(serde_json::to_string(&state).unwrap()));
+                    }
+                }
+                Err(e) => {
+                    let _ = sender.send(Frame::Text(error(&e.to_string())));
+                }
+            }
+            // A race without a countdown starts right away and marks may
+            // reveal cells.
+            entry.reveal(time);
+        }
+
+        let mut rooms = self.rooms.lock().unwrap();
+        let is_empty = {
+            let entry = rooms.get_mut(name).unwrap();
+            entry.clients.retain(|client| client.player != player);
+            if let Some(update) = entry.room.leave(player) {
+                entry.broadcast(&update);
+            }
+            entry.clients.is_empty()
+        };
+        if is_empty {
+            rooms.remove(name);
+        }
+        let _ = sender.send(Frame::Close);
+        Ok(())
+    }
+}
+
+fn create_room(service: &Service, request: &Request) -> Result<Room, Response> {
+    let template = request
+        .query("template")
+        .and_then(|name| service.template(name))
+        .ok_or_else(|| Response::text(404, "The template is unknown"))?;
+    let seed = request
+        .query("seed")
+        .ok_or_else(|| Response::text(400, "The seed is missing"))?;
+    let mode = parse_mode(request.query("mode").unwrap_or("normal"))
+        .ok_or_else(|| Response::text(400, "The mode is unknown"))?;
+    let board = template
+        .try_generate(Seed::from(seed), mode)
+        .map_err(|e| Response::text(500, &e.to_string()))?;
+    Ok(Room::new(&board))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::read_request;
+    use speedrun_bingo::{Mode, Template};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::{SocketAddr, TcpListener};
+    use std::sync::Arc;
+    use websocket::accept_key;
+    use {listen, Server};
+
+    /// Starts a server on a free port of the loopback interface.
+    fn spawn_server() -> SocketAddr {
+        let mut service = Service::new(Vec::new());
+        let json = include_str!("../../templates/sm64.json");
+        service.add_template("sm64", json).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || listen(listener, Arc::new(Server::new(service))));
+        address
+    }
+
+    struct TestClient {
+        receiver: Receiver<TcpStream>,
+        stream: TcpStream,
+    }
+
+    impl TestClient {
+        fn connect(address: SocketAddr, target: &str) -> Result<Self, String> {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            let key = "dGhlIHNhbXBsZSBub25jZQ==";
+            write!(
+                stream,
+                "GET {} HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\n\
+                 Connection: Upgrade\r\nSec-WebSocket-Key: {}\r\n\
+                 Sec-WebSocket-Version: 13\r\n\r\n",
+                target, key
+            )
+            .unwrap();
+
+            // The handshake is read byte by byte, so no frames get buffered.
+            let mut head = Vec::new();
+            let mut reader = BufReader::with_capacity(1, stream.try_clone().unwrap());
+            while !head.ends_with(b"\r\n\r\n") {
+                let mut line = Vec::new();
+                if reader.read_until(b'\n', &mut line).unwrap() == 0 {
+                    break;
+                }
+                head.extend(line);
+            }
+            let head = String::from_utf8(head).unwrap();
+            if !head.starts_with("HTTP/1.1 101 ") {
+                return Err(head.lines().next().unwrap_or("").to_owned());
+            }
+            assert!(head.contains(&format!("Sec-WebSocket-Accept: {}\r\n", accept_key(key))));
+
+            Ok(TestClient {
+                receiver: Receiver::from_server(stream.try_clone().unwrap()),
+                stream: stream,
+            })
+        }
+
+        fn send(&mut self, json: &str) {
+            websocket::write_frame(
+                &mut self.stream,
+                websocket::TEXT,
+                json.as_bytes(),
+                Some([1, 2, 3, 4]),
+            )
+            .unwrap();
+        }
+
+        fn receive(&mut self) -> Value {
+            match self.receiver.receive().unwrap() {
+                Message::Text(text) => serde_json::from_str(&text).unwrap(),
+                message => panic!("{:?}", message),
+            }
+        }
+    }
+
+    #[test]
+    fn race() {
+        let address = spawn_server();
+        let target = "/rooms/final?template=sm64&seed=587062&mode=long";
+        let mut alice = TestClient::connect(address, target).unwrap();
+        let state = alice.receive();
+        assert_eq!(state["State"]["size"], 5);
+        assert_eq!(state["State"]["goals"], Value::Null);
+
+        // The room already exists, so the query doesn't matter anymore.
+        let mut bob = TestClient::connect(address, "/rooms/final").unwrap();
+        bob.receive();
+
+        alice.send(r#"{"Join":{"name":"Alice","team":"Red"}}"#);
+        assert_eq!(alice.receive(), bob.receive());
+        assert_eq!(alice.receive()["State"]["players"][0]["name"], "Alice");
+        bob.send(r#"{"Join":{"name":"Bob","team":"Blue"}}"#);
+        let joined = alice.receive();
+        assert_eq!(joined["Joined"]["player"]["team"], 1);
+        assert_eq!(joined["Joined"]["team_name"], "Blue");
+        bob.receive();
+        bob.receive();
+
+        alice.send(r#"{"Mark":{"x":0,"y":0}}"#);
+        assert_eq!(alice.receive()["Error"], "The board is not revealed yet");
+        alice.send("Start");
+        assert!(alice.receive()["Error"].is_string());
+
+        alice.send(r#"{"Start":{"countdown":200}}"#);
+        let starting = alice.receive();
+        assert!(starting["Starting"]["at"].is_u64());
+        assert_eq!(bob.receive(), starting);
+
+        let revealed = bob.receive();
+        let template = Template::from_json_str(include_str!("../../templates/sm64.json")).unwrap();
+        let board = template.generate(587062, Mode::Long);
+        let goals = revealed["Revealed"]["goals"].as_array().unwrap();
+        assert_eq!(goals.len(), 25);
+        assert_eq!(goals[7], board.cells[7]);
+        assert_eq!(alice.receive(), revealed);
+
+        bob.send(r#"{"Mark":{"x":2,"y":1}}"#);
+        let marked = alice.receive();
+        assert_eq!(marked["Marked"]["team"], 1);
+        assert_eq!(marked["Marked"]["x"], 2);
+        assert_eq!(bob.receive(), marked);
+
+        drop(bob);
+        assert_eq!(
+            alice.receive()["Left"]["player"],
+            joined["Joined"]["player"]["id"]
+        );
+
+        // A player that connects later sees the board and the marks.
+        let mut carol = TestClient::connect(address, "/rooms/final").unwrap();
+        let state = carol.receive();
+        assert_eq!(state["State"]["goals"].as_array().unwrap().len(), 25);
+        assert!(state["State"]["teams"][1]["state"]["marks"][7].is_u64());
+        assert_eq!(state["State"]["players"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn invalid_rooms() {
+        let address = spawn_server();
+        for &(target, status) in &[
+            ("/rooms/a", "HTTP/1.1 404 Not Found"),
+            ("/rooms/a?template=sm63&seed=5", "HTTP/1.1 404 Not Found"),
+            ("/rooms/a?template=sm64", "HTTP/1.1 400 Bad Request"),
+            (
+                "/rooms/a?template=sm64&seed=5&mode=x",
+                "HTTP/1.1 400 Bad Request",
+            ),
+        ] {
+            assert_eq!(TestClient::connect(address, target).err().unwrap(), status);
+        }
+
+        let service = Service::new(Vec::new());
+        let request = read_request(&b"GET /rooms/a HTTP/1.1\r\n\r\n"[..]).unwrap();
+        let stream = TcpStream::connect(address).unwrap();
+        let rooms = Rooms::default();
+        let response = rooms.connect(&service, &request, "a", &stream).unwrap_err();
+        assert_eq!(response.status, 400);
+    }
+}
@@ -0,0 +1,589 @@
+use http::{encode, Request, Response};
+use image::png::PNGEncoder;
+use image::ColorType;
+use serde_json::{self, Value};
+use speedrun_bingo::{try_render, try_render_svg, Bingo, Mode, RenderOptions, Seed, Template};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+/// The most boards whose responses are kept in the cache. The boards that
+/// were cached first are dropped once there are more.
+const MAX_CACHED_BOARDS: usize = 256;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Format {
+    Json,
+    Png,
+    Svg,
+    Html,
+}
+
+impl Format {
+    fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "json" => Some(Format::Json),
+            "png" => Some(Format::Png),
+            "svg" => Some(Format::Svg),
+            "html" => Some(Format::Html),
+            _ => None,
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::Png => "image/png",
+            Format::Svg => "image/svg+xml",
+            Format::Html => "text/html; charset=utf-8",
+        }
+    }
+}
+
+struct LoadedTemplate {
+    /// The hash of the template's JSON, so the cached boards of a template
+    /// can't be mixed up with the ones of a template that replaced it.
+    hash: u64,
+    template: Template,
+}
+
+/// Identifies a board by the template's hash, the seed and the mode.
+type Key = (u64, String, Mode);
+/// The bodies of the formats a board has been requested in so far.
+type Bodies = Vec<(Format, Arc<Vec<u8>>)>;
+
+#[derive(Default)]
+struct Cache {
+    boards: HashMap<Key, Bodies>,
+    /// The keys in the order they were added.
+    order: VecDeque<Key>,
+}
+
+impl Cache {
+    fn get(&self, key: &Key, format: Format) -> Option<Arc<Vec<u8>>> {
+        let formats = self.boards.get(key)?;
+        formats
+            .iter()
+            .find(|cached| cached.0 == format)
+            .map(|cached| cached.1.clone())
+    }
+
+    fn insert(&mut self, key: Key, format: Format, body: Arc<Vec<u8>>) {
+        if !self.boards.contains_key(&key) {
+            if self.order.len() == MAX_CACHED_BOARDS {
+                let oldest = self.order.pop_front().unwrap();
+                self.boards.remove(&oldest);
+            }
+            self.order.push_back(key.clone());
+        }
+        let formats = self.boards.entry(key).or_default();
+        if !formats.iter().any(|cached| cached.0 == format) {
+            formats.push((format, body));
+        }
+    }
+}
+
+/// Answers the requests for boards. It's shared by all the connections.
+///
+/// - `GET /boards` lists the names of the templates as JSON.
+/// - `GET /boards/{template}/{seed}?mode=normal` generates a board. The
+///   format is chosen by the seed's extension, which is one of `.json`,
+///   `.png`, `.svg` and `.html`. Without an extension the board is sent as
+///   HTML to browsers and as JSON otherwise.
+pub struct Service {
+    templates: HashMap<String, LoadedTemplate>,
+    font: Vec<u8>,
+    cache: Mutex<Cache>,
+}
+
+impl Service {
+    /// Creates a service without templates that renders the images with the
+    /// font.
+    pub fn new(font: Vec<u8>) -> Self {
+        Service {
+            templates: HashMap::new(),
+            font: font,
+            cache: Mutex::new(Cache::default()),
+        }
+    }
+
+    pub fn add_template(&mut self, name: &str, json: &str) -> Result<(), String> {
+        let template = Template::from_json_str(json)
+            .map_err(|e| format!("The template {} is invalid: {}", name, e))?;
+        let mut hasher = DefaultHasher::new();
+        json.hash(&mut hasher);
+        let loaded = LoadedTemplate {
+            hash: hasher.finish(),
+            template: template,
+        };
+        self.templates.insert(name.to_owned(), loaded);
+        Ok(())
+    }
+
+    pub fn template(&self, name: &str) -> Option<&Template> {
+        self.templates.get(name).map(|loaded| &loaded.template)
+    }
+
+    pub fn handle(&self, request: &Request) -> Response {
+        if request.method != "GET" {
+            return Response::text(405, "Only GET requests are supported");
+        }
+        let path = request.path.iter().map(|s| &**s).collect::<Vec<_>>();
+        match &*path {
+            ["boards"] => {
+                let mut names = self.templates.keys().collect::<Vec<_>>();
+                names.sort();
+                let json = serde_json::to_vec(&names).unwrap();
+                Response::new(200, Format::Json.content_type(), json)
+            }
+            ["boards", template, seed] => self.board(request, template, seed),
+            _ => Response::text(404, "There is nothing at this path"),
+        }
+    }
+
+    fn board(&self, request: &Request, template_name: &str, seed: &str) -> Response {
+        let (seed, format) = match seed.rfind('.') {
+            Some(i) => match Format::from_extension(&seed[i + 1..]) {
+                Some(format) => (&seed[..i], format),
+                None => return Response::text(404, "The format is unknown"),
+            },
+            None => {
+                let accept = request.header("Accept").unwrap_or("");
+                if accept.contains("text/html") {
+                    (seed, Format::Html)
+                } else {
+                    (seed, Format::Json)
+                }
+            }
+        };
+        let loaded = match self.templates.get(template_name) {
+            Some(loaded) => loaded,
+            None => return Response::text(404, "The template is unknown"),
+        };
+        let mode = match parse_mode(request.query("mode").unwrap_or("normal")) {
+            Some(mode) => mode,
+            None => return Response::text(400, "The mode is unknown"),
+        };
+        let seed = Seed::from(seed);
+
+        let key = (loaded.hash, seed.to_string(), mode);
+        if let Some(body) = self.cache.lock().unwrap().get(&key, format) {
+            return Response {
+                status: 200,
+                content_type: format.content_type(),
+                body: body,
+            };
+        }
+
+        // The lock isn't held while rendering, so a board that is requested
+        // multiple times at once may be rendered more than once.
+        let board = match loaded.template.try_generate(seed, mode) {
+            Ok(board) => board,
+            Err(e) => return Response::text(500, &e.to_string()),
+        };
+        let body = match format {
+            Format::Json => Ok(json(&board, template_name, seed, mode)),
+            Format::Html => Ok(html(&board, template_name, seed, mode)),
+            Format::Png => try_render(&board, &RenderOptions::new(&self.font)).map(|rendered| {
+                let (width, height) = rendered.dimensions();
+                let mut png = Vec::new();
+                PNGEncoder::new(&mut png)
+                    .encode(&rendered, width, height, ColorType::RGBA(8))
+                    .unwrap();
+                png
+            }),
+            Format::Svg => {
+                try_render_svg(&board, &RenderOptions::new(&self.font)).map(String::into_bytes)
+            }
+        };
+        match body {
+            Ok(body) => {
+                let response = Response::new(200, format.content_type(), body);
+                let body = response.body.clone();
+                self.cache.lock().unwrap().insert(key, format, body);
+                response
+            }
+            Err(e) => Response::text(500, &e.to_string()),
+        }
+    }
+
+    #[cfg(test)]
+    fn cached_boards(&self) -> usize {
+        self.cache.lock().unwrap().boards.len()
+    }
+}
+
+pub fn parse_mode(mode: &str) -> Option<Mode> {
+    match mode {
+        "short" => Some(Mode::Short),
+        "normal" => Some(Mode::Normal),
+        "long" => Some(Mode::Long),
+        "special" => Some(Mode::Special),
+        _ => None,
+    }
+}
+
+fn mode_name(mode: Mode) -> &'static str {
+    match mode {
+        Mode::Short => "short",
+        Mode::Normal => "normal",
+        Mode::Long => "long",
+        Mode::Special => "special",
+    }
+}
+
+fn json(board: &Bingo, template: &str, seed: Seed, mode: Mode) -> Vec<u8> {
+    let goals = board
+        .cells
+        .iter()
+        .zip(&board.icons)
+        .map(|(&name, &icon)| {
+            let mut goal = serde_json::Map::new();
+            goal.insert("name".into(), name.into());
+            if let Some(icon) = icon {
+                goal.insert("icon".into(), icon.into());
+            }
+            Value::Object(goal)
+        })
+        .collect::<Vec<_>>();
+
+    let mut json = serde_json::Map::new();
+    json.insert("template".into(), template.into());
+    json.insert("seed".into(), seed.to_string().into());
+    json.insert("mode".into(), mode_name(mode).into());
+    json.insert("size".into(), board.size.into());
+    json.insert("goals".into(), goals.into());
+    serde_json::to_vec(&Value::Object(json)).unwrap()
+}
+
+fn escape(html: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            c => html.push(c),
+        }
+    }
+}
+
+/// A page with the board as a table and links to the other formats.
+fn html(board: &Bingo, template: &str, seed: Seed, mode: Mode) -> Vec<u8> {
+    let mut html = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str("<title>");
+    escape(
+        &mut html,
+        &format!("{} {} ({})", template, seed, mode_name(mode)),
+    );
+    html.push_str("</title>\n</head>\n<body>\n<table>\n");
+    for row in board.rows() {
+        html.push_str("<tr>");
+        for cell in row {
+            html.push_str("<td>");
+            escape(&mut html, cell);
+            html.push_str("</td>");
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>\n<p>");
+    let seed = encode(&seed.to_string());
+    for (i, extension) in ["png", "svg", "json"].iter().enumerate() {
+        if i > 0 {
+            html.push(' ');
+        }
+        write!(
+            html,
+            "<a href=\"{}.{}?mode={}\">{}</a>",
+            seed,
+            extension,
+            mode_name(mode),
+            extension.to_uppercase()
+        )
+        .unwrap();
+    }
+    html.push_str("</p>\n</body>\n</html>\n");
+    html.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::read_request;
+    use speedrun_bingo::{render, render_svg, RenderError};
+    use std::iter;
+
+    fn service() -> Service {
+        service_with_font(Vec::new())
+    }
+
+    fn service_with_font(font: Vec<u8>) -> Service {
+        let mut service = Service::new(font);
+        let json = include_str!("../../templates/sm64.json");
+        service.add_template("sm64", json).unwrap();
+        service
+    }
+
+    fn u16s(values: &[u16]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|&v| vec![(v >> 8) as u8, v as u8])
+            .collect()
+    }
+
+    /// A TrueType font with empty glyphs for the printable ASCII characters,
+    /// which is all the goals of the template need to be laid out.
+    fn ascii_font() -> Vec<u8> {
+        // A single format 4 subtable maps the characters 0x20 to 0x7e to the
+        // glyphs 1 to 95.
+        let cmap = u16s(&[
+            0, 1, 3, 1, 0, 12, 4, 32, 0, 4, 4, 1, 0, 0x7e, 0xffff, 0, 0x20, 0xffff, 0xffe1, 1, 0, 0,
+        ]);
+        // 1000 units per em and short glyph offsets.
+        let head = u16s(&[
+            1, 0, 1, 0, 0, 0, 0x5f0f, 0x3cf5, 0, 1000, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            0, 0,
+        ]);
+        // An ascent of 800, a descent of 200 and a single advance width of
+        // 500 for all the glyphs.
+        let hhea = u16s(&[
+            1, 0, 800, 0xff38, 0, 500, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1,
+        ]);
+        let mut hmtx = u16s(&[500, 0]);
+        hmtx.resize(4 + 2 * 95, 0);
+        let loca = vec![0; 2 * 97];
+        let maxp = u16s(&[0, 0x5000, 96]);
+        let tables: [(&[u8], Vec<u8>); 7] = [
+            (b"cmap", cmap),
+            (b"glyf", Vec::new()),
+            (b"head", head),
+            (b"hhea", hhea),
+            (b"hmtx", hmtx),
+            (b"loca", loca),
+            (b"maxp", maxp),
+        ];
+
+        let mut font = u16s(&[1, 0, 7, 64, 2, 48]);
+        let mut offset = font.len() + 16 * tables.len();
+        for (tag, table) in &tables {
+            font.extend_from_slice(tag);
+            let (len, start) = (table.len() as u16, offset as u16);
+            font.extend(u16s(&[0, 0, 0, start, 0, len]));
+            offset += table.len();
+        }
+        for (_, table) in &tables {
+            font.extend_from_slice(table);
+        }
+        font
+    }
+
+    /// Sends the request through the HTTP parser to the service without going
+    /// through the network.
+    fn get(service: &Service, target: &str, accept: &str) -> Response {
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: localhost\r\nAccept: {}\r\n\r\n",
+            target, accept
+        );
+        let request = read_request(request.as_bytes()).unwrap();
+        service.handle(&request)
+    }
+
+    fn body(response: &Response) -> &str {
+        ::std::str::from_utf8(&response.body).unwrap()
+    }
+
+    #[test]
+    fn json_board() {
+        let service = service();
+        let response = get(&service, "/boards/sm64/587062?mode=long", "*/*");
+        assert_eq!(response.status, 200);
+        assert_eq!(response.content_type, "application/json");
+
+        let json = serde_json::from_slice::<Value>(&response.body).unwrap();
+        assert_eq!(json["template"], "sm64");
+        assert_eq!(json["seed"], "587062");
+        assert_eq!(json["mode"], "long");
+        assert_eq!(json["size"], 5);
+
+        let template = Template::from_json_str(include_str!("../../templates/sm64.json")).unwrap();
+        let board = template.generate(587062, Mode::Long);
+        let goals = json["goals"].as_array().unwrap();
+        assert_eq!(goals.len(), 25);
+        for (goal, &name) in goals.iter().zip(&board.cells) {
+            assert_eq!(goal["name"], name);
+        }
+
+        assert_eq!(
+            get(&service, "/boards/sm64/587062.json?mode=long", "text/html"),
+            response
+        );
+    }
+
+    #[test]
+    fn html_board() {
+        let service = service();
+        let response = get(&service, "/boards/sm64/Final%20Race", "text/html,*/*");
+        assert_eq!(response.status, 200);
+        assert_eq!(response.content_type, "text/html; charset=utf-8");
+        let html = body(&response);
+        assert!(html.contains("<title>sm64 Final Race (normal)</title>"));
+        assert_eq!(html.matches("<tr>").count(), 5);
+        assert_eq!(html.matches("<td>").count(), 25);
+        assert!(html.contains("<a href=\"Final%20Race.png?mode=normal\">PNG</a>"));
+        assert_eq!(
+            get(&service, "/boards/sm64/Final%20Race.html", ""),
+            response
+        );
+    }
+
+    #[test]
+    fn png_board() {
+        let service = service_with_font(ascii_font());
+        let response = get(&service, "/boards/sm64/587062.png", "");
+        assert_eq!(response.status, 200);
+        assert_eq!(response.content_type, "image/png");
+        assert!(response.body.starts_with(b"\x89PNG\r\n"));
+        assert_eq!(service.cached_boards(), 1);
+    }
+
+    #[test]
+    fn svg_board() {
+        let service = service_with_font(ascii_font());
+        let response = get(&service, "/boards/sm64/587062.svg?mode=long", "");
+        assert_eq!(response.status, 200);
+        assert_eq!(response.content_type, "image/svg+xml");
+        let svg = body(&response);
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert!(svg.contains(">6 Stars in</text>"));
+    }
+
+    /// The ASCII font with the length of a table in the table directory
+    /// changed, so the table ends early.
+    fn truncated_font(table: usize, len: u16) -> Vec<u8> {
+        let mut font = ascii_font();
+        let record = 12 + 16 * table;
+        font[record + 14..record + 16].copy_from_slice(&u16s(&[len]));
+        font
+    }
+
+    #[test]
+    fn truncated_tables() {
+        let template = Template::from_json_str(include_str!("../../templates/sm64.json")).unwrap();
+        let board = template.generate(587062, Mode::Normal);
+        let font = ascii_font();
+        assert!(try_render(&board, &RenderOptions::new(&font)).is_ok());
+
+        // The cmap table ends before its subtable, or in the middle of it, and
+        // the loca table is missing the end of the last glyph.
+        for font in &[
+            truncated_font(0, 12),
+            truncated_font(0, 30),
+            truncated_font(5, 2 * 96),
+        ] {
+            let options = RenderOptions::new(font);
+            assert_eq!(
+                try_render(&board, &options).err(),
+                Some(RenderError::InvalidFont)
+            );
+            assert_eq!(
+                try_render_svg(&board, &options).err(),
+                Some(RenderError::InvalidFont)
+            );
+        }
+    }
+
+    #[test]
+    fn missing_glyphs() {
+        let board = Bingo {
+            size: 1,
+            cells: iter::once("Café ★").collect(),
+            icons: iter::once(None).collect(),
+        };
+        let font = ascii_font();
+        let options = RenderOptions::new(&font);
+        assert_eq!(
+            try_render(&board, &options).err(),
+            Some(RenderError::MissingGlyph {
+                cell: 0,
+                character: 'é',
+            })
+        );
+        assert!(try_render_svg(&board, &options).is_err());
+
+        // Only the fallible functions check the glyphs, the others draw the
+        // .notdef glyph instead.
+        assert_eq!(render(&board, &options).dimensions(), (120, 120));
+        assert!(render_svg(&board, &options).contains(">Café</text>"));
+    }
+
+    #[test]
+    fn caching() {
+        let service = service();
+        let first = get(&service, "/boards/sm64/5?mode=short", "");
+        assert_eq!(service.cached_boards(), 1);
+
+        // The responses share the body with the cache instead of being
+        // generated again.
+        let second = get(&service, "/boards/sm64/5?mode=short", "");
+        assert!(Arc::ptr_eq(&first.body, &second.body));
+        get(&service, "/boards/sm64/5.html?mode=short", "");
+        assert_eq!(service.cached_boards(), 1);
+
+        get(&service, "/boards/sm64/5?mode=long", "");
+        get(&service, "/boards/sm64/6?mode=short", "");
+        assert_eq!(service.cached_boards(), 3);
+
+        for seed in 0..MAX_CACHED_BOARDS {
+            get(&service, &format!("/boards/sm64/{}", 100 + seed), "");
+        }
+        assert_eq!(service.cached_boards(), MAX_CACHED_BOARDS);
+        let third = get(&service, "/boards/sm64/5?mode=short", "");
+        assert!(!Arc::ptr_eq(&first.body, &third.body));
+        assert_eq!(first, third);
+    }
+
+    #[test]
+    fn list_templates() {
+        let mut service = service();
+        service
+            .add_template("empty", "[[{\"name\":\"Goal\",\"types\":[]}]]")
+            .unwrap();
+        let response = get(&service, "/boards", "");
+        assert_eq!(response.status, 200);
+        assert_eq!(body(&response), "[\"empty\",\"sm64\"]");
+    }
+
+    #[test]
+    fn errors() {
+        let service = service();
+        for &(target, status, message) in &[
+            ("/", 404, "There is nothing at this path"),
+            ("/boards/sm64", 404, "There is nothing at this path"),
+            ("/boards/sm64/5/6", 404, "There is nothing at this path"),
+            ("/boards/sm63/5", 404, "The template is unknown"),
+            ("/boards/sm64/5.gif", 404, "The format is unknown"),
+            ("/boards/sm64/5?mode=medium", 400, "The mode is unknown"),
+            (
+                "/boards/sm64/5.png",
+                500,
+                "The font data is not a valid font",
+            ),
+            (
+                "/boards/sm64/5.svg",
+                500,
+                "The font data is not a valid font",
+            ),
+        ] {
+            let response = get(&service, target, "");
+            assert_eq!(response.status, status);
+            assert_eq!(body(&response), message);
+        }
+        assert_eq!(service.cached_boards(), 0);
+
+        let request = read_request(&b"POST /boards HTTP/1.1\r\n\r\n"[..]).unwrap();
+        assert_eq!(service.handle(&request).status, 405);
+    }
+}
@@ -0,0 +1,379 @@
+//! The parts of WebSockets (RFC 6455) the rooms need: the handshake, text
+//! messages and the control frames.
+
+use std::io::{self, Read, Write};
+
+/// The GUID that is appended to the client's key for the handshake.
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// The longest message that is accepted from a client.
+const MAX_MESSAGE_LEN: u64 = 64 * 1024;
+
+const CONTINUATION: u8 = 0x0;
+pub const TEXT: u8 = 0x1;
+const BINARY: u8 = 0x2;
+pub const CLOSE: u8 = 0x8;
+const PING: u8 = 0x9;
+pub const PONG: u8 = 0xA;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    Text(String),
+    Ping(Vec<u8>),
+    Pong,
+    Close,
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Completes the handshake for the client's `Sec-WebSocket-Key`.
+pub fn write_handshake<W: Write>(mut writer: W, key: &str) -> io::Result<()> {
+    write!(
+        writer,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key)
+    )?;
+    writer.flush()
+}
+
+/// The key the server answers the handshake with.
+pub fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key, GUID).as_bytes()))
+}
+
+/// Reads the messages of a connection, putting fragmented messages back
+/// together.
+pub struct Receiver<R> {
+    reader: R,
+    /// Whether the frames need to be masked, which is the case for all the
+    /// frames a client sends and none of the frames a server sends.
+    masked: bool,
+    /// The start of a fragmented message.
+    partial: Option<Vec<u8>>,
+}
+
+impl<R: Read> Receiver<R> {
+    /// Reads the messages a client sends.
+    pub fn new(reader: R) -> Self {
+        Receiver {
+            reader: reader,
+            masked: true,
+            partial: None,
+        }
+    }
+
+    /// Reads the messages a server sends.
+    #[cfg(test)]
+    pub fn from_server(reader: R) -> Self {
+        Receiver {
+            masked: false,
+            ..Receiver::new(reader)
+        }
+    }
+
+    /// Reads the next message. Binary messages aren't used by the rooms, so
+    /// they are an error.
+    pub fn receive(&mut self) -> io::Result<Message> {
+        loop {
+            let mut head = [0; 2];
+            self.reader.read_exact(&mut head)?;
+            let fin = head[0] & 0x80 != 0;
+            let opcode = head[0] & 0x0F;
+            let masked = head[1] & 0x80 != 0;
+            if head[0] & 0x70 != 0 {
+                return Err(invalid("The frame uses an unknown extension"));
+            }
+            if masked != self.masked {
+                return Err(invalid(if self.masked {
+                    "The frame is not masked"
+                } else {
+                    "The frame is masked"
+                }));
+            }
+            // Control frames can't be fragmented and have a short payload.
+            if opcode & 0x8 != 0 && (!fin || head[1] & 0x7F > 125) {
+                return Err(invalid("The control frame is invalid"));
+            }
+            let len = match head[1] & 0x7F {
+                126 => {
+                    let mut len = [0; 2];
+                    self.reader.read_exact(&mut len)?;
+                    u64::from(u16::from_be_bytes(len))
+                }
+                127 => {
+                    let mut len = [0; 8];
+                    self.reader.read_exact(&mut len)?;
+                    // The most significant bit has to be 0.
+                    if len[0] & 0x80 != 0 {
+                        return Err(invalid("The frame length is invalid"));
+                    }
+                    u64::from_be_bytes(len)
+                }
+                len => u64::from(len),
+            };
+            let partial_len = self.partial.as_ref().map_or(0, |partial| partial.len());
+            if len > MAX_MESSAGE_LEN - partial_len as u64 {
+                return Err(invalid("The message is too long"));
+            }
+            let mut mask = [0; 4];
+            if masked {
+                self.reader.read_exact(&mut mask)?;
+            }
+            let mut payload = vec![0; len as usize];
+            self.reader.read_exact(&mut payload)?;
+            for (i, b) in payload.iter_mut().enumerate() {
+                *b ^= mask[i % 4];
+            }
+
+            // Control frames may be sent in between the fragments of a
+            // message.
+            let text = match opcode {
+                TEXT if self.partial.is_none() => payload,
+                CONTINUATION if self.partial.is_some() => {
+                    let mut text = self.partial.take().unwrap();
+                    text.extend_from_slice(&payload);
+                    text
+                }
+                PING => return Ok(Message::Ping(payload)),
+                PONG => return Ok(Message::Pong),
+                CLOSE => return Ok(Message::Close),
+                BINARY => return Err(invalid("Binary messages are not supported")),
+                _ => return Err(invalid("The frame is unexpected")),
+            };
+            if !fin {
+                self.partial = Some(text);
+                continue;
+            }
+            return String::from_utf8(text)
+                .map(Message::Text)
+                .map_err(|_| invalid("The message is not valid UTF-8"));
+        }
+    }
+}
+
+/// Writes a single unfragmented frame. Only clients mask their frames.
+pub fn write_frame<W: Write>(
+    mut writer: W,
+    opcode: u8,
+    payload: &[u8],
+    mask: Option<[u8; 4]>,
+) -> io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+    match payload.len() {
+        len @ 0..=125 => frame.push(mask_bit | len as u8),
+        len @ 126..=0xFFFF => {
+            frame.push(mask_bit | 126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(mask_bit | 127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    match mask {
+        Some(mask) => {
+            frame.extend_from_slice(&mask);
+            frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        }
+        None => frame.extend_from_slice(payload),
+    }
+    writer.write_all(&frame)?;
+    writer.flush()
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h = [
+        0x67452301u32,
+        0xEFCDAB89,
+        0x98BADCFE,
+        0x10325476,
+        0xC3D2E1F0,
+    ];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for chunk in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in chunk.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, &w) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(w);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (h, v) in h.iter_mut().zip(&[a, b, c, d, e]) {
+            *h = h.wrapping_add(*v);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (bytes, h) in digest.chunks_mut(4).zip(&h) {
+        bytes.copy_from_slice(&h.to_be_bytes());
+    }
+    digest
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (bytes[0] as usize) << 16 | (bytes[1] as usize) << 8 | bytes[2] as usize;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[n >> (18 - 6 * i) & 0x3F] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handshake() {
+        // The example from the RFC.
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+        assert_eq!(base64(b"ab"), "YWI=");
+        assert_eq!(base64(b"a"), "YQ==");
+        assert_eq!(sha1(b"")[..4], [0xda, 0x39, 0xa3, 0xee]);
+    }
+
+    #[test]
+    fn frames() {
+        let mut written = Vec::new();
+        write_frame(&mut written, TEXT, b"Hello", None).unwrap();
+        assert_eq!(written, b"\x81\x05Hello");
+
+        // The masked example from the RFC.
+        let mut written = Vec::new();
+        write_frame(&mut written, TEXT, b"Hello", Some([0x37, 0xfa, 0x21, 0x3d])).unwrap();
+        assert_eq!(written, b"\x81\x85\x37\xfa\x21\x3d\x7f\x9f\x4d\x51\x58");
+        assert_eq!(
+            Receiver::new(&written[..]).receive().unwrap(),
+            Message::Text("Hello".into())
+        );
+
+        let long = "x".repeat(300);
+        let mut written = Vec::new();
+        write_frame(&mut written, TEXT, long.as_bytes(), Some([1, 2, 3, 4])).unwrap();
+        assert_eq!(written[1..4], [0xFE, 0x01, 0x2C]);
+        assert_eq!(
+            Receiver::new(&written[..]).receive().unwrap(),
+            Message::Text(long)
+        );
+    }
+
+    #[test]
+    fn fragments() {
+        // A fragmented message with a ping in between, from the RFC.
+        let frames = b"\x01\x03Hel\x89\x00\x80\x02lo\x88\x00";
+        let mut receiver = Receiver::from_server(&frames[..]);
+        assert_eq!(receiver.receive().unwrap(), Message::Ping(Vec::new()));
+        assert_eq!(receiver.receive().unwrap(), Message::Text("Hello".into()));
+        assert_eq!(receiver.receive().unwrap(), Message::Close);
+        assert!(receiver.receive().is_err());
+
+        let mut receiver = Receiver::from_server(&b"\x80\x02lo"[..]);
+        assert!(receiver.receive().is_err());
+    }
+
+    fn receive_error(frames: &[u8]) -> String {
+        Receiver::new(frames).receive().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn invalid_frames() {
+        // Clients have to mask their frames, servers must not.
+        assert_eq!(receive_error(b"\x81\x05Hello"), "The frame is not masked");
+        let mut masked = Vec::new();
+        write_frame(&mut masked, TEXT, b"Hello", Some([1, 2, 3, 4])).unwrap();
+        assert_eq!(
+            Receiver::from_server(&masked[..])
+                .receive()
+                .unwrap_err()
+                .to_string(),
+            "The frame is masked"
+        );
+
+        let mut ping = Vec::new();
+        write_frame(&mut ping, PING, &[0; 125], Some([1, 2, 3, 4])).unwrap();
+        assert_eq!(
+            Receiver::new(&ping[..]).receive().unwrap(),
+            Message::Ping(vec![0; 125])
+        );
+
+        let mut long_ping = Vec::new();
+        write_frame(&mut long_ping, PING, &[0; 126], Some([1, 2, 3, 4])).unwrap();
+        assert_eq!(receive_error(&long_ping), "The control frame is invalid");
+
+        // A ping without the fin bit.
+        ping[0] &= 0x7F;
+        assert_eq!(receive_error(&ping), "The control frame is invalid");
+
+        assert_eq!(
+            receive_error(b"\xC1\x80\0\0\0\0"),
+            "The frame uses an unknown extension"
+        );
+    }
+
+    #[test]
+    fn long_frames() {
+        let mut frames = Vec::new();
+        write_frame(&mut frames, TEXT, b"Hel", Some([1, 2, 3, 4])).unwrap();
+        frames[0] &= 0x7F;
+        // A continuation that is so long that adding the start of the
+        // message to it would overflow.
+        frames.extend_from_slice(b"\x80\xFF\x7F\xFF\xFF\xFF\xFF\xFF\xFF\xFF");
+        assert_eq!(receive_error(&frames), "The message is too long");
+
+        let mut receiver = Receiver::new(&b"\x81\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF"[..]);
+        assert_eq!(
+            receiver.receive().unwrap_err().to_string(),
+            "The frame length is invalid"
+        );
+
+        let mut long = Vec::new();
+        write_frame(&mut long, TEXT, &[b'x'; 64 * 1024 + 1], Some([1, 2, 3, 4])).unwrap();
+        assert_eq!(receive_error(&long), "The message is too long");
+    }
+}
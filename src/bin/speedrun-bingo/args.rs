@@ -0,0 +1,134 @@
+use std::str::FromStr;
+
+/// The options passed to a command, like `--seed 5` or `--seed=5`.
+#[derive(Debug)]
+pub struct Options {
+    values: Vec<(&'static str, String)>,
+}
+
+impl Options {
+    /// Parses the arguments, which may only contain the allowed options, each
+    /// at most once and followed by its value.
+    pub fn parse(args: &[String], allowed: &[&'static str]) -> Result<Self, String> {
+        Self::parse_repeatable(args, allowed, &[])
+    }
+
+    /// Parses the arguments like `parse`, except that the repeatable options
+    /// may be passed any number of times.
+    pub fn parse_repeatable(
+        args: &[String],
+        allowed: &[&'static str],
+        repeatable: &[&str],
+    ) -> Result<Self, String> {
+        let mut values = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if !arg.starts_with("--") {
+                return Err(format!("Unexpected argument {:?}", arg));
+            }
+            let (name, value) = match arg.find('=') {
+                Some(i) => (&arg[2..i], Some(arg[i + 1..].to_owned())),
+                None => (&arg[2..], None),
+            };
+            let name = match allowed.iter().find(|&&a| a == name) {
+                Some(&name) => name,
+                None => return Err(format!("Unknown option --{}", name)),
+            };
+            if !repeatable.contains(&name) && values.iter().any(|&(n, _)| n == name) {
+                return Err(format!("The option --{} is passed more than once", name));
+            }
+            let value = match value.or_else(|| args.next().cloned()) {
+                Some(value) => value,
+                None => return Err(format!("The option --{} is missing its value", name)),
+            };
+            values.push((name, value));
+        }
+        Ok(Options { values: values })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.get_all(name).into_iter().next()
+    }
+
+    /// The values of a repeatable option, in the order they are passed.
+    pub fn get_all(&self, name: &str) -> Vec<&str> {
+        self.values
+            .iter()
+            .filter(|&&(n, _)| n == name)
+            .map(|option| &*option.1)
+            .collect()
+    }
+
+    pub fn require(&self, name: &str) -> Result<&str, String> {
+        self.get(name)
+            .ok_or_else(|| format!("The option --{} is required", name))
+    }
+
+    /// Parses the value of the option, if it's passed.
+    pub fn parse_value<T: FromStr>(&self, name: &str) -> Result<Option<T>, String> {
+        match self.get(name) {
+            Some(value) => value
+                .parse()
+                .map(Some)
+                .map_err(|_| format!("The value {:?} of --{} is invalid", value, name)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        let args = args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        Options::parse(&args, &["seed", "mode", "size"])
+    }
+
+    #[test]
+    fn options() {
+        let options = parse(&["--seed", "587062", "--size=7"]).unwrap();
+        assert_eq!(options.get("seed"), Some("587062"));
+        assert_eq!(options.get("mode"), None);
+        assert_eq!(options.parse_value::<usize>("size"), Ok(Some(7)));
+        assert_eq!(options.parse_value::<usize>("mode"), Ok(None));
+        assert_eq!(
+            options.require("mode").unwrap_err(),
+            "The option --mode is required"
+        );
+        assert_eq!(
+            options.parse_value::<u8>("seed").unwrap_err(),
+            "The value \"587062\" of --seed is invalid"
+        );
+    }
+
+    #[test]
+    fn repeatable() {
+        let args = ["--seed", "1", "--mode=short", "--seed", "2"]
+            .iter()
+            .map(|a| a.to_string())
+            .collect::<Vec<_>>();
+        let options = Options::parse_repeatable(&args, &["seed", "mode"], &["seed"]).unwrap();
+        assert_eq!(options.get_all("seed"), ["1", "2"]);
+        assert_eq!(options.get("seed"), Some("1"));
+        assert!(options.get_all("size").is_empty());
+        assert!(Options::parse_repeatable(&args, &["seed", "mode"], &["mode"]).is_err());
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(parse(&["5"]).unwrap_err(), "Unexpected argument \"5\"");
+        assert_eq!(
+            parse(&["--template", "sm64.json"]).unwrap_err(),
+            "Unknown option --template"
+        );
+        assert_eq!(
+            parse(&["--seed", "1", "--seed=2"]).unwrap_err(),
+            "The option --seed is passed more than once"
+        );
+        assert_eq!(
+            parse(&["--seed"]).unwrap_err(),
+            "The option --seed is missing its value"
+        );
+    }
+}
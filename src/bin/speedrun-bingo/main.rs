@@ -0,0 +1,495 @@
+extern crate serde_json;
+extern crate speedrun_bingo;
+
+mod args;
+mod web;
+
+use args::Options;
+use serde_json::Value;
+use speedrun_bingo::{
+    try_render, try_render_svg, Analysis, Bingo, Criterion, Curve, DifficultyMapping, Mode,
+    RenderOptions, Search, Seed, Template, Theme,
+};
+use std::ops::Range;
+use std::path::Path;
+use std::{env, fs, process};
+
+const USAGE: &str = "\
+Usage: speedrun-bingo <command> [options]
+
+Commands:
+  generate   Prints a board
+  render     Writes an image of a board
+  search     Prints the seeds of boards that meet all the criteria
+  analyze    Reports how balanced the boards of a template are
+  validate   Checks a template for problems
+  convert    Converts a template to another format
+  help       Prints this message
+
+Board options for generate and render:
+  --template <file>     The template as JSON, defaults to the built-in SM64 template
+  --seed <seed>         The seed, which is a number or any other text
+  --mode <mode>         short, normal, long or special, defaults to the
+                        template's curve or normal
+  --size <size>         The width and height of the board, defaults to 5
+
+Options for generate:
+  --format <format>     text or json, defaults to text
+
+Options for render:
+  --font <file>         The TrueType font the goals are drawn with
+  --output <file>       The image to write, which is an SVG if it ends with .svg
+                        and a PNG otherwise
+  --font-family <name>  The name of the font an SVG refers to
+  --font-size <size>    The font size, defaults to 16
+  --cell-size <size>    The width and height of a cell in pixels, defaults to 120
+  --theme <file>        The theme as JSON
+
+Options for search:
+  --template <file>     The template as JSON, defaults to the built-in SM64 template
+  --mode <mode>         short, normal, long or special, defaults to the
+                        template's curve or normal
+  --size <size>         The width and height of the boards, defaults to 5
+  --seeds <range>       The seeds to search, like 0..100000 without the end,
+                        defaults to 0..1000000
+  --limit <count>       The most seeds to print, defaults to 10
+  --threads <count>     The threads to search on, defaults to one per core
+
+Criteria for search, which may be passed multiple times:
+  --contains <goal>          The board contains the goal
+  --excludes <goal>          The board doesn't contain the goal
+  --max-line-synergy <n>     No line has more synergy than n
+  --max-per-line <type>=<n>  No line has more than n goals of the type
+  --min-type <type>=<n>      The board has at least n goals of the type
+  --max-type <type>=<n>      The board has at most n goals of the type
+
+Options for analyze:
+  --template <file>     The template as JSON, defaults to the built-in SM64 template
+  --mode <mode>         short, normal, long or special, defaults to the
+                        template's curve or normal
+  --size <size>         The width and height of the boards, defaults to 5
+  --seeds <range>       The seeds to analyze, like 0..100000 without the end,
+                        defaults to 0..10000
+  --threads <count>     The threads to analyze on, defaults to one per core
+  --format <format>     text, json or csv, defaults to text
+  --output <path>       The file to write the JSON to, or the directory to write
+                        goals.csv, positions.csv, types.csv and lines.csv to
+
+Options for validate:
+  --template <file>     The template as JSON
+
+Options for convert:
+  --input <file>        The template to convert
+  --output <file>       The converted template
+  --from <format>       json or web, defaults to the extension of the input
+  --to <format>         json, pretty or web, defaults to the extension of the
+                        output
+
+A JSON template is either the list of difficulty tiers or an object with the
+tiers and a curve that lists the tier of each of the 25 difficulty levels.
+The web format is the JavaScript of the web-based generator, which assigns the
+difficulty tiers to bingoList[1] and onwards.
+";
+
+const BOARD_OPTIONS: &[&str] = &["template", "seed", "mode", "size"];
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let result = match args.first().map(|a| &**a) {
+        Some("generate") => generate(&args[1..]),
+        Some("render") => render(&args[1..]),
+        Some("search") => search(&args[1..]),
+        Some("analyze") => analyze(&args[1..]),
+        Some("validate") => validate(&args[1..]),
+        Some("convert") => convert(&args[1..]),
+        Some("help") | Some("--help") | Some("-h") | None => {
+            print!("{}", USAGE);
+            Ok(())
+        }
+        Some(command) => Err(format!(
+            "Unknown command {:?}, see speedrun-bingo help",
+            command
+        )),
+    };
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn read(path: &str) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("Can't read {}: {}", path, e))
+}
+
+fn write(path: &str, contents: &[u8]) -> Result<(), String> {
+    fs::write(path, contents).map_err(|e| format!("Can't write {}: {}", path, e))
+}
+
+fn load_template(path: Option<&str>) -> Result<Template, String> {
+    let json = match path {
+        Some(path) => read(path)?,
+        None => include_str!("../../templates/sm64.json").to_owned(),
+    };
+    Template::from_json_str(&json).map_err(|e| format!("The template is invalid: {}", e))
+}
+
+/// The difficulty the boards get generated with, which is either one of the
+/// modes or the template's own curve.
+#[derive(Copy, Clone)]
+enum Difficulty {
+    Mode(Mode),
+    Curve(Curve),
+}
+
+impl DifficultyMapping for Difficulty {
+    fn tier(&self, value: usize) -> usize {
+        match *self {
+            Difficulty::Mode(mode) => mode.tier(value),
+            Difficulty::Curve(curve) => curve.tier(value),
+        }
+    }
+
+    fn special_only(&self) -> bool {
+        match *self {
+            Difficulty::Mode(mode) => mode.special_only(),
+            Difficulty::Curve(curve) => curve.special_only(),
+        }
+    }
+}
+
+/// The difficulty passed as `--mode`. Without it, templates with a curve use
+/// their curve and all the others use normal mode.
+fn parse_mode(options: &Options, template: &Template) -> Result<Difficulty, String> {
+    let mode = match (options.get("mode"), template.1) {
+        (Some(mode), _) => mode,
+        (None, Some(curve)) => return Ok(Difficulty::Curve(curve)),
+        (None, None) => "normal",
+    };
+    match mode {
+        "short" => Ok(Difficulty::Mode(Mode::Short)),
+        "normal" => Ok(Difficulty::Mode(Mode::Normal)),
+        "long" => Ok(Difficulty::Mode(Mode::Long)),
+        "special" => Ok(Difficulty::Mode(Mode::Special)),
+        _ => Err(format!("Unknown mode {:?}", mode)),
+    }
+}
+
+/// Generates the board the board options describe.
+fn board<'a>(options: &Options, template: &'a Template) -> Result<Bingo<'a>, String> {
+    let seed = Seed::from(options.require("seed")?);
+    let mode = parse_mode(options, template)?;
+    let size = options.parse_value("size")?.unwrap_or(5);
+    template
+        .try_generate_sized(seed, mode, size)
+        .map_err(|e| e.to_string())
+}
+
+fn generate(args: &[String]) -> Result<(), String> {
+    let options = Options::parse(args, &[BOARD_OPTIONS, &["format"]].concat())?;
+    let template = load_template(options.get("template"))?;
+    let board = board(&options, &template)?;
+
+    match options.get("format").unwrap_or("text") {
+        "text" => {
+            let widths = (0..board.size)
+                .map(|x| {
+                    (0..board.size)
+                        .map(|y| board.cell(x, y).unwrap_or("").chars().count())
+                        .max()
+                        .unwrap_or(0)
+                })
+                .collect::<Vec<_>>();
+            for row in board.rows() {
+                let cells = row
+                    .iter()
+                    .zip(&widths)
+                    .map(|(cell, &width)| format!("{:1$}", cell, width))
+                    .collect::<Vec<_>>();
+                println!("{}", cells.join(" | ").trim_end());
+            }
+        }
+        // The format bingo sites import boards from.
+        "json" => {
+            let cells = board
+                .cells
+                .iter()
+                .zip(&board.icons)
+                .map(|(&name, &icon)| {
+                    let mut cell = serde_json::Map::new();
+                    cell.insert("name".into(), name.into());
+                    if let Some(icon) = icon {
+                        cell.insert("icon".into(), icon.into());
+                    }
+                    Value::Object(cell)
+                })
+                .collect();
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&Value::Array(cells)).unwrap()
+            );
+        }
+        format => return Err(format!("Unknown format {:?}", format)),
+    }
+    Ok(())
+}
+
+fn render(args: &[String]) -> Result<(), String> {
+    let allowed = [
+        BOARD_OPTIONS,
+        &[
+            "font",
+            "output",
+            "font-family",
+            "font-size",
+            "cell-size",
+            "theme",
+        ],
+    ]
+    .concat();
+    let options = Options::parse(args, &allowed)?;
+    let template = load_template(options.get("template"))?;
+    let board = board(&options, &template)?;
+    let output = options.require("output")?;
+    let font_path = options.require("font")?;
+    let font = fs::read(font_path).map_err(|e| format!("Can't read {}: {}", font_path, e))?;
+
+    let mut render_options = RenderOptions::new(&font);
+    if let Some(family) = options.get("font-family") {
+        render_options.font_family = family;
+    }
+    if let Some(size) = options.parse_value("font-size")? {
+        render_options.font_size = size;
+    }
+    if let Some(size) = options.parse_value("cell-size")? {
+        render_options.cell_size = size;
+    }
+    if let Some(path) = options.get("theme") {
+        render_options.theme = Theme::from_json_str(&read(path)?)
+            .map_err(|e| format!("The theme is invalid: {}", e))?;
+    }
+
+    if output.ends_with(".svg") {
+        let svg = try_render_svg(&board, &render_options).map_err(|e| e.to_string())?;
+        write(output, svg.as_bytes())
+    } else {
+        let image = try_render(&board, &render_options).map_err(|e| e.to_string())?;
+        image
+            .save(output)
+            .map_err(|e| format!("Can't write {}: {}", output, e))
+    }
+}
+
+fn search(args: &[String]) -> Result<(), String> {
+    let criteria_options = [
+        "contains",
+        "excludes",
+        "max-line-synergy",
+        "max-per-line",
+        "min-type",
+        "max-type",
+    ];
+    let allowed = [
+        &["template", "mode", "size", "seeds", "limit", "threads"],
+        &criteria_options[..],
+    ]
+    .concat();
+    let options = Options::parse_repeatable(args, &allowed, &criteria_options)?;
+    let template = load_template(options.get("template"))?;
+    let mode = parse_mode(&options, &template)?;
+    let seeds = seeds(&options, 0..1_000_000)?;
+
+    let mut criteria = Vec::new();
+    for name in options.get_all("contains") {
+        check_goal(&template, name)?;
+        criteria.push(Criterion::Contains(name));
+    }
+    for name in options.get_all("excludes") {
+        check_goal(&template, name)?;
+        criteria.push(Criterion::Excludes(name));
+    }
+    for max in options.get_all("max-line-synergy") {
+        let max = max
+            .parse()
+            .map_err(|_| format!("The value {:?} of --max-line-synergy is invalid", max))?;
+        criteria.push(Criterion::MaxLineSynergy(max));
+    }
+    for value in options.get_all("max-per-line") {
+        let (goal_type, max) = type_limit(&template, "max-per-line", value)?;
+        criteria.push(Criterion::MaxTypePerLine {
+            goal_type: goal_type,
+            max: max,
+        });
+    }
+    for value in options.get_all("min-type") {
+        let (goal_type, min) = type_limit(&template, "min-type", value)?;
+        criteria.push(Criterion::MinTypeCount {
+            goal_type: goal_type,
+            min: min,
+        });
+    }
+    for value in options.get_all("max-type") {
+        let (goal_type, max) = type_limit(&template, "max-type", value)?;
+        criteria.push(Criterion::MaxTypeCount {
+            goal_type: goal_type,
+            max: max,
+        });
+    }
+
+    let mut search = Search::new(&criteria);
+    search.size = options.parse_value("size")?.unwrap_or(5);
+    search.limit = Some(options.parse_value("limit")?.unwrap_or(10));
+    search.threads = options.parse_value("threads")?.unwrap_or(0);
+    let found = search
+        .run(&template, mode, seeds.clone())
+        .map_err(|e| e.to_string())?;
+    for seed in &found {
+        println!("{}", seed);
+    }
+    if found.is_empty() {
+        return Err(format!(
+            "None of the seeds {}..{} meet the criteria",
+            seeds.start, seeds.end
+        ));
+    }
+    Ok(())
+}
+
+fn check_goal(template: &Template, name: &str) -> Result<(), String> {
+    let mut goals = template.0.iter().flat_map(|tier| tier.iter());
+    if goals.any(|g| g.name == name) {
+        Ok(())
+    } else {
+        Err(format!("The template doesn't contain the goal {:?}", name))
+    }
+}
+
+/// Parses the value of an option like `--max-type WF=2` into the type and the
+/// amount.
+fn type_limit<'v>(
+    template: &Template,
+    option: &str,
+    value: &'v str,
+) -> Result<(&'v str, usize), String> {
+    let invalid = || format!("The value {:?} of --{} is invalid", value, option);
+    let i = value.rfind('=').ok_or_else(invalid)?;
+    let (goal_type, count) = (&value[..i], &value[i + 1..]);
+    let mut goals = template.0.iter().flat_map(|tier| tier.iter());
+    if !goals.any(|g| g.types.iter().any(|t| t == goal_type)) {
+        return Err(format!(
+            "No goal of the template has the type {:?}",
+            goal_type
+        ));
+    }
+    Ok((goal_type, count.parse().map_err(|_| invalid())?))
+}
+
+fn analyze(args: &[String]) -> Result<(), String> {
+    let options = Options::parse(
+        args,
+        &[
+            "template", "mode", "size", "seeds", "threads", "format", "output",
+        ],
+    )?;
+    let template = load_template(options.get("template"))?;
+    let mode = parse_mode(&options, &template)?;
+    let size = options.parse_value("size")?.unwrap_or(5);
+    let seeds = seeds(&options, 0..10_000)?;
+    let threads = options.parse_value("threads")?.unwrap_or(0);
+    let analysis =
+        Analysis::with_threads(&template, mode, size, seeds, threads).map_err(|e| e.to_string())?;
+
+    match options.get("format").unwrap_or("text") {
+        "text" => print!("{}", analysis),
+        "json" => {
+            let json = analysis.to_json_string_pretty().unwrap();
+            match options.get("output") {
+                Some(output) => write(output, json.as_bytes())?,
+                None => println!("{}", json),
+            }
+        }
+        "csv" => {
+            let directory = Path::new(options.require("output")?);
+            fs::create_dir_all(directory)
+                .map_err(|e| format!("Can't create {}: {}", directory.display(), e))?;
+            for &(name, ref csv) in &[
+                ("goals.csv", analysis.goals_csv()),
+                ("positions.csv", analysis.positions_csv()),
+                ("types.csv", analysis.types_csv()),
+                ("lines.csv", analysis.lines_csv()),
+            ] {
+                let path = directory.join(name);
+                write(&path.to_string_lossy(), csv.as_bytes())?;
+            }
+        }
+        format => return Err(format!("Unknown format {:?}", format)),
+    }
+    Ok(())
+}
+
+/// The range of seeds passed as `--seeds`.
+fn seeds(options: &Options, default: Range<u32>) -> Result<Range<u32>, String> {
+    match options.get("seeds") {
+        Some(seeds) => {
+            parse_range(seeds).ok_or_else(|| format!("The value {:?} of --seeds is invalid", seeds))
+        }
+        None => Ok(default),
+    }
+}
+
+/// Parses a range of seeds like `0..1000`, which doesn't include the end.
+fn parse_range(range: &str) -> Option<Range<u32>> {
+    let i = range.find("..")?;
+    let start = range[..i].parse().ok()?;
+    let end = range[i + 2..].parse().ok()?;
+    if start > end {
+        return None;
+    }
+    Some(start..end)
+}
+
+fn validate(args: &[String]) -> Result<(), String> {
+    let options = Options::parse(args, &["template"])?;
+    let template = load_template(Some(options.require("template")?))?;
+    let report = template.validate();
+    print!("{}", report);
+    match report.errors().count() {
+        0 => {
+            println!("The template is valid");
+            Ok(())
+        }
+        1 => Err("The template has 1 error".into()),
+        count => Err(format!("The template has {} errors", count)),
+    }
+}
+
+fn convert(args: &[String]) -> Result<(), String> {
+    let options = Options::parse(args, &["input", "output", "from", "to"])?;
+    let input = options.require("input")?;
+    let output = options.require("output")?;
+    let extension = |path: &str| {
+        if path.ends_with(".js") {
+            "web"
+        } else {
+            "json"
+        }
+    };
+
+    let text = read(input)?;
+    let template = match options.get("from").unwrap_or_else(|| extension(input)) {
+        "json" => Template::from_json_str(&text).map_err(|e| e.to_string()),
+        "web" => web::from_str(&text),
+        format => return Err(format!("Unknown format {:?}", format)),
+    }
+    .map_err(|e| format!("Can't read the template {}: {}", input, e))?;
+
+    let text = match options.get("to").unwrap_or_else(|| extension(output)) {
+        "json" => template.to_json_string().unwrap(),
+        "pretty" => template.to_json_string_pretty().unwrap(),
+        "web" if template.1.is_some() => {
+            return Err("The web format can't store the template's curve".into())
+        }
+        "web" => web::to_string(&template),
+        format => return Err(format!("Unknown format {:?}", format)),
+    };
+    write(output, text.as_bytes())
+}
@@ -0,0 +1,408 @@
+//! The format of the web-based generator, which defines the difficulty tiers
+//! in JavaScript, starting at tier 1:
+//!
+//! ```js
+//! var bingoList = [];
+//! bingoList[1] = [
+//!   { name: "Red Coin Star in WF", types: ["WF"] },
+//! ];
+//! ```
+
+use serde_json;
+use speedrun_bingo::{Goal, Template, MAX_TIERS};
+use std::fmt::Write;
+use std::iter::Peekable;
+use std::str::Chars;
+
+pub fn to_string(template: &Template) -> String {
+    let mut js = String::from("var bingoList = [];\n");
+    for (tier, goals) in template.0.iter().enumerate() {
+        writeln!(js, "bingoList[{}] = [", tier + 1).unwrap();
+        for goal in goals {
+            write!(js, "  {{ name: {}, types: [", string(&goal.name)).unwrap();
+            for (i, ty) in goal.types.iter().enumerate() {
+                if i > 0 {
+                    js.push_str(", ");
+                }
+                js.push_str(&string(ty));
+            }
+            js.push(']');
+            if goal.special {
+                js.push_str(", special: true");
+            }
+            if let Some(ref icon) = goal.icon {
+                write!(js, ", icon: {}", string(icon)).unwrap();
+            }
+            js.push_str(" },\n");
+        }
+        js.push_str("];\n");
+    }
+    js
+}
+
+/// Writes the text as a string literal, which is the same in JSON and
+/// JavaScript.
+fn string(text: &str) -> String {
+    serde_json::to_string(text).unwrap()
+}
+
+/// Reads the difficulty tiers that are assigned to `bingoList`. Keys don't
+/// need to be quoted, strings may use single quotes and comments are
+/// skipped.
+pub fn from_str(js: &str) -> Result<Template, String> {
+    let mut tokens = Tokens::new(js);
+    let mut tiers = Vec::<Option<Vec<Goal>>>::new();
+
+    while tokens.peek()?.is_some() {
+        if tokens.eat(&Token::Ident("var".into()))? {
+            tokens.expect(&Token::Ident("bingoList".into()))?;
+            tokens.expect(&Token::Punct('='))?;
+            tokens.expect(&Token::Punct('['))?;
+            tokens.expect(&Token::Punct(']'))?;
+        } else {
+            tokens.expect(&Token::Ident("bingoList".into()))?;
+            tokens.expect(&Token::Punct('['))?;
+            let tier = match tokens.next()? {
+                Some(Token::Number(tier)) if tier >= 1 => tier,
+                token => return Err(unexpected(token, "a tier number starting at 1")),
+            };
+            if tier > MAX_TIERS as u64 {
+                return Err(format!("Tier {} is out of range", tier));
+            }
+            let tier = tier as usize;
+            tokens.expect(&Token::Punct(']'))?;
+            tokens.expect(&Token::Punct('='))?;
+            let goals = goals(&mut tokens)?;
+            if tiers.len() < tier {
+                tiers.resize(tier, None);
+            }
+            if tiers[tier - 1].is_some() {
+                return Err(format!("Tier {} is assigned more than once", tier));
+            }
+            tiers[tier - 1] = Some(goals);
+        }
+        tokens.eat(&Token::Punct(';'))?;
+    }
+
+    let mut template = Vec::new();
+    for (i, tier) in tiers.into_iter().enumerate() {
+        template.push(tier.ok_or_else(|| format!("Tier {} is missing", i + 1))?);
+    }
+    Ok(Template(template, None))
+}
+
+fn goals(tokens: &mut Tokens) -> Result<Vec<Goal>, String> {
+    let mut goals = Vec::new();
+    tokens.expect(&Token::Punct('['))?;
+    while !tokens.eat(&Token::Punct(']'))? {
+        tokens.expect(&Token::Punct('{'))?;
+        let mut goal = Goal {
+            name: String::new(),
+            types: Vec::new(),
+            special: false,
+            icon: None,
+        };
+        let mut has_name = false;
+        while !tokens.eat(&Token::Punct('}'))? {
+            let key = match tokens.next()? {
+                Some(Token::Ident(key)) | Some(Token::Str(key)) => key,
+                token => return Err(unexpected(token, "a key")),
+            };
+            tokens.expect(&Token::Punct(':'))?;
+            match &*key {
+                "name" => {
+                    goal.name = tokens.string()?;
+                    has_name = true;
+                }
+                "types" => {
+                    tokens.expect(&Token::Punct('['))?;
+                    while !tokens.eat(&Token::Punct(']'))? {
+                        goal.types.push(tokens.string()?);
+                        if !tokens.eat(&Token::Punct(','))? {
+                            tokens.expect(&Token::Punct(']'))?;
+                            break;
+                        }
+                    }
+                }
+                "special" => {
+                    goal.special = match tokens.next()? {
+                        Some(Token::Ident(ref value)) if value == "true" => true,
+                        Some(Token::Ident(ref value)) if value == "false" => false,
+                        token => return Err(unexpected(token, "true or false")),
+                    }
+                }
+                "icon" => goal.icon = Some(tokens.string()?),
+                _ => tokens.skip_value()?,
+            }
+            if !tokens.eat(&Token::Punct(','))? {
+                tokens.expect(&Token::Punct('}'))?;
+                break;
+            }
+        }
+        if !has_name {
+            return Err(format!("Goal {} doesn't have a name", goals.len()));
+        }
+        goals.push(goal);
+        if !tokens.eat(&Token::Punct(','))? {
+            tokens.expect(&Token::Punct(']'))?;
+            break;
+        }
+    }
+    Ok(goals)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Number(u64),
+    Punct(char),
+}
+
+fn unexpected(token: Option<Token>, expected: &str) -> String {
+    match token {
+        Some(token) => format!("Expected {}, but found {:?}", expected, token),
+        None => format!("Expected {}, but the file ended", expected),
+    }
+}
+
+struct Tokens<'a> {
+    chars: Peekable<Chars<'a>>,
+    peeked: Option<Option<Token>>,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(js: &'a str) -> Self {
+        Tokens {
+            chars: js.chars().peekable(),
+            peeked: None,
+        }
+    }
+
+    fn peek(&mut self) -> Result<Option<&Token>, String> {
+        if self.peeked.is_none() {
+            self.peeked = Some(self.read()?);
+        }
+        Ok(self.peeked.as_ref().unwrap().as_ref())
+    }
+
+    fn next(&mut self) -> Result<Option<Token>, String> {
+        match self.peeked.take() {
+            Some(token) => Ok(token),
+            None => self.read(),
+        }
+    }
+
+    /// Skips the token if it's the expected one.
+    fn eat(&mut self, expected: &Token) -> Result<bool, String> {
+        let found = self.peek()? == Some(expected);
+        if found {
+            self.next()?;
+        }
+        Ok(found)
+    }
+
+    fn expect(&mut self, expected: &Token) -> Result<(), String> {
+        match self.next()? {
+            Some(ref token) if token == expected => Ok(()),
+            token => Err(unexpected(token, &format!("{:?}", expected))),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        match self.next()? {
+            Some(Token::Str(text)) => Ok(text),
+            token => Err(unexpected(token, "a string")),
+        }
+    }
+
+    /// Skips a value of a key that isn't part of templates.
+    fn skip_value(&mut self) -> Result<(), String> {
+        let mut depth = 0;
+        loop {
+            match self.next()? {
+                Some(Token::Punct('[')) | Some(Token::Punct('{')) => depth += 1,
+                Some(Token::Punct(']')) | Some(Token::Punct('}')) if depth > 0 => depth -= 1,
+                Some(_) if depth > 0 => {}
+                Some(Token::Punct(c)) => return Err(unexpected(Some(Token::Punct(c)), "a value")),
+                Some(_) => {}
+                None => return Err(unexpected(None, "a value")),
+            }
+            if depth == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    fn read(&mut self) -> Result<Option<Token>, String> {
+        self.skip_whitespace_and_comments()?;
+        let c = match self.chars.next() {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+        let token = match c {
+            '"' | '\'' => Token::Str(self.read_string(c)?),
+            '0'..='9' => {
+                let mut number = c.to_digit(10).unwrap() as u64;
+                while let Some(digit) = self.chars.peek().and_then(|c| c.to_digit(10)) {
+                    self.chars.next();
+                    number = number
+                        .checked_mul(10)
+                        .and_then(|n| n.checked_add(digit as u64))
+                        .ok_or("A number is too large")?;
+                }
+                Token::Number(number)
+            }
+            c if c.is_alphabetic() || c == '_' || c == '$' => {
+                let mut ident = c.to_string();
+                while let Some(&c) = self.chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_' || c == '$') {
+                        break;
+                    }
+                    ident.push(c);
+                    self.chars.next();
+                }
+                Token::Ident(ident)
+            }
+            '[' | ']' | '{' | '}' | ':' | ',' | '=' | ';' => Token::Punct(c),
+            c => return Err(format!("Unexpected character {:?}", c)),
+        };
+        Ok(Some(token))
+    }
+
+    fn skip_whitespace_and_comments(&mut self) -> Result<(), String> {
+        loop {
+            match self.chars.peek() {
+                Some(c) if c.is_whitespace() => {
+                    self.chars.next();
+                }
+                Some(&'/') => {
+                    self.chars.next();
+                    match self.chars.next() {
+                        Some('/') => loop {
+                            match self.chars.next() {
+                                Some('\n') | None => break,
+                                Some(_) => {}
+                            }
+                        },
+                        Some('*') => {
+                            let mut previous = ' ';
+                            loop {
+                                match self.chars.next() {
+                                    Some('/') if previous == '*' => break,
+                                    Some(c) => previous = c,
+                                    None => return Err("A comment is never closed".into()),
+                                }
+                            }
+                        }
+                        _ => return Err("Unexpected character '/'".into()),
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn read_string(&mut self, quote: char) -> Result<String, String> {
+        let mut text = String::new();
+        loop {
+            let c = self.chars.next().ok_or("A string is never closed")?;
+            if c == quote {
+                return Ok(text);
+            }
+            if c != '\\' {
+                text.push(c);
+                continue;
+            }
+            let escaped = match self.chars.next().ok_or("A string is never closed")? {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                'u' => {
+                    let hex = self.chars.by_ref().take(4).collect::<String>();
+                    u32::from_str_radix(&hex, 16)
+                        .ok()
+                        .and_then(::std::char::from_u32)
+                        .ok_or_else(|| format!("Invalid escape sequence \\u{}", hex))?
+                }
+                c => c,
+            };
+            text.push(escaped);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sm64() {
+        let json = include_str!("../../templates/sm64.json");
+        let template = Template::from_json_str(json).unwrap();
+        let js = to_string(&template);
+        assert!(js.starts_with(
+            "var bingoList = [];\nbingoList[1] = [\n  \
+             { name: \"Red Coin Star in WF\", types: [\"WF\"] },\n"
+        ));
+        let converted = from_str(&js).unwrap();
+        assert_eq!(
+            converted.to_json_string().unwrap(),
+            template.to_json_string().unwrap()
+        );
+    }
+
+    #[test]
+    fn handwritten() {
+        let js = r#"
+            // Generated by hand
+            bingoList[2] = [{ 'name': 'Beat the King in BOB ★', types: [] }];
+            bingoList[1] = [
+                { name: "Red Coin \"Star\"", types: ["WF", "red"], special: true, icon: "★" },
+                /* The notes aren't part of the template */
+                { name: "100 Coins", types: ["coins"], notes: { tier: [1, "x"] }, },
+            ]
+        "#;
+        let template = from_str(js).unwrap();
+        assert_eq!(
+            template.to_json_string().unwrap(),
+            concat!(
+                r#"[[{"name":"Red Coin \"Star\"","types":["WF","red"],"special":true,"#,
+                r#""icon":"★"},{"name":"100 Coins","types":["coins"]}],"#,
+                r#"[{"name":"Beat the King in BOB ★","types":[]}]]"#
+            )
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            from_str("bingoList[2] = [];").unwrap_err(),
+            "Tier 1 is missing"
+        );
+        assert_eq!(
+            from_str("bingoList[0] = [];").unwrap_err(),
+            "Expected a tier number starting at 1, but found Number(0)"
+        );
+        assert_eq!(
+            from_str("bingoList[99999999999] = [];").unwrap_err(),
+            "Tier 99999999999 is out of range"
+        );
+        assert_eq!(
+            from_str("bingoList[33] = [];").unwrap_err(),
+            "Tier 33 is out of range"
+        );
+        assert_eq!(
+            from_str("bingoList[1] = [{ types: [] }];").unwrap_err(),
+            "Goal 0 doesn't have a name"
+        );
+        assert_eq!(
+            from_str("bingoList[1] = [{ name: 'WF }];").unwrap_err(),
+            "A string is never closed"
+        );
+        assert_eq!(
+            from_str("bingoList[1] = [").unwrap_err(),
+            "Expected Punct('{'), but the file ended"
+        );
+    }
+}
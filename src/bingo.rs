@@ -1,20 +1,126 @@
-use {generator, Template};
+use arrayvec::ArrayVec;
+use core::slice::Chunks;
+use serde::de::{Error, Unexpected};
+use {generator, DifficultyMapping, GenerateError, Seed, Template};
+
+/// The largest supported width and height of a board.
+pub const MAX_SIZE: usize = 10;
+/// The largest supported amount of cells on a board.
+pub const MAX_CELLS: usize = MAX_SIZE * MAX_SIZE;
+
+/// The index of the cell on a board with the size, counting row by row, as
+/// long as the cell is on the board.
+pub fn cell_index(size: usize, x: usize, y: usize) -> Option<usize> {
+    if x < size && y < size {
+        Some(y * size + x)
+    } else {
+        None
+    }
+}
+
+/// Makes sure that a board with the size has at least one cell and isn't
+/// larger than the supported size.
+pub fn check_size(size: usize) {
+    assert!(
+        size > 0 && size <= MAX_SIZE,
+        "The size {} is not between 1 and {}",
+        size,
+        MAX_SIZE
+    );
+}
+
+/// Makes sure that deserialized per cell data of a board with the size has
+/// exactly one entry for each cell.
+pub fn check_cells<E: Error>(size: usize, len: usize) -> Result<(), E> {
+    if size == 0 || size > MAX_SIZE {
+        return Err(E::invalid_value(
+            Unexpected::Unsigned(size as u64),
+            &"a supported board size",
+        ));
+    }
+    if len != size * size {
+        return Err(E::invalid_length(len, &"one entry per cell"));
+    }
+    Ok(())
+}
 
 #[derive(Debug, Eq, PartialEq)]
 pub struct Bingo<'a> {
-    pub cells: [[&'a str; 5]; 5],
+    /// The width and height of the board.
+    pub size: usize,
+    /// The goals of all the cells, row by row.
+    pub cells: ArrayVec<[&'a str; MAX_CELLS]>,
+    /// The icons of the goals of all the cells, row by row.
+    pub icons: ArrayVec<[Option<&'a str>; MAX_CELLS]>,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum Mode {
+    /// Only uses the easier half of the difficulty tiers.
     Short,
+    /// Uses all the difficulty tiers.
     Normal,
+    /// Only uses the harder half of the difficulty tiers.
     Long,
+    /// Only uses the harder half of the difficulty tiers like long mode, but
+    /// only draws the goals marked as special.
     Special,
 }
 
 impl<'a> Bingo<'a> {
-    pub fn new(seed: u32, mode: Mode, template: &'a Template) -> Self {
-        generator::generate(seed, mode, template)
+    pub fn new<'s, S, M>(seed: S, mode: M, template: &'a Template) -> Self
+    where
+        S: Into<Seed<'s>>,
+        M: DifficultyMapping,
+    {
+        Self::new_sized(seed, mode, 5, template)
+    }
+
+    pub fn new_sized<'s, S, M>(seed: S, mode: M, size: usize, template: &'a Template) -> Self
+    where
+        S: Into<Seed<'s>>,
+        M: DifficultyMapping,
+    {
+        generator::generate(seed, mode, size, template)
+    }
+
+    pub fn try_new<'s, S, M>(
+        seed: S,
+        mode: M,
+        template: &'a Template,
+    ) -> Result<Self, GenerateError>
+    where
+        S: Into<Seed<'s>>,
+        M: DifficultyMapping,
+    {
+        Self::try_new_sized(seed, mode, 5, template)
+    }
+
+    pub fn try_new_sized<'s, S, M>(
+        seed: S,
+        mode: M,
+        size: usize,
+        template: &'a Template,
+    ) -> Result<Self, GenerateError>
+    where
+        S: Into<Seed<'s>>,
+        M: DifficultyMapping,
+    {
+        generator::try_generate(seed, mode, size, template)
+    }
+
+    /// The goal of the cell, unless the cell is outside of the board.
+    pub fn cell(&self, x: usize, y: usize) -> Option<&'a str> {
+        cell_index(self.size, x, y).map(|i| self.cells[i])
+    }
+
+    /// The icon of the cell's goal, if it has one and the cell is on the
+    /// board.
+    pub fn icon(&self, x: usize, y: usize) -> Option<&'a str> {
+        cell_index(self.size, x, y).and_then(|i| self.icons[i])
+    }
+
+    pub fn rows<'s>(&'s self) -> Chunks<'s, &'a str> {
+        self.cells.chunks(self.size)
     }
 }
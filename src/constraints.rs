@@ -0,0 +1,81 @@
+use Goal;
+
+/// Restricts which goals the generator may put on the board, without having to
+/// edit the template. The default constraints don't restrict anything, so the
+/// boards are the same as without any constraints.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Constraints<'c> {
+    /// The names of the goals that never get placed on the board.
+    pub banned_goals: &'c [&'c str],
+    /// Goals with any of these types never get placed on the board.
+    pub banned_types: &'c [&'c str],
+    /// The goals that always get placed on the board.
+    pub required: &'c [Requirement<'c>],
+    /// The maximum amount of goals on the board that may share a type.
+    pub max_per_type: Option<usize>,
+}
+
+/// A goal that always gets placed on the board.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Requirement<'c> {
+    /// The name of the goal.
+    pub goal: &'c str,
+    /// The column and row of the cell the goal gets placed in. Without a
+    /// position, the goal gets placed in the first free cell whose difficulty
+    /// tier is closest to the goal's tier.
+    pub position: Option<(usize, usize)>,
+}
+
+impl<'c> Requirement<'c> {
+    pub fn new(goal: &'c str) -> Self {
+        Requirement {
+            goal: goal,
+            position: None,
+        }
+    }
+
+    pub fn at(goal: &'c str, x: usize, y: usize) -> Self {
+        Requirement {
+            goal: goal,
+            position: Some((x, y)),
+        }
+    }
+}
+
+impl<'c> Constraints<'c> {
+    /// Whether the goal is banned either directly or through one of its
+    /// types.
+    pub fn is_banned(&self, goal: &Goal) -> bool {
+        let name: &str = &goal.name;
+        self.banned_goals.contains(&name)
+            || goal
+                .types
+                .iter()
+                .any(|t| self.banned_types.iter().any(|&b| b == &**t))
+    }
+
+    /// Whether the goal is one of the required goals.
+    pub fn is_required(&self, goal: &Goal) -> bool {
+        self.required.iter().any(|r| r.goal == &*goal.name)
+    }
+
+    /// Whether the goal can be added to the goals already on the board
+    /// without exceeding the maximum amount of goals per type.
+    pub fn fits<'a, I>(&self, goal: &Goal, placed: I) -> bool
+    where
+        I: IntoIterator<Item = &'a Goal> + Clone,
+    {
+        let max = match self.max_per_type {
+            Some(max) => max,
+            None => return true,
+        };
+        goal.types.iter().all(|t| {
+            let count = placed
+                .clone()
+                .into_iter()
+                .filter(|g| g.types.iter().any(|o| o == t))
+                .count();
+            count < max
+        })
+    }
+}
@@ -0,0 +1,111 @@
+use Mode;
+
+/// The amount of difficulty levels the magic square values are scaled to,
+/// regardless of the size of the board.
+pub const LEVELS: usize = 25;
+
+/// Decides which difficulty tier of the template a cell draws its goal from.
+pub trait DifficultyMapping {
+    /// Maps the cell's magic square value, which is between 0 and 24, to the
+    /// index of a difficulty tier.
+    fn tier(&self, value: usize) -> usize;
+
+    /// Whether only the goals marked as special should be drawn, as long as
+    /// the difficulty tier contains any.
+    fn special_only(&self) -> bool {
+        false
+    }
+
+    /// The amount of difficulty tiers a template needs to be used with this
+    /// mapping.
+    fn required_tiers(&self) -> usize {
+        (0..LEVELS).map(|value| self.tier(value) + 1).max().unwrap()
+    }
+}
+
+impl DifficultyMapping for Mode {
+    fn tier(&self, value: usize) -> usize {
+        match *self {
+            Mode::Short => value / 2, // if short mode, limit difficulty
+            Mode::Long | Mode::Special => (value + 25) / 2,
+            Mode::Normal => value,
+        }
+    }
+
+    fn special_only(&self) -> bool {
+        *self == Mode::Special
+    }
+}
+
+/// A difficulty curve that lists the tier for each of the magic square values.
+/// Curves can be serialized, so custom curves can be loaded from JSON, either
+/// on their own or as part of the template they are meant for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Curve(pub [usize; LEVELS]);
+
+impl Curve {
+    /// Creates a curve that maps the magic square values linearly onto the
+    /// tiers between `min` and `max`, both inclusive.
+    pub fn linear(min: usize, max: usize) -> Self {
+        let mut tiers = [0; LEVELS];
+        for (value, tier) in tiers.iter_mut().enumerate() {
+            *tier = if max >= min {
+                min + value * (max - min + 1) / LEVELS
+            } else {
+                min - value * (min - max + 1) / LEVELS
+            };
+        }
+        Curve(tiers)
+    }
+}
+
+impl DifficultyMapping for Curve {
+    fn tier(&self, value: usize) -> usize {
+        self.0[value]
+    }
+}
+
+impl<F> DifficultyMapping for F
+where
+    F: Fn(usize) -> usize,
+{
+    fn tier(&self, value: usize) -> usize {
+        self(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modes() {
+        assert_eq!(Mode::Short.required_tiers(), 13);
+        assert_eq!(Mode::Normal.required_tiers(), 25);
+        assert_eq!(Mode::Long.required_tiers(), 25);
+        assert_eq!(Mode::Long.tier(0), 12);
+        assert_eq!(Mode::Special.required_tiers(), 25);
+        assert_eq!(Mode::Special.tier(0), 12);
+    }
+
+    #[test]
+    fn linear() {
+        for value in 0..LEVELS {
+            assert_eq!(Curve::linear(0, 24).tier(value), Mode::Normal.tier(value));
+            assert_eq!(Curve::linear(0, 12).tier(value), Mode::Short.tier(value));
+        }
+        let curve = Curve::linear(6, 18);
+        assert_eq!(curve.tier(0), 6);
+        assert_eq!(curve.tier(24), 18);
+        assert_eq!(curve.required_tiers(), 19);
+        assert_eq!(Curve::linear(24, 0).tier(0), 24);
+        assert_eq!(Curve::linear(24, 0).tier(24), 0);
+    }
+
+    #[test]
+    fn closure() {
+        let extreme = |value: usize| 20 + value / 5;
+        assert_eq!(extreme.tier(24), 24);
+        assert_eq!(extreme.required_tiers(), 25);
+    }
+}
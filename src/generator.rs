@@ -1,181 +1,1178 @@
-use arrayvec::{ArrayString, ArrayVec};
-use core::fmt::Write;
-use {Bingo, Goal, Mode, Template};
-use seed_random::SeedRandom;
-
-static LINE_CHECK_LIST: &[&[usize]; 25] = &[
-    &[1usize, 2, 3, 4, 5, 10, 15, 20, 6, 12, 18, 24] as &[_],
-    &[0, 2, 3, 4, 6, 11, 16, 21],
-    &[0, 1, 3, 4, 7, 12, 17, 22],
-    &[0, 1, 2, 4, 8, 13, 18, 23],
-    &[0, 1, 2, 3, 8, 12, 16, 20, 9, 14, 19, 24],
-    &[0, 10, 15, 20, 6, 7, 8, 9],
-    &[0, 12, 18, 24, 5, 7, 8, 9, 1, 11, 16, 21],
-    &[5, 6, 8, 9, 2, 12, 17, 22],
-    &[4, 12, 16, 20, 9, 7, 6, 5, 3, 13, 18, 23],
-    &[4, 14, 19, 24, 8, 7, 6, 5],
-    &[0, 5, 15, 20, 11, 12, 13, 14],
-    &[1, 6, 16, 21, 10, 12, 13, 14],
-    &[0, 6, 12, 18, 24, 20, 16, 8, 4, 2, 7, 17, 22, 10, 11, 13, 14],
-    &[3, 8, 18, 23, 10, 11, 12, 14],
-    &[4, 9, 19, 24, 10, 11, 12, 13],
-    &[0, 5, 10, 20, 16, 17, 18, 19],
-    &[15, 17, 18, 19, 1, 6, 11, 21, 20, 12, 8, 4],
-    &[15, 16, 18, 19, 2, 7, 12, 22],
-    &[15, 16, 17, 19, 23, 13, 8, 3, 24, 12, 6, 0],
-    &[4, 9, 14, 24, 15, 16, 17, 18],
-    &[0, 5, 10, 15, 16, 12, 8, 4, 21, 22, 23, 24],
-    &[20, 22, 23, 24, 1, 6, 11, 16],
-    &[2, 7, 12, 17, 20, 21, 23, 24],
-    &[20, 21, 22, 24, 3, 8, 13, 18],
-    &[0, 6, 12, 18, 20, 21, 22, 23, 19, 14, 9, 4],
-];
-
-fn difficulty(seed: u32, i: usize, mode: Mode) -> i64 {
-    // To create the magic square we need 2 random orderings of the numbers 0, 1, 2, 3, 4.
-    // The following creates those orderings and calls them Table5 and Table1
-
-    let num3 = seed % 1000; // Table5 will use the ones, tens, and hundreds digits.
-
-    let rem8 = num3 % 8;
-    let rem4 = rem8 / 2;
-    let rem2 = rem8 % 2;
-    let rem5 = num3 % 5;
-    let rem3 = num3 % 3; // Note that Rem2, Rem3, Rem4, and Rem5 are mathematically independent.
-    let rem_t = num3 / 120; // This is between 0 and 8
-
-    // The idea is to begin with an array containing a single number, 0.
-    // Each number 1 through 4 is added in a random spot in the array's current size.
-    // The result - the numbers 0 to 4 are in the array in a random (and uniform) order.
-    let mut table5 = ArrayVec::<[_; 5]>::new();
-    table5.push(0);
-    table5.insert(rem2 as usize, 1);
-    table5.insert(rem3 as usize, 2);
-    table5.insert(rem4 as usize, 3);
-    table5.insert(rem5 as usize, 4);
-
-    let num3 = seed / 1000; // Table1 will use the next 3 digits.
-    let num3 = num3 % 1000;
-
-    let rem8 = num3 % 8;
-    let rem4 = rem8 / 2;
-    let rem2 = rem8 % 2;
-    let rem5 = num3 % 5;
-    let rem3 = num3 % 3;
-    let rem_t = rem_t * 8 + num3 / 120; // This is between 0 and 64.
-
-    let mut table1 = ArrayVec::<[_; 5]>::new();
-    table1.push(0);
-    table1.insert(rem2 as usize, 1);
-    table1.insert(rem3 as usize, 2);
-    table1.insert(rem4 as usize, 3);
-    table1.insert(rem5 as usize, 4);
-
-    let i = i - 1;
-    let rem_t = rem_t % 5; // Between 0 and 4, fairly uniformly.
-    let x = (i + rem_t as usize) % 5; // RemT is horizontal shift to put any diagonal on the main diagonal.
-    let y = i / 5;
-
-    // The Tables are set into a single magic square template
-    // Some are the same up to some rotation, reflection, or row permutation.
-    // However, all genuinely different magic squares can arise in this fashion.
-    let e5 = table5[((x + 3 * y) % 5) as usize];
-    let e1 = table1[((3 * x + y) % 5) as usize];
-
-    // Table5 controls the 5* part and Table1 controls the 1* part.
-    let value = 5 * e5 + e1;
-
-    match mode {
-        Mode::Short => value / 2, // if short mode, limit difficulty
-        Mode::Long | Mode::Special => (value + 25) / 2,
-        _ => value,
-    }
-}
-
-fn check_line<'a, I, U>(i: usize, types_a: &'a I, gen_cells: &[GenCell]) -> usize
+use arrayvec::ArrayVec;
+use core::fmt;
+use bingo::{MAX_CELLS, MAX_SIZE};
+use difficulty::{DifficultyMapping, LEVELS};
+#[cfg(feature = "std")]
+use Line;
+use {Bingo, Constraints, Goal, Seed, Template};
+
+struct MagicSquare {
+    size: usize,
+    table5: ArrayVec<[usize; MAX_SIZE]>,
+    table1: ArrayVec<[usize; MAX_SIZE]>,
+    shift: usize,
+}
+
+impl MagicSquare {
+    fn new(seed: u32, size: usize) -> Self {
+        if size == 5 {
+            return Self::classic(seed);
+        }
+
+        match size % 4 {
+            // Singly even squares are built from an odd square of half the
+            // size, so they use its orderings and shift.
+            2 => MagicSquare {
+                size: size,
+                ..Self::new(seed, size / 2)
+            },
+            0 => MagicSquare {
+                size: size,
+                table5: symmetric_ordering(seed % 1000, size),
+                table1: symmetric_ordering(seed / 1000 % 1000, size),
+                shift: 0,
+            },
+            // Odd boards of other sizes use the same idea as the classic 5x5
+            // square, but derive the orderings of 0..size from the seed in a
+            // generic way. The lowest three digits create the first ordering,
+            // the next three digits create the second ordering and the
+            // remaining digits select the horizontal shift. Squares whose size
+            // is a multiple of three don't stay magic when they are shifted.
+            _ => MagicSquare {
+                size: size,
+                table5: ordering(seed % 1000, size),
+                table1: ordering(seed / 1000 % 1000, size),
+                shift: match size % 3 {
+                    0 => 0,
+                    _ => (seed / 1_000_000) as usize % size,
+                },
+            },
+        }
+    }
+
+    fn classic(seed: u32) -> Self {
+        // To create the magic square we need 2 random orderings of the numbers 0, 1, 2, 3, 4.
+        // The following creates those orderings and calls them Table5 and Table1
+
+        let num3 = seed % 1000; // Table5 will use the ones, tens, and hundreds digits.
+
+        let rem8 = num3 % 8;
+        let rem4 = rem8 / 2;
+        let rem2 = rem8 % 2;
+        let rem5 = num3 % 5;
+        let rem3 = num3 % 3; // Note that Rem2, Rem3, Rem4, and Rem5 are mathematically independent.
+        let rem_t = num3 / 120; // This is between 0 and 8
+
+        // The idea is to begin with an array containing a single number, 0.
+        // Each number 1 through 4 is added in a random spot in the array's current size.
+        // The result - the numbers 0 to 4 are in the array in a random (and uniform) order.
+        let mut table5 = ArrayVec::new();
+        table5.push(0);
+        table5.insert(rem2 as usize, 1);
+        table5.insert(rem3 as usize, 2);
+        table5.insert(rem4 as usize, 3);
+        table5.insert(rem5 as usize, 4);
+
+        let num3 = seed / 1000; // Table1 will use the next 3 digits.
+        let num3 = num3 % 1000;
+
+        let rem8 = num3 % 8;
+        let rem4 = rem8 / 2;
+        let rem2 = rem8 % 2;
+        let rem5 = num3 % 5;
+        let rem3 = num3 % 3;
+        let rem_t = rem_t * 8 + num3 / 120; // This is between 0 and 64.
+
+        let mut table1 = ArrayVec::new();
+        table1.push(0);
+        table1.insert(rem2 as usize, 1);
+        table1.insert(rem3 as usize, 2);
+        table1.insert(rem4 as usize, 3);
+        table1.insert(rem5 as usize, 4);
+
+        MagicSquare {
+            size: 5,
+            table5: table5,
+            table1: table1,
+            shift: rem_t as usize % 5, // Between 0 and 4, fairly uniformly.
+        }
+    }
+
+    /// The value of the i-th cell, which is between 0 and size * size.
+    fn value(&self, i: usize) -> usize {
+        let size = self.size;
+        let (x, y) = (i % size, i / size);
+
+        match size % 4 {
+            0 => {
+                // Doubly even squares count up row by row, except for the
+                // cells on the diagonals of every 4x4 block, which count down
+                // instead. Both orderings map opposite numbers to opposite
+                // numbers, so every line keeps its sum.
+                let (i5, i1) = if x % 4 == y % 4 || x % 4 + y % 4 == 3 {
+                    (size - 1 - y, size - 1 - x)
+                } else {
+                    (y, x)
+                };
+                size * self.table5[i5] + self.table1[i1]
+            }
+            2 => {
+                // Singly even squares use Conway's LUX method. Every cell of
+                // the odd half sized square becomes a 2x2 block filled in the
+                // shape of an L, U or X. The upper half of the blocks are Ls,
+                // followed by a row of Us and Xs for the rest, except that the
+                // middle U swaps places with the L above it.
+                let half = size / 2;
+                let (bx, by) = (x / 2, y / 2);
+                let k = half / 2;
+                let mut letter = if by <= k {
+                    0
+                } else if by == k + 1 {
+                    1
+                } else {
+                    2
+                };
+                if bx == k && (by == k || by == k + 1) {
+                    letter = 1 - letter;
+                }
+                4 * self.odd_value(half, bx, by) + LUX[letter][y % 2][x % 2]
+            }
+            _ => self.odd_value(size, x, y),
+        }
+    }
+
+    /// The value of the cell at x, y of an odd square with the given size.
+    fn odd_value(&self, size: usize, x: usize, y: usize) -> usize {
+        let x = (x + self.shift) % size; // The shift puts any diagonal on the main diagonal.
+
+        // The Tables are set into a single magic square template
+        // Some are the same up to some rotation, reflection, or row permutation.
+        // However, all genuinely different magic squares can arise in this fashion.
+        let (i5, i1) = match (size, size % 3) {
+            (5, _) => ((x + 3 * y) % 5, (3 * x + y) % 5),
+            (_, 0) => {
+                // These squares can't be magic along every broken diagonal, so
+                // the 5* part stays in the middle along the anti diagonal and
+                // the 1* part stays in the middle along the main diagonal.
+                let middle = (size - 1) / 2;
+                let c5 = position(&self.table5, middle);
+                let c1 = position(&self.table1, middle);
+                ((x + y + c5 + 1) % size, (x + size - y + c1) % size)
+            }
+            _ => ((x + 2 * y) % size, (2 * x + y) % size),
+        };
+
+        // Table5 controls the 5* part and Table1 controls the 1* part.
+        size * self.table5[i5] + self.table1[i1]
+    }
+}
+
+/// The 2x2 blocks of the L, U and X shapes used by the LUX method.
+const LUX: [[[usize; 2]; 2]; 3] = [[[3, 0], [1, 2]], [[0, 3], [1, 2]], [[0, 3], [2, 1]]];
+
+/// The position of n in the ordering.
+fn position(table: &[usize], n: usize) -> usize {
+    table.iter().position(|&t| t == n).unwrap()
+}
+
+/// Creates an ordering of the numbers 0 to size - 1 by inserting each number
+/// at a position picked by the next digit of `num` in a mixed radix system.
+fn ordering(mut num: u32, size: usize) -> ArrayVec<[usize; MAX_SIZE]> {
+    let mut table = ArrayVec::new();
+    table.push(0);
+    for n in 1..size {
+        let radix = n as u32 + 1;
+        table.insert((num % radix) as usize, n);
+        num /= radix;
+    }
+    table
+}
+
+/// Creates an ordering of the numbers 0 to size - 1 for an even size that
+/// maps the numbers i and size - 1 - i to opposite numbers.
+fn symmetric_ordering(num: u32, size: usize) -> ArrayVec<[usize; MAX_SIZE]> {
+    let half = size / 2;
+    let mut flips = num / (1..half as u32 + 1).product::<u32>();
+    let mut table = (0..size).collect::<ArrayVec<[usize; MAX_SIZE]>>();
+    for (i, n) in ordering(num, half).into_iter().enumerate() {
+        let (low, high) = if flips % 2 == 1 {
+            (size - 1 - n, n)
+        } else {
+            (n, size - 1 - n)
+        };
+        table[i] = low;
+        table[size - 1 - i] = high;
+        flips /= 2;
+    }
+    table
+}
+
+/// The magic square value of the i-th cell, scaled to the difficulty levels.
+fn level(square: &MagicSquare, i: usize) -> usize {
+    let cells = square.size * square.size;
+    square.value(i) * LEVELS / cells
+}
+
+/// Whether the cells i and j are on the same row, column or diagonal.
+fn shares_line(size: usize, i: usize, j: usize) -> bool {
+    let (xi, yi) = (i % size, i / size);
+    let (xj, yj) = (j % size, j / size);
+    xi == xj || yi == yj || (xi == yi && xj == yj) || (xi + yi == size - 1 && xj + yj == size - 1)
+}
+
+fn check_line<'a, I, U>(size: usize, i: usize, types_a: &'a I, gen_cells: &[Option<&Goal>]) -> usize
 where
     &'a I: IntoIterator<Item = U>,
     U: AsRef<str>,
 {
     let mut synergy = 0;
 
-    for j in 0..LINE_CHECK_LIST[i].len() {
-        if let Some(types_b) = gen_cells.get(LINE_CHECK_LIST[i][j]) {
-            let types_b = &types_b.goal.types;
-            for (k, tk) in types_a.into_iter().enumerate() {
-                let tk = tk.as_ref();
-                for (l, tl) in types_b.iter().enumerate() {
-                    if tk == tl {
-                        synergy += 1; // if match increase
-                        if k == 0 {
-                            synergy += 1; // if main type increase
-                        }
-                        if l == 0 {
-                            synergy += 1; // if main type increase
-                        }
-                    }
+    for (j, goal) in gen_cells.iter().enumerate() {
+        if j != i && shares_line(size, i, j) {
+            if let Some(goal) = *goal {
+                synergy += pair_synergy(types_a, goal);
+            }
+        }
+    }
+
+    synergy
+}
+
+fn pair_synergy<'a, I, U>(types_a: &'a I, goal: &Goal) -> usize
+where
+    &'a I: IntoIterator<Item = U>,
+    U: AsRef<str>,
+{
+    let mut synergy = 0;
+    let types_b = &goal.types;
+    for (k, tk) in types_a.into_iter().enumerate() {
+        let tk = tk.as_ref();
+        for (l, tl) in types_b.iter().enumerate() {
+            if tk == tl {
+                synergy += 1; // if match increase
+                if k == 0 {
+                    synergy += 1; // if main type increase
+                }
+                if l == 0 {
+                    synergy += 1; // if main type increase
                 }
             }
         }
     }
+    synergy
+}
 
+/// The synergy of all the pairs of goals in the line, scored the same way the
+/// generator scores the goals it considers for a cell. Only the analysis and
+/// the search use this.
+#[cfg(feature = "std")]
+pub fn line_synergy(size: usize, line: Line, goals: &[&Goal]) -> usize {
+    let cells = line.cells(size).collect::<ArrayVec<[usize; MAX_SIZE]>>();
+    let mut synergy = 0;
+    for (n, &i) in cells.iter().enumerate() {
+        for &j in &cells[n + 1..] {
+            synergy += pair_synergy(&goals[i].types, goals[j]);
+        }
+    }
     synergy
 }
 
+/// The goals of a difficulty tier that the generator draws from.
+struct Pool<'a> {
+    goals: &'a [Goal],
+    special_only: bool,
+    len: usize,
+}
+
+impl<'a> Pool<'a> {
+    fn new(goals: &'a [Goal], special_only: bool) -> Self {
+        let special_count = goals.iter().filter(|g| g.special).count();
+        if special_only && special_count > 0 {
+            Pool {
+                goals: goals,
+                special_only: true,
+                len: special_count,
+            }
+        } else {
+            Pool {
+                goals: goals,
+                special_only: false,
+                len: goals.len(),
+            }
+        }
+    }
+
+    fn get(&self, index: usize) -> &'a Goal {
+        &self.goals[self.tier_index(index)]
+    }
+
+    /// The index in the whole difficulty tier of the pool's n-th goal.
+    fn tier_index(&self, index: usize) -> usize {
+        if self.special_only {
+            self.goals
+                .iter()
+                .enumerate()
+                .filter(|&(_, g)| g.special)
+                .nth(index)
+                .unwrap()
+                .0
+        } else {
+            index
+        }
+    }
+}
+
 struct GenCell<'a> {
     goal: &'a Goal,
     synergy: usize,
+    /// The index of this goal among the considered candidates.
+    candidate: usize,
+}
+
+/// Gets notified about every decision the generator makes.
+pub trait Observer<'a> {
+    /// The generator starts looking for a goal for the cell.
+    fn cell(&mut self, _index: usize, _level: usize, _tier: usize, _offset: usize) {}
+    /// The generator considered the goal for the current cell.
+    fn candidate(&mut self, _goal: &'a Goal, _synergy: usize) {}
+    /// The generator picked the goal it considered as the n-th candidate.
+    fn chosen(&mut self, _candidate: usize) {}
+}
+
+impl<'a> Observer<'a> for () {}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GenerateError {
+    /// The board needs a difficulty tier that the template doesn't have.
+    MissingTier { tier: usize, tier_count: usize },
+    /// The board needs a difficulty tier that doesn't contain any goals.
+    EmptyTier { tier: usize },
+    /// The board can't have the requested size.
+    InvalidSize { size: usize },
+    /// The template doesn't contain the goal of the n-th requirement.
+    UnknownGoal { requirement: usize },
+    /// The goal of the n-th requirement can't be placed, because its cell is
+    /// outside of the board or already taken, the goal is banned or it would
+    /// exceed the maximum amount of goals per type.
+    UnplaceableGoal { requirement: usize },
+    /// The constraints exclude every goal of the cell's difficulty tier.
+    NoEligibleGoal { cell: usize, tier: usize },
+}
+
+impl fmt::Display for GenerateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GenerateError::MissingTier { tier, tier_count } => write!(
+                f,
+                "The template has {} difficulty tiers, but tier {} is required",
+                tier_count, tier
+            ),
+            GenerateError::EmptyTier { tier } => {
+                write!(f, "Difficulty tier {} of the template is empty", tier)
+            }
+            GenerateError::InvalidSize { size: 2 } => {
+                write!(f, "There are no balanced 2x2 boards")
+            }
+            GenerateError::InvalidSize { size } => write!(
+                f,
+                "Boards need to be between 1x1 and {max}x{max}, not {size}x{size}",
+                max = MAX_SIZE,
+                size = size
+            ),
+            GenerateError::UnknownGoal { requirement } => write!(
+                f,
+                "The goal of requirement {} is not part of the template",
+                requirement
+            ),
+            GenerateError::UnplaceableGoal { requirement } => write!(
+                f,
+                "The goal of requirement {} can't be placed on the board",
+                requirement
+            ),
+            GenerateError::NoEligibleGoal { cell, tier } => write!(
+                f,
+                "The constraints exclude every goal of difficulty tier {} for cell {}",
+                tier, cell
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for GenerateError {
+    fn description(&self) -> &str {
+        match *self {
+            GenerateError::MissingTier { .. } => "missing difficulty tier",
+            GenerateError::EmptyTier { .. } => "empty difficulty tier",
+            GenerateError::InvalidSize { .. } => "invalid board size",
+            GenerateError::UnknownGoal { .. } => "unknown required goal",
+            GenerateError::UnplaceableGoal { .. } => "unplaceable required goal",
+            GenerateError::NoEligibleGoal { .. } => "no eligible goal",
+        }
+    }
+}
+
+pub fn generate<'a, 's, S, M>(seed: S, mode: M, size: usize, template: &'a Template) -> Bingo<'a>
+where
+    S: Into<Seed<'s>>,
+    M: DifficultyMapping,
+{
+    match try_generate(seed, mode, size, template) {
+        Ok(bingo) => bingo,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+pub fn try_generate<'a, 's, S, M>(
+    seed: S,
+    mode: M,
+    size: usize,
+    template: &'a Template,
+) -> Result<Bingo<'a>, GenerateError>
+where
+    S: Into<Seed<'s>>,
+    M: DifficultyMapping,
+{
+    generate_observed(seed, mode, size, &Constraints::default(), template, &mut ())
 }
 
-pub fn generate<'a>(seed: u32, mode: Mode, template: &'a Template) -> Bingo<'a> {
-    let mut seed_str = ArrayString::<[_; 10]>::new();
-    write!(seed_str, "{}", seed).unwrap();
-    let mut random = SeedRandom::new(seed_str.as_bytes().iter().cloned().collect());
+/// Looks up the goals of the difficulty tier.
+fn tier_goals(template: &Template, tier: usize) -> Result<&[Goal], GenerateError> {
+    let goals = template.0.get(tier).ok_or(GenerateError::MissingTier {
+        tier: tier,
+        tier_count: template.0.len(),
+    })?;
+    if goals.is_empty() {
+        return Err(GenerateError::EmptyTier { tier: tier });
+    }
+    Ok(&goals[..])
+}
 
-    let mut cells = <[[&str; 5]; 5]>::default();
+/// Places the required goals on the board before any other goal gets picked.
+fn place_required<'a, M: DifficultyMapping>(
+    square: &MagicSquare,
+    mode: &M,
+    constraints: &Constraints,
+    template: &'a Template,
+    cells: &mut [Option<&'a Goal>],
+) -> Result<(), GenerateError> {
+    let size = square.size;
+
+    let find = |requirement: usize| {
+        let name = constraints.required[requirement].goal;
+        template
+            .0
+            .iter()
+            .enumerate()
+            .flat_map(|(tier, goals)| goals.iter().map(move |g| (tier, g)))
+            .find(|&(_, g)| &*g.name == name)
+            .ok_or(GenerateError::UnknownGoal {
+                requirement: requirement,
+            })
+    };
+    // Required goals go through the same constraints as the others, so a
+    // requirement can't sneak a banned goal or one type too many onto the
+    // board.
+    let check = |requirement: usize, goal: &Goal, cells: &[Option<&Goal>]| {
+        if constraints.is_banned(goal) || !constraints.fits(goal, cells.iter().filter_map(|&g| g)) {
+            return Err(GenerateError::UnplaceableGoal {
+                requirement: requirement,
+            });
+        }
+        Ok(())
+    };
+
+    // The goals with a fixed position get placed first, so the others can't
+    // take their cells.
+    for (requirement, r) in constraints.required.iter().enumerate() {
+        if let Some((x, y)) = r.position {
+            let (_, goal) = find(requirement)?;
+            check(requirement, goal, cells)?;
+            if x >= size || y >= size || cells[y * size + x].is_some() {
+                return Err(GenerateError::UnplaceableGoal {
+                    requirement: requirement,
+                });
+            }
+            cells[y * size + x] = Some(goal);
+        }
+    }
+
+    for (requirement, r) in constraints.required.iter().enumerate() {
+        if r.position.is_none() {
+            let (goal_tier, goal) = find(requirement)?;
+            check(requirement, goal, cells)?;
+            let distance = |i: usize| mode.tier(level(square, i)).abs_diff(goal_tier);
+            let i = (0..size * size)
+                .filter(|&i| cells[i].is_none())
+                .min_by_key(|&i| (distance(i), i))
+                .ok_or(GenerateError::UnplaceableGoal {
+                    requirement: requirement,
+                })?;
+            cells[i] = Some(goal);
+        }
+    }
+
+    Ok(())
+}
+
+pub fn generate_observed<'a, 's, S, M, O>(
+    seed: S,
+    mode: M,
+    size: usize,
+    constraints: &Constraints,
+    template: &'a Template,
+    observer: &mut O,
+) -> Result<Bingo<'a>, GenerateError>
+where
+    S: Into<Seed<'s>>,
+    M: DifficultyMapping,
+    O: Observer<'a>,
+{
+    let goals = generate_goals(seed.into(), &mode, size, constraints, template, observer)?;
+
+    // populate the actual table
+    let cells = goals.iter().map(|g| &*g.name).collect();
+    let icons = goals.iter().map(|g| g.icon.as_deref()).collect();
+
+    Ok(Bingo {
+        size: size,
+        cells: cells,
+        icons: icons,
+    })
+}
+
+/// Picks the goals of the board, row by row, without allocating. Searching
+/// and analyzing many boards uses this directly, as it needs the goals' types
+/// and not only their names.
+pub fn generate_goals<'a, M, O>(
+    seed: Seed,
+    mode: &M,
+    size: usize,
+    constraints: &Constraints,
+    template: &'a Template,
+    observer: &mut O,
+) -> Result<ArrayVec<[&'a Goal; MAX_CELLS]>, GenerateError>
+where
+    M: DifficultyMapping,
+    O: Observer<'a>,
+{
+    if size == 0 || size == 2 || size > MAX_SIZE {
+        return Err(GenerateError::InvalidSize { size: size });
+    }
+
+    let mut random = seed.random();
+
+    let square = MagicSquare::new(seed.magic_number(), size);
+
+    let mut gen_cells = ArrayVec::<[_; MAX_CELLS]>::new();
+    gen_cells.extend((0..size * size).map(|_| None));
+    place_required(&square, mode, constraints, template, &mut gen_cells)?;
 
     // populate the bingo board in the array
-    let mut gen_cells = ArrayVec::<[_; 25]>::new();
-    for i in 1..26 {
-        let difficulty = difficulty(seed, i, mode); // difficulty of current square
-        let template_part = &template.0[difficulty as usize];
-        let rng = (template_part.len() as f64 * random.next()) as usize;
-        let mut j = 0;
+    for i in 0..size * size {
+        let level = level(&square, i);
+        let difficulty = mode.tier(level); // difficulty of current square
+
+        if let Some(goal) = gen_cells[i] {
+            // Required goals are already in place, but the random number is
+            // still consumed so that all the other cells draw the same
+            // numbers as without the requirement.
+            random.next_f64();
+            observer.cell(i, level, difficulty, 0);
+            observer.candidate(goal, check_line(size, i, &goal.types, &gen_cells));
+            observer.chosen(0);
+            continue;
+        }
+
+        let template_part = tier_goals(template, difficulty)?;
+        let template_part = Pool::new(template_part, mode.special_only());
+        let rng = (template_part.len as f64 * random.next_f64()) as usize;
+        observer.cell(i, level, difficulty, template_part.tier_index(rng));
+        let mut candidates = 0;
         let mut gen_cell = None::<GenCell>;
-        loop {
-            let current_obj = &template_part[(j + rng) % template_part.len()];
-            let synergy = check_line(i - 1, &current_obj.types, &gen_cells);
+        for j in 0..template_part.len {
+            let current_obj = template_part.get((j + rng) % template_part.len);
+            if constraints.is_banned(current_obj)
+                || constraints.is_required(current_obj)
+                || !constraints.fits(current_obj, gen_cells.iter().filter_map(|&g| g))
+            {
+                continue;
+            }
+
+            let synergy = check_line(size, i, &current_obj.types, &gen_cells);
+            observer.candidate(current_obj, synergy);
             if gen_cell.as_ref().map_or(true, |c| synergy < c.synergy) {
                 gen_cell = Some(GenCell {
                     goal: current_obj,
                     synergy: synergy,
+                    candidate: candidates,
                 });
             }
 
-            j += 1;
-            if synergy == 0 || j >= template_part.len() {
+            candidates += 1;
+            if synergy == 0 {
                 break;
             }
         }
-        gen_cells.push(gen_cell.unwrap());
+        let gen_cell = gen_cell.ok_or(GenerateError::NoEligibleGoal {
+            cell: i,
+            tier: difficulty,
+        })?;
+        observer.chosen(gen_cell.candidate);
+        gen_cells[i] = Some(gen_cell.goal);
     }
 
-    // populate the actual table
-    for (c, g) in cells
-        .iter_mut()
-        .flat_map(|r| r.iter_mut())
-        .zip(gen_cells.into_iter())
-    {
-        *c = &g.goal.name;
-    }
-
-    Bingo { cells: cells }
+    Ok(gen_cells.iter().map(|g| g.unwrap()).collect())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use Template;
+    use difficulty::Curve;
+    use {Mode, Requirement, Template};
+
+    fn sm64() -> Template {
+        Template::from_json_str(include_str!("templates/sm64.json")).unwrap()
+    }
+
+    #[test]
+    fn missing_tier() {
+        let mut template = sm64();
+        template.0.truncate(20);
+        assert_eq!(
+            template.try_generate(587062, Mode::Normal).err(),
+            Some(GenerateError::MissingTier {
+                tier: 23,
+                tier_count: 20,
+            })
+        );
+    }
+
+    #[test]
+    fn empty_tier() {
+        let mut template = sm64();
+        template.0[7].clear();
+        assert_eq!(
+            template.try_generate(587062, Mode::Normal).err(),
+            Some(GenerateError::EmptyTier { tier: 7 })
+        );
+    }
+
+    #[test]
+    fn invalid_size() {
+        assert_eq!(
+            sm64()
+                .try_generate_sized(587062, Mode::Normal, MAX_SIZE + 1)
+                .err(),
+            Some(GenerateError::InvalidSize { size: MAX_SIZE + 1 })
+        );
+        assert_eq!(
+            sm64().try_generate_sized(587062, Mode::Normal, 2).err(),
+            Some(GenerateError::InvalidSize { size: 2 })
+        );
+    }
+
+    #[test]
+    fn neighbours() {
+        let neighbours = |i| {
+            (0..25)
+                .filter(|&j| j != i && shares_line(5, i, j))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(neighbours(0), [1, 2, 3, 4, 5, 6, 10, 12, 15, 18, 20, 24]);
+        assert_eq!(neighbours(7), [2, 5, 6, 8, 9, 12, 17, 22]);
+        assert_eq!(
+            neighbours(12),
+            [0, 2, 4, 6, 7, 8, 10, 11, 13, 14, 16, 17, 18, 20, 22, 24]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn line_synergy() {
+        let template = Template::from_json_str(
+            r#"[[
+                { "name": "A", "types": ["WF", "coins"] },
+                { "name": "B", "types": ["coins"] },
+                { "name": "C", "types": ["WF"] }
+            ]]"#,
+        )
+        .unwrap();
+        let goals = &template.0[0];
+        let board = [&goals[0], &goals[1], &goals[2], &goals[0]];
+        assert_eq!(super::line_synergy(2, Line::Row(0), &board), 2);
+        assert_eq!(super::line_synergy(2, Line::Column(0), &board), 3);
+        assert_eq!(super::line_synergy(2, Line::Row(1), &board), 3);
+        assert_eq!(
+            super::line_synergy(2, Line::TopLeftToBottomRight, &board),
+            4
+        );
+        assert_eq!(
+            super::line_synergy(2, Line::BottomLeftToTopRight, &board),
+            0
+        );
+    }
+
+    #[test]
+    fn goals() {
+        let template = sm64();
+        let bingo = template.generate(587062, Mode::Normal);
+        let goals = generate_goals(
+            587062.into(),
+            &Mode::Normal,
+            5,
+            &Constraints::default(),
+            &template,
+            &mut (),
+        )
+        .unwrap();
+        let names = goals.iter().map(|g| &*g.name).collect::<Vec<_>>();
+        assert_eq!(names, &bingo.cells[..]);
+    }
+
+    #[test]
+    fn magic_square_values() {
+        for size in (1..MAX_SIZE + 1).filter(|&size| size != 2) {
+            for &seed in &[0, 587062, 123456789, 4294967295] {
+                let square = MagicSquare::new(seed, size);
+                let mut values = (0..size * size)
+                    .map(|i| square.value(i))
+                    .collect::<Vec<_>>();
+
+                // Every row, column and diagonal adds up to the same sum.
+                let magic_sum = size * (size * size - 1) / 2;
+                for n in 0..size {
+                    assert_eq!(
+                        (0..size).map(|x| values[n * size + x]).sum::<usize>(),
+                        magic_sum
+                    );
+                    assert_eq!(
+                        (0..size).map(|y| values[y * size + n]).sum::<usize>(),
+                        magic_sum
+                    );
+                }
+                assert_eq!(
+                    (0..size).map(|n| values[n * size + n]).sum::<usize>(),
+                    magic_sum
+                );
+                assert_eq!(
+                    (0..size)
+                        .map(|n| values[n * size + size - 1 - n])
+                        .sum::<usize>(),
+                    magic_sum
+                );
+
+                values.sort();
+                assert_eq!(values, (0..size * size).collect::<Vec<_>>());
+            }
+        }
+    }
+
+    #[test]
+    fn sizes() {
+        let template = sm64();
+        for size in (1..MAX_SIZE + 1).filter(|&size| size != 2) {
+            for &mode in &[Mode::Short, Mode::Normal, Mode::Long] {
+                let bingo = template.generate_sized(587062, mode, size);
+                assert_eq!(bingo.size, size);
+                assert_eq!(bingo.cells.len(), size * size);
+                assert_eq!(bingo.rows().count(), size);
+            }
+        }
+    }
+
+    #[test]
+    fn mappings() {
+        let template = sm64();
+        assert_eq!(
+            template.generate(587062, Curve::linear(0, 24)),
+            template.generate(587062, Mode::Normal)
+        );
+        assert_eq!(
+            template.generate(587062, |value| value / 2),
+            template.generate(587062, Mode::Short)
+        );
+        assert_eq!(
+            template.try_generate(587062, |value| value + 1).err(),
+            Some(GenerateError::MissingTier {
+                tier: 25,
+                tier_count: 25,
+            })
+        );
+    }
+
+    #[test]
+    fn text_seeds() {
+        let template = sm64();
+        assert_eq!(
+            template.generate("587062", Mode::Normal),
+            template.generate(587062, Mode::Normal)
+        );
+        assert_eq!(
+            template.generate("mario", Mode::Normal),
+            template.generate(Seed::Text("mario"), Mode::Normal)
+        );
+        assert_ne!(
+            template.generate("mario", Mode::Normal),
+            template.generate("luigi", Mode::Normal)
+        );
+        assert_eq!(
+            template
+                .generate("mario", Mode::Normal)
+                .rows()
+                .next()
+                .unwrap(),
+            [
+                "5 Stars in BBH",
+                "100 Coin Star in THI",
+                "At least 1 star from 10 stages",
+                "Red Coin Star in WF",
+                "12 Castle Secret Stars",
+            ]
+        );
+    }
+
+    #[test]
+    fn special() {
+        let mut template = sm64();
+        assert_eq!(
+            template.generate(587062, Mode::Special),
+            template.generate(587062, Mode::Long)
+        );
+
+        for tier in &mut template.0 {
+            tier[0].special = true;
+        }
+        let square = MagicSquare::new(587062, 5);
+        let bingo = template.generate(587062, Mode::Special);
+        for (i, &cell) in bingo.cells.iter().enumerate() {
+            let tier = Mode::Special.tier(square.value(i));
+            assert_eq!(cell, template.0[tier][0].name);
+        }
+    }
+
+    #[test]
+    fn special_seed() {
+        let mut template = sm64();
+        for tier in &mut template.0 {
+            for (i, goal) in tier.iter_mut().enumerate() {
+                goal.special = i % 2 == 1;
+            }
+        }
+        let bingo = template.generate(587062, Mode::Special);
+        assert_eq!(
+            bingo.rows().collect::<Vec<_>>(),
+            [
+                [
+                    "6 Stars in BBH",
+                    "All Stars in JRB",
+                    "100 Coin Star in BOB",
+                    "All Stars in DDD",
+                    "Open 9 Cannons",
+                ],
+                [
+                    "All Stars in WDW",
+                    "6 Stars in SSL",
+                    "35 Total Stars",
+                    "3 Stars each from THI and TTM",
+                    "100 Coin Star in DDD",
+                ],
+                [
+                    "All Stars in HMC",
+                    "100 Coin Star in SL",
+                    "Top Floor Toad Star",
+                    "Open 9 Cannons",
+                    "35 Total Stars",
+                ],
+                [
+                    "3 Stars each from THI and TTM",
+                    "At least 1 Star from each Stage",
+                    "All Stars in HMC",
+                    "100 Coin Star in BOB",
+                    "All Stars in RR",
+                ],
+                [
+                    "100 Coin Star in SL",
+                    "All Stars in WDW",
+                    "6 Stars in SSL",
+                    "At least 1 Star from each Stage",
+                    "All Stars in JRB",
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn special_compatibility() {
+        // Without any goals marked as special, special mode creates the same
+        // boards as the special mode of the original web generator.
+        let template = sm64();
+        assert_eq!(
+            template
+                .generate(0, Mode::Special)
+                .rows()
+                .collect::<Vec<_>>(),
+            [
+                [
+                    "All Stars in RR",
+                    "All Stars in SSL",
+                    "All Stars in TTM",
+                    "At least 2 stars from 10 stages",
+                    "1 Star in All Odd Number Courses",
+                ],
+                [
+                    "Five 100 Coin Stars",
+                    "6 Stars in HMC",
+                    "6 Stars in DDD",
+                    "6 Stars in BBH",
+                    "Open 9 Cannons",
+                ],
+                [
+                    "12 Castle Secret Stars",
+                    "6 Stars in WDW",
+                    "At least 3 stars from 6 stages",
+                    "100 Coin Star in SL",
+                    "6 Stars in BBH",
+                ],
+                [
+                    "Open 7 Cannons",
+                    "All Stars in RR",
+                    "All Stars in SSL",
+                    "All Stars in TTC",
+                    "3 Stars each from THI and TTM",
+                ],
+                [
+                    "6 Stars in TTM",
+                    "5 Stars in DDD",
+                    "3 Stars each from JRB and BBH",
+                    "All Stars in WDW",
+                    "12 Castle Secret Stars",
+                ],
+            ]
+        );
+        assert_eq!(
+            template
+                .generate(1234, Mode::Special)
+                .rows()
+                .collect::<Vec<_>>(),
+            [
+                [
+                    "100 Coin Star in BOB",
+                    "All Stars in Snowmans",
+                    "All Stars in TTM",
+                    "6 Stars in RR",
+                    "4 Stars each from JRB and DDD",
+                ],
+                [
+                    "12 Castle Secret Stars",
+                    "Five 100 Coin Stars",
+                    "Rematch with Koopa the Quick THI",
+                    "All Stars in WDW",
+                    "All Stars in BOB",
+                ],
+                [
+                    "Open All 11 Cannons",
+                    "All Stars in TTC",
+                    "All Stars in SSL",
+                    "5 Stars in DDD",
+                    "100 Coin Star in BBH",
+                ],
+                [
+                    "At least 2 stars from 10 stages",
+                    "Rematch with Koopa the Quick THI",
+                    "All Stars in BBH",
+                    "All Stars in TTM",
+                    "All Stars in SSL",
+                ],
+                [
+                    "5 Stars in BBH",
+                    "Top Floor Toad Star",
+                    "All Stars in JRB",
+                    "6 Stars in HMC",
+                    "All Stars in RR",
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn no_constraints() {
+        let template = sm64();
+        for &seed in &[0, 587062, 4294967295] {
+            assert_eq!(
+                template.generate_constrained(seed, Mode::Normal, 5, &Constraints::default()),
+                Ok(template.generate(seed, Mode::Normal))
+            );
+        }
+    }
+
+    #[test]
+    fn banned() {
+        let template = sm64();
+        let normal = template.generate(587062, Mode::Normal);
+        let constraints = Constraints {
+            banned_goals: &[normal.cells[1], normal.cells[17]],
+            banned_types: &["vanishcap"],
+            ..Constraints::default()
+        };
+        let bingo = template
+            .generate_constrained(587062, Mode::Normal, 5, &constraints)
+            .unwrap();
+        assert_eq!(bingo.cells[0], normal.cells[0]);
+        for goal in template.0.iter().flatten() {
+            if bingo.cells.contains(&&*goal.name) {
+                assert!(!constraints.is_banned(goal));
+            }
+        }
+        assert!(!bingo.cells.contains(&normal.cells[1]));
+        assert!(!bingo.cells.contains(&normal.cells[17]));
+    }
+
+    #[test]
+    fn required() {
+        let template = sm64();
+        let required = [
+            Requirement::at("Reach the Castle Roof", 2, 2),
+            Requirement::new("Two Bowser Stage Red Coin Stars"),
+        ];
+        let constraints = Constraints {
+            required: &required,
+            ..Constraints::default()
+        };
+        for &seed in &[0, 587062, 4294967295] {
+            let bingo = template
+                .generate_constrained(seed, Mode::Normal, 5, &constraints)
+                .unwrap();
+            assert_eq!(bingo.cell(2, 2), Some("Reach the Castle Roof"));
+            assert_eq!(bingo.cell(5, 0), None);
+            assert_eq!(bingo.icon(0, 5), None);
+            assert_eq!(
+                bingo
+                    .cells
+                    .iter()
+                    .filter(|&&c| c == "Two Bowser Stage Red Coin Stars")
+                    .count(),
+                1
+            );
+        }
+    }
+
+    #[test]
+    fn max_per_type() {
+        let template = sm64();
+        let constraints = Constraints {
+            max_per_type: Some(3),
+            ..Constraints::default()
+        };
+        let bingo = template
+            .generate_constrained(587062, Mode::Normal, 5, &constraints)
+            .unwrap();
+        let goals = bingo
+            .cells
+            .iter()
+            .map(|&c| template.0.iter().flatten().find(|g| g.name == c).unwrap())
+            .collect::<Vec<_>>();
+        for goal in &goals {
+            for ty in &goal.types {
+                assert!(goals.iter().filter(|g| g.types.contains(ty)).count() <= 3);
+            }
+        }
+    }
+
+    #[test]
+    fn constraint_errors() {
+        let template = sm64();
+        let generate =
+            |constraints| template.generate_constrained(587062, Mode::Normal, 5, &constraints);
+
+        let required = [Requirement::new("Beat the Game Blindfolded")];
+        assert_eq!(
+            generate(Constraints {
+                required: &required,
+                ..Constraints::default()
+            }),
+            Err(GenerateError::UnknownGoal { requirement: 0 })
+        );
+
+        let required = [
+            Requirement::at("Reach the Castle Roof", 1, 1),
+            Requirement::at("Two Bowser Stage Red Coin Stars", 1, 1),
+        ];
+        assert_eq!(
+            generate(Constraints {
+                required: &required,
+                ..Constraints::default()
+            }),
+            Err(GenerateError::UnplaceableGoal { requirement: 1 })
+        );
+
+        let max = usize::MAX;
+        let outside = [
+            [Requirement::at("Reach the Castle Roof", 5, 0)],
+            [Requirement::at("Reach the Castle Roof", 0, max)],
+            [Requirement::at("Reach the Castle Roof", max, max)],
+        ];
+        for required in &outside {
+            assert_eq!(
+                generate(Constraints {
+                    required: required,
+                    ..Constraints::default()
+                }),
+                Err(GenerateError::UnplaceableGoal { requirement: 0 })
+            );
+        }
+
+        let required = [
+            Requirement::new("Open 9 Cannons"),
+            Requirement::at("Reach the Castle Roof", 1, 1),
+        ];
+        assert_eq!(
+            generate(Constraints {
+                banned_goals: &["Reach the Castle Roof"],
+                required: &required,
+                ..Constraints::default()
+            }),
+            Err(GenerateError::UnplaceableGoal { requirement: 1 })
+        );
+        assert_eq!(
+            generate(Constraints {
+                banned_types: &["cannons"],
+                required: &required,
+                ..Constraints::default()
+            }),
+            Err(GenerateError::UnplaceableGoal { requirement: 0 })
+        );
+
+        let required = [
+            Requirement::new("15 Lives"),
+            Requirement::at("Reach the Castle Roof", 1, 1),
+            Requirement::new("20 lives"),
+        ];
+        let constraints = Constraints {
+            required: &required,
+            max_per_type: Some(2),
+            ..Constraints::default()
+        };
+        assert_eq!(
+            generate(constraints),
+            Err(GenerateError::UnplaceableGoal { requirement: 2 })
+        );
+        assert!(generate(Constraints {
+            required: &required[..2],
+            ..constraints
+        })
+        .is_ok());
+
+        let banned = template.0[0].iter().map(|g| &*g.name).collect::<Vec<_>>();
+        let result = generate(Constraints {
+            banned_goals: &banned,
+            ..Constraints::default()
+        });
+        match result {
+            Err(GenerateError::NoEligibleGoal { tier: 0, .. }) => {}
+            _ => panic!("{:?}", result),
+        }
+    }
 
     #[test]
     fn test() {
@@ -183,46 +1180,44 @@ mod tests {
         let template = Template::from_json_str(sm64).unwrap();
         let bingo = template.generate(587062, Mode::Normal);
         assert_eq!(
-            bingo,
-            Bingo {
-                cells: [
-                    [
-                        "All Stars in TTM".into(),
-                        "2 Cap Stage Stars".into(),
-                        "100 Coin Star in CCM".into(),
-                        "6 Stars in DDD".into(),
-                        "6 Stars in HMC".into()
-                    ],
-                    [
-                        "6 Stars in RR".into(),
-                        "100 Coin Star in BBH".into(),
-                        "6 Stars in TTM".into(),
-                        "100 Coin Star in SSL".into(),
-                        "Peach\'s Slide x 2".into()
-                    ],
-                    [
-                        "One Star in All Even Number Courses".into(),
-                        "Cruiser Crossing the Rainbow RR".into(),
-                        "At least 1 Star from each Stage".into(),
-                        "All Stars in LLL".into(),
-                        "Open 9 Cannons".into()
-                    ],
-                    [
-                        "Top Floor Cloud Stage Star".into(),
-                        "5 Stars in DDD".into(),
-                        "Three Bowser Stage Red Coin Stars".into(),
-                        "Open 3 cannons".into(),
-                        "All Stars in RR".into()
-                    ],
-                    [
-                        "Three 100 Coin Stars".into(),
-                        "All Stars in THI".into(),
-                        "3 Stars each from JRB and BBH".into(),
-                        "At least 3 stars from 6 stages".into(),
-                        "100 Coin Star in LLL".into()
-                    ]
+            bingo.rows().collect::<Vec<_>>(),
+            [
+                [
+                    "All Stars in TTM",
+                    "2 Cap Stage Stars",
+                    "100 Coin Star in CCM",
+                    "6 Stars in DDD",
+                    "6 Stars in HMC",
                 ],
-            }
+                [
+                    "6 Stars in RR",
+                    "100 Coin Star in BBH",
+                    "6 Stars in TTM",
+                    "100 Coin Star in SSL",
+                    "Peach's Slide x 2",
+                ],
+                [
+                    "One Star in All Even Number Courses",
+                    "Cruiser Crossing the Rainbow RR",
+                    "At least 1 Star from each Stage",
+                    "All Stars in LLL",
+                    "Open 9 Cannons",
+                ],
+                [
+                    "Top Floor Cloud Stage Star",
+                    "5 Stars in DDD",
+                    "Three Bowser Stage Red Coin Stars",
+                    "Open 3 cannons",
+                    "All Stars in RR",
+                ],
+                [
+                    "Three 100 Coin Stars",
+                    "All Stars in THI",
+                    "3 Stars each from JRB and BBH",
+                    "At least 3 stars from 6 stages",
+                    "100 Coin Star in LLL",
+                ],
+            ]
         );
     }
 }
@@ -11,6 +11,7 @@ extern crate image;
 extern crate imageproc;
 #[cfg(feature = "std")]
 extern crate rusttype;
+extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 #[cfg(feature = "std")]
@@ -18,14 +19,55 @@ extern crate serde_json;
 #[cfg(all(not(feature = "std"), feature = "json_core"))]
 extern crate serde_json_core as serde_json;
 
+#[cfg(feature = "std")]
+mod analysis;
 mod bingo;
+mod constraints;
+mod difficulty;
+mod seed;
 mod seed_random;
+mod state;
 mod template;
+#[cfg(feature = "std")]
+mod trace;
 mod generator;
+mod lockout;
+mod validation;
+mod visibility;
 #[cfg(feature = "std")]
 mod renderer;
+#[cfg(feature = "std")]
+mod room;
+#[cfg(feature = "std")]
+mod search;
 
-pub use template::{Goal, Template};
-pub use bingo::{Bingo, Mode};
+pub use template::{Goal, Template, MAX_TIERS};
+#[cfg(feature = "std")]
+pub use analysis::{Analysis, GoalStats, LineStats, TypeStats};
+#[cfg(feature = "std")]
+pub use trace::{Candidate, CellTrace, Trace};
+pub use bingo::{Bingo, Mode, MAX_SIZE};
+pub use constraints::{Constraints, Requirement};
+pub use difficulty::{Curve, DifficultyMapping};
+pub use generator::GenerateError;
+#[cfg(feature = "std")]
+pub use room::{Command, Player, PlayerId, Room, RoomError, Team, Update};
+pub use lockout::{Claim, ClaimError, Event, Lockout, Outcome, Rules, WinCondition, MAX_EVENTS};
+#[cfg(feature = "std")]
+pub use search::{Criterion, Search};
+pub use seed::Seed;
+pub use seed_random::SeedRandom;
+pub use state::{BoardState, Line};
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub use seed_random::FloatSeedRandom;
+pub use validation::{Diagnostic, Severity};
+pub use visibility::{Fog, Visibility};
+#[cfg(feature = "std")]
+pub use validation::Report;
 #[cfg(feature = "std")]
-pub use renderer::render;
+pub use renderer::{
+    render, render_progress, render_svg, render_svg_progress, try_render, try_render_progress,
+    try_render_svg, try_render_svg_progress, Align, CellState, Color, FontFit, Marker, Progress,
+    RenderError, RenderOptions, Theme, VerticalAlign,
+};
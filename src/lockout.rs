@@ -0,0 +1,534 @@
+use arrayvec::ArrayVec;
+use bingo::{self, MAX_CELLS};
+use core::fmt;
+use serde::de::{Deserialize, Deserializer};
+use {Bingo, Line};
+
+/// The maximum amount of events a lockout game can record without `std`.
+pub const MAX_EVENTS: usize = 256;
+
+#[cfg(feature = "std")]
+type Log = Vec<Event>;
+#[cfg(not(feature = "std"))]
+type Log = ArrayVec<[Event; MAX_EVENTS]>;
+
+/// The ways a team can win a lockout game.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum WinCondition {
+    /// The team claimed every cell of the line first.
+    Line(Line),
+    /// The team claimed more than half of the cells, so no other team can
+    /// catch up anymore. On a 5x5 board that's 13 cells.
+    Majority,
+    /// The team claimed the most cells by the time the time limit was
+    /// reached.
+    TimeLimit,
+}
+
+/// Which win conditions end the game early. The time limit always applies,
+/// as it's up to the caller to end the game with `finish`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Rules {
+    /// The first team to claim a complete line wins.
+    pub line: bool,
+    /// The first team to claim the majority of the cells wins.
+    pub majority: bool,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            line: true,
+            majority: true,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Outcome {
+    Won {
+        team: usize,
+        condition: WinCondition,
+    },
+    /// The time limit was reached with multiple teams sharing the most cells.
+    Draw,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Claim {
+    pub team: usize,
+    pub time: u64,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Event {
+    Claimed {
+        team: usize,
+        x: usize,
+        y: usize,
+        time: u64,
+    },
+    Unclaimed {
+        team: usize,
+        x: usize,
+        y: usize,
+        time: u64,
+    },
+    Ended {
+        outcome: Outcome,
+        time: u64,
+    },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ClaimError {
+    /// The team isn't part of the game.
+    UnknownTeam { team: usize },
+    /// The cell isn't on the board.
+    InvalidCell { x: usize, y: usize },
+    /// Another team already claimed the cell. Cells can't be stolen in
+    /// lockout.
+    Taken { owner: usize },
+    /// The team already claimed the cell.
+    AlreadyClaimed,
+    /// Only the team that claimed a cell can give it up again.
+    NotOwner { owner: Option<usize> },
+    /// The game already ended.
+    GameOver,
+    /// The event log is full. This only happens without `std`, where the last
+    /// slot of the log is kept free for the end of the game.
+    LogFull,
+}
+
+impl fmt::Display for ClaimError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ClaimError::UnknownTeam { team } => write!(f, "Team {} is not part of the game", team),
+            ClaimError::InvalidCell { x, y } => write!(f, "There is no cell at ({}, {})", x, y),
+            ClaimError::Taken { owner } => {
+                write!(f, "The cell is already claimed by team {}", owner)
+            }
+            ClaimError::AlreadyClaimed => write!(f, "The team already claimed the cell"),
+            ClaimError::NotOwner { owner: Some(owner) } => {
+                write!(f, "The cell is claimed by team {}", owner)
+            }
+            ClaimError::NotOwner { owner: None } => write!(f, "The cell is not claimed"),
+            ClaimError::GameOver => write!(f, "The game is already over"),
+            ClaimError::LogFull => write!(f, "The event log is full"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for ClaimError {
+    fn description(&self) -> &str {
+        match *self {
+            ClaimError::UnknownTeam { .. } => "unknown team",
+            ClaimError::InvalidCell { .. } => "invalid cell",
+            ClaimError::Taken { .. } => "cell already taken",
+            ClaimError::AlreadyClaimed => "cell already claimed",
+            ClaimError::NotOwner { .. } => "cell not owned",
+            ClaimError::GameOver => "game over",
+            ClaimError::LogFull => "event log full",
+        }
+    }
+}
+
+/// A lockout game, where multiple teams race on the same board and every cell
+/// can only be claimed by a single team. The times are whatever the caller
+/// passes in, like milliseconds since the start of the race.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Lockout {
+    size: usize,
+    teams: usize,
+    rules: Rules,
+    owners: ArrayVec<[Option<Claim>; MAX_CELLS]>,
+    events: Log,
+    outcome: Option<Outcome>,
+}
+
+impl Lockout {
+    pub fn new(bingo: &Bingo, teams: usize, rules: Rules) -> Self {
+        Self::with_size(bingo.size, teams, rules)
+    }
+
+    /// Creates a game on a board with the size. Panics if the size isn't
+    /// between 1 and `MAX_SIZE`.
+    pub fn with_size(size: usize, teams: usize, rules: Rules) -> Self {
+        bingo::check_size(size);
+        Lockout {
+            size: size,
+            teams: teams,
+            rules: rules,
+            owners: (0..size * size).map(|_| None).collect(),
+            events: Log::new(),
+            outcome: None,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn teams(&self) -> usize {
+        self.teams
+    }
+
+    pub fn rules(&self) -> Rules {
+        self.rules
+    }
+
+    /// The team that claimed the cell and when. Cells that aren't on the
+    /// board are never claimed.
+    pub fn owner(&self, x: usize, y: usize) -> Option<Claim> {
+        bingo::cell_index(self.size, x, y).and_then(|i| self.owners[i])
+    }
+
+    /// The amount of cells the team claimed.
+    pub fn score(&self, team: usize) -> usize {
+        self.owners
+            .iter()
+            .filter(|c| c.map(|c| c.team) == Some(team))
+            .count()
+    }
+
+    /// The lines that are completely claimed by the team.
+    pub fn lines<'s>(&'s self, team: usize) -> impl Iterator<Item = Line> + 's {
+        Line::all(self.size).filter(move |&line| {
+            line.cells(self.size)
+                .all(|i| self.owners[i].map(|c| c.team) == Some(team))
+        })
+    }
+
+    /// Every claim, unclaim and the end of the game, in the order they
+    /// happened.
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /// How the game ended, if it did.
+    pub fn outcome(&self) -> Option<Outcome> {
+        self.outcome
+    }
+
+    /// Claims the cell for the team. Returns the outcome if the claim ended
+    /// the game.
+    pub fn claim(
+        &mut self,
+        team: usize,
+        x: usize,
+        y: usize,
+        time: u64,
+    ) -> Result<Option<Outcome>, ClaimError> {
+        let i = self.check(team, x, y)?;
+        match self.owners[i] {
+            Some(claim) if claim.team == team => return Err(ClaimError::AlreadyClaimed),
+            Some(claim) => return Err(ClaimError::Taken { owner: claim.team }),
+            None => {}
+        }
+
+        self.log(Event::Claimed {
+            team: team,
+            x: x,
+            y: y,
+            time: time,
+        })?;
+        self.owners[i] = Some(Claim {
+            team: team,
+            time: time,
+        });
+
+        let condition = if self.rules.line {
+            self.lines(team)
+                .find(|line| line.cells(self.size).any(|j| j == i))
+                .map(WinCondition::Line)
+        } else {
+            None
+        };
+        let condition = condition.or_else(|| {
+            if self.rules.majority && 2 * self.score(team) > self.size * self.size {
+                Some(WinCondition::Majority)
+            } else {
+                None
+            }
+        });
+
+        Ok(condition.map(|condition| {
+            let outcome = Outcome::Won {
+                team: team,
+                condition: condition,
+            };
+            self.end(outcome, time);
+            outcome
+        }))
+    }
+
+    /// Gives up the team's claim of the cell, like when the cell got claimed
+    /// by mistake.
+    pub fn unclaim(
+        &mut self,
+        team: usize,
+        x: usize,
+        y: usize,
+        time: u64,
+    ) -> Result<(), ClaimError> {
+        let i = self.check(team, x, y)?;
+        match self.owners[i] {
+            Some(claim) if claim.team == team => {}
+            owner => {
+                return Err(ClaimError::NotOwner {
+                    owner: owner.map(|c| c.team),
+                })
+            }
+        }
+
+        self.log(Event::Unclaimed {
+            team: team,
+            x: x,
+            y: y,
+            time: time,
+        })?;
+        self.owners[i] = None;
+        Ok(())
+    }
+
+    /// Ends the game because the time limit was reached. The team with the
+    /// most cells wins, unless multiple teams share the most cells.
+    pub fn finish(&mut self, time: u64) -> Result<Outcome, ClaimError> {
+        if self.outcome.is_some() {
+            return Err(ClaimError::GameOver);
+        }
+
+        let mut best = None;
+        let mut best_score = 0;
+        for team in 0..self.teams {
+            let score = self.score(team);
+            if score > best_score {
+                best = Some(team);
+                best_score = score;
+            } else if score == best_score {
+                best = None;
+            }
+        }
+
+        let outcome = match best {
+            Some(team) => Outcome::Won {
+                team: team,
+                condition: WinCondition::TimeLimit,
+            },
+            None => Outcome::Draw,
+        };
+        self.end(outcome, time);
+        Ok(outcome)
+    }
+
+    fn check(&self, team: usize, x: usize, y: usize) -> Result<usize, ClaimError> {
+        if self.outcome.is_some() {
+            return Err(ClaimError::GameOver);
+        }
+        if team >= self.teams {
+            return Err(ClaimError::UnknownTeam { team: team });
+        }
+        bingo::cell_index(self.size, x, y).ok_or(ClaimError::InvalidCell { x: x, y: y })
+    }
+
+    fn end(&mut self, outcome: Outcome, time: u64) {
+        // Claims and unclaims never take the last slot of the log, so the end
+        // of the game always gets recorded.
+        let _ = self.log(Event::Ended {
+            outcome: outcome,
+            time: time,
+        });
+        self.outcome = Some(outcome);
+    }
+
+    #[cfg(feature = "std")]
+    fn log(&mut self, event: Event) -> Result<(), ClaimError> {
+        self.events.push(event);
+        Ok(())
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn log(&mut self, event: Event) -> Result<(), ClaimError> {
+        let reserved = match event {
+            Event::Ended { .. } => 0,
+            _ => 1,
+        };
+        if self.events.len() + reserved >= MAX_EVENTS {
+            return Err(ClaimError::LogFull);
+        }
+        self.events.try_push(event).map_err(|_| ClaimError::LogFull)
+    }
+}
+
+impl<'de> Deserialize<'de> for Lockout {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename = "Lockout")]
+        struct Fields {
+            size: usize,
+            teams: usize,
+            rules: Rules,
+            owners: ArrayVec<[Option<Claim>; MAX_CELLS]>,
+            events: Log,
+            outcome: Option<Outcome>,
+        }
+
+        let fields = Fields::deserialize(deserializer)?;
+        bingo::check_cells(fields.size, fields.owners.len())?;
+        Ok(Lockout {
+            size: fields.size,
+            teams: fields.teams,
+            rules: fields.rules,
+            owners: fields.owners,
+            events: fields.events,
+            outcome: fields.outcome,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn conflicts() {
+        let mut game = Lockout::with_size(5, 2, Rules::default());
+        assert_eq!(game.claim(0, 1, 1, 10), Ok(None));
+        assert_eq!(game.claim(0, 1, 1, 20), Err(ClaimError::AlreadyClaimed));
+        assert_eq!(game.claim(1, 1, 1, 20), Err(ClaimError::Taken { owner: 0 }));
+        assert_eq!(
+            game.claim(2, 0, 0, 20),
+            Err(ClaimError::UnknownTeam { team: 2 })
+        );
+        assert_eq!(
+            game.claim(1, 5, 0, 20),
+            Err(ClaimError::InvalidCell { x: 5, y: 0 })
+        );
+        assert_eq!(
+            game.unclaim(1, 1, 1, 30),
+            Err(ClaimError::NotOwner { owner: Some(0) })
+        );
+        assert_eq!(game.unclaim(0, 1, 1, 30), Ok(()));
+        assert_eq!(
+            game.unclaim(0, 1, 1, 40),
+            Err(ClaimError::NotOwner { owner: None })
+        );
+        assert_eq!(game.claim(1, 1, 1, 50), Ok(None));
+        assert_eq!(game.owner(1, 1), Some(Claim { team: 1, time: 50 }));
+        assert_eq!(game.owner(6, 0), None);
+        assert_eq!(game.owner(0, 5), None);
+        assert_eq!(
+            game.events(),
+            [
+                Event::Claimed {
+                    team: 0,
+                    x: 1,
+                    y: 1,
+                    time: 10,
+                },
+                Event::Unclaimed {
+                    team: 0,
+                    x: 1,
+                    y: 1,
+                    time: 30,
+                },
+                Event::Claimed {
+                    team: 1,
+                    x: 1,
+                    y: 1,
+                    time: 50,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn first_line() {
+        let mut game = Lockout::with_size(5, 2, Rules::default());
+        for n in 0..4 {
+            assert_eq!(game.claim(1, n, n, n as u64), Ok(None));
+            assert_eq!(game.claim(0, n, 4, n as u64), Ok(None));
+        }
+        let outcome = Outcome::Won {
+            team: 1,
+            condition: WinCondition::Line(Line::TopLeftToBottomRight),
+        };
+        assert_eq!(game.claim(1, 4, 4, 100), Ok(Some(outcome)));
+        assert_eq!(game.outcome(), Some(outcome));
+        assert_eq!(
+            game.events().last(),
+            Some(&Event::Ended {
+                outcome: outcome,
+                time: 100,
+            })
+        );
+        assert_eq!(game.claim(0, 0, 4, 110), Err(ClaimError::GameOver));
+    }
+
+    #[test]
+    fn majority() {
+        let rules = Rules {
+            line: false,
+            ..Rules::default()
+        };
+        let mut game = Lockout::with_size(5, 2, rules);
+        // A checkerboard pattern has 13 cells, but every line has cells of
+        // both colors except for the diagonals.
+        let mut cells = (0..25).filter(|i| (i % 5 + i / 5) % 2 == 0);
+        for _ in 0..12 {
+            let i = cells.next().unwrap();
+            assert_eq!(game.claim(0, i % 5, i / 5, 0), Ok(None));
+        }
+        assert_eq!(game.score(0), 12);
+        assert_eq!(
+            game.claim(0, 4, 4, 0),
+            Ok(Some(Outcome::Won {
+                team: 0,
+                condition: WinCondition::Majority,
+            }))
+        );
+    }
+
+    #[test]
+    fn time_limit() {
+        let mut game = Lockout::with_size(5, 3, Rules::default());
+        game.claim(0, 0, 0, 0).unwrap();
+        game.claim(2, 1, 0, 0).unwrap();
+        assert_eq!(game.clone().finish(1000), Ok(Outcome::Draw));
+
+        game.claim(2, 2, 0, 0).unwrap();
+        assert_eq!(
+            game.finish(1000),
+            Ok(Outcome::Won {
+                team: 2,
+                condition: WinCondition::TimeLimit,
+            })
+        );
+        assert_eq!(game.finish(1000), Err(ClaimError::GameOver));
+    }
+
+    #[test]
+    fn to_json() {
+        let mut game = Lockout::with_size(2, 2, Rules::default());
+        game.claim(1, 1, 0, 10).unwrap();
+        let json = serde_json::to_string(&game).unwrap();
+        assert_eq!(serde_json::from_str::<Lockout>(&json).unwrap(), game);
+
+        let json = json.replace("[null,", "[");
+        assert!(serde_json::from_str::<Lockout>(&json).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "The size 11 is not between 1 and 10")]
+    fn too_large() {
+        Lockout::with_size(11, 2, Rules::default());
+    }
+
+    #[test]
+    #[should_panic(expected = "The size 0 is not between 1 and 10")]
+    fn empty() {
+        Lockout::with_size(0, 2, Rules::default());
+    }
+}
@@ -0,0 +1,551 @@
+use super::RenderOptions;
+use rusttype::{Font, Scale};
+use std::mem::replace;
+use Bingo;
+
+/// How much the font size gets reduced at a time when fitting the text into
+/// a cell.
+const FONT_SIZE_STEP: f32 = 0.5;
+
+/// How the font size is adjusted to the length of the goals.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FontFit {
+    /// Every goal is drawn with the font size, even if it overflows its cell.
+    Fixed,
+    /// The font size of each goal that doesn't fit into its cell is reduced
+    /// separately, down to the minimum font size.
+    Shrink,
+    /// The whole board is drawn with the smallest font size any of the goals
+    /// needs to fit into its cell.
+    Uniform,
+}
+
+/// A suffix that marks goals in their names, like ` ★`. The renderer removes
+/// it from the text and shows its icon as a badge instead.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Marker<'a> {
+    pub suffix: &'a str,
+    pub icon: &'a str,
+}
+
+/// The horizontal alignment of the lines in a cell.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// The vertical alignment of the text in a cell.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum VerticalAlign {
+    Top,
+    Middle,
+}
+
+/// Measures the text that gets laid out.
+pub trait Measure {
+    /// The width of the text at the font size, which is the sum of the
+    /// advance widths of its glyphs and the kerning between them.
+    fn width(&self, text: &str, size: f32) -> f32;
+    /// The height of the font above the baseline at a font size of 1.
+    fn ascent(&self) -> f32;
+    /// The space between two lines at a font size of 1.
+    fn line_gap(&self) -> f32;
+}
+
+impl<'a> Measure for Font<'a> {
+    fn width(&self, text: &str, size: f32) -> f32 {
+        let scale = Scale::uniform(size);
+        let mut width = 0.0;
+        let mut previous = None;
+        for glyph in text.chars().filter_map(|c| self.glyph(c)) {
+            let glyph = glyph.scaled(scale);
+            if let Some(previous) = previous {
+                width += self.pair_kerning(scale, previous, glyph.id());
+            }
+            width += glyph.h_metrics().advance_width;
+            previous = Some(glyph.id());
+        }
+        width
+    }
+
+    fn ascent(&self) -> f32 {
+        self.v_metrics(Scale::uniform(1.0)).ascent
+    }
+
+    fn line_gap(&self) -> f32 {
+        self.v_metrics(Scale::uniform(1.0)).line_gap
+    }
+}
+
+/// The lines of a goal's text and the font size they are drawn with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CellText {
+    pub font_size: f32,
+    pub lines: Vec<String>,
+}
+
+/// A line of a cell's text, positioned relative to the top left corner of
+/// the cell.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlacedLine<'a> {
+    pub text: &'a str,
+    /// Where the line starts.
+    pub x: f32,
+    /// The point the line is aligned to, which is where it starts, where its
+    /// center is or where it ends.
+    pub anchor: f32,
+    pub baseline: f32,
+}
+
+impl CellText {
+    /// Positions the lines in the cell according to the alignment of the
+    /// options.
+    pub fn place<'a, M: Measure>(
+        &'a self,
+        options: &RenderOptions,
+        measure: &M,
+    ) -> Vec<PlacedLine<'a>> {
+        let size = self.font_size;
+        let cell_size = options.cell_size as f32;
+        let padding = options.cell_padding as f32;
+        let line_height = (1.0 + measure.line_gap()) * size;
+
+        let anchor = match options.align {
+            Align::Left => padding,
+            Align::Center => cell_size / 2.0,
+            Align::Right => cell_size - padding,
+        };
+        let top = match options.vertical_align {
+            VerticalAlign::Top => padding,
+            VerticalAlign::Middle => {
+                (cell_size - text_height(self.lines.len(), size, measure)) / 2.0
+            }
+        };
+
+        self.lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| {
+                let width = measure.width(line, size);
+                let x = match options.align {
+                    Align::Left => anchor,
+                    Align::Center => anchor - width / 2.0,
+                    Align::Right => anchor - width,
+                };
+                PlacedLine {
+                    text: line,
+                    x: x,
+                    anchor: anchor,
+                    baseline: top + measure.ascent() * size + line_height * i as f32,
+                }
+            })
+            .collect()
+    }
+}
+
+/// The icon of a goal, shown as a badge in the top right corner of its cell.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Badge<'a> {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub icon: PlacedLine<'a>,
+}
+
+/// Positions the badge of the icon in its cell, with the icon centered in
+/// it.
+pub fn place_badge<'a, M: Measure>(
+    icon: &'a str,
+    options: &RenderOptions,
+    measure: &M,
+) -> Badge<'a> {
+    let size = options.badge_size;
+    let margin = size / 4.0;
+    let width = measure.width(icon, size) + 2.0 * margin;
+    let x = options.cell_size as f32 - options.cell_padding as f32 - width;
+    let y = options.cell_padding as f32;
+    Badge {
+        x: x,
+        y: y,
+        width: width,
+        height: size + 2.0 * margin,
+        icon: PlacedLine {
+            text: icon,
+            x: x + margin,
+            anchor: x + width / 2.0,
+            baseline: y + margin + measure.ascent() * size,
+        },
+    }
+}
+
+/// The text of a goal and its icon. A marker at the end of the text gets
+/// removed and provides the icon, unless the goal has its own.
+pub fn goal_text<'a>(
+    text: &'a str,
+    icon: Option<&'a str>,
+    markers: &[Marker<'a>],
+) -> (&'a str, Option<&'a str>) {
+    let marker = markers
+        .iter()
+        .find(|marker| !marker.suffix.is_empty() && text.ends_with(marker.suffix));
+    match marker {
+        Some(marker) => (
+            &text[..text.len() - marker.suffix.len()],
+            icon.or(Some(marker.icon)),
+        ),
+        None => (text, icon),
+    }
+}
+
+/// The text and icon of the cell's goal on the board.
+pub fn cell_text<'a>(
+    board: &'a Bingo,
+    index: usize,
+    options: &'a RenderOptions,
+) -> (&'a str, Option<&'a str>) {
+    goal_text(board.cells[index], board.icons[index], &options.markers)
+}
+
+/// The height of the lines from the top of the first one to the bottom of
+/// the last one.
+fn text_height<M: Measure>(lines: usize, size: f32, measure: &M) -> f32 {
+    if lines == 0 {
+        0.0
+    } else {
+        size + (1.0 + measure.line_gap()) * size * (lines - 1) as f32
+    }
+}
+
+/// Lays out the text of every cell, row by row, with the font size chosen by
+/// the options.
+pub fn fit_cells<M: Measure>(board: &Bingo, options: &RenderOptions, measure: &M) -> Vec<CellText> {
+    let max_width = (options.cell_size as i32 - 2 * options.cell_padding) as f32;
+    let max_height = max_width;
+    let texts = (0..board.cells.len())
+        .map(|i| cell_text(board, i, options).0)
+        .collect::<Vec<_>>();
+
+    // Wraps the text at the size and returns whether it fits into the cell
+    // without breaking up any words, which only happens if it doesn't.
+    let wrap = |text: &str, size: f32| {
+        let width = |line: &str| measure.width(line, size);
+        let lines = wrap_lines(text, max_width, false, width);
+        let fits = text_height(lines.len(), size, measure) <= max_height
+            && lines.iter().all(|line| width(line) <= max_width);
+        if fits {
+            (lines, true)
+        } else {
+            (wrap_lines(text, max_width, true, width), false)
+        }
+    };
+
+    // The largest size the text fits into the cell with, which is the
+    // minimum size if it doesn't fit without breaking up words.
+    let shrink = |text: &str| {
+        let mut size = options.font_size;
+        while size > options.min_font_size && !wrap(text, size).1 {
+            size = (size - FONT_SIZE_STEP).max(options.min_font_size);
+        }
+        size
+    };
+
+    let sizes = match options.font_fit {
+        FontFit::Fixed => vec![options.font_size; texts.len()],
+        FontFit::Shrink => texts.iter().map(|text| shrink(text)).collect(),
+        FontFit::Uniform => {
+            let size = texts
+                .iter()
+                .map(|text| shrink(text))
+                .fold(options.font_size, f32::min);
+            vec![size; texts.len()]
+        }
+    };
+
+    texts
+        .iter()
+        .zip(sizes)
+        .map(|(text, size)| CellText {
+            font_size: size,
+            lines: wrap(text, size).0,
+        })
+        .collect()
+}
+
+/// Splits the text into lines that fit into the width, by adding words to a
+/// line for as long as it fits. Words that are too wide for a line on their
+/// own are broken up and hyphenated if `break_words` is set, otherwise they
+/// get a line of their own.
+pub fn wrap_lines<F>(text: &str, max_width: f32, break_words: bool, width: F) -> Vec<String>
+where
+    F: Fn(&str) -> f32,
+{
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if break_words && width(word) > max_width {
+            let mut pieces = break_word(word, max_width, &width);
+            let last = pieces.pop().unwrap();
+            if !line.is_empty() {
+                lines.push(line);
+            }
+            lines.extend(pieces);
+            line = last;
+        } else if line.is_empty() {
+            line.push_str(word);
+        } else {
+            let len = line.len();
+            line.push(' ');
+            line.push_str(word);
+            if width(&line) > max_width {
+                line.truncate(len);
+                lines.push(replace(&mut line, word.to_owned()));
+            }
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Breaks the word into pieces that fit into a line, where every piece but
+/// the last one ends with a hyphen. A piece contains at least one character,
+/// even if that is too wide on its own.
+fn break_word<F>(word: &str, max_width: f32, width: F) -> Vec<String>
+where
+    F: Fn(&str) -> f32,
+{
+    let hyphenate = |piece: &str| {
+        if piece.ends_with('-') {
+            piece.to_owned()
+        } else {
+            format!("{}-", piece)
+        }
+    };
+
+    let mut pieces = Vec::new();
+    let mut rest = word;
+    while width(rest) > max_width {
+        let first = rest.chars().next().map_or(0, char::len_utf8);
+        if first == rest.len() {
+            break;
+        }
+        let mut split = (first, hyphenate(&rest[..first]));
+        for (i, _) in rest.char_indices().skip(2) {
+            let piece = hyphenate(&rest[..i]);
+            if width(&piece) > max_width {
+                break;
+            }
+            split = (i, piece);
+        }
+        pieces.push(split.1);
+        rest = &rest[split.0..];
+    }
+    pieces.push(rest.to_owned());
+    pieces
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use arrayvec::ArrayVec;
+
+    /// Measures text as if every character had the same width, relative to
+    /// the font size.
+    pub struct Monospace(pub f32);
+
+    impl Measure for Monospace {
+        fn width(&self, text: &str, size: f32) -> f32 {
+            self.0 * size * text.chars().count() as f32
+        }
+
+        fn ascent(&self) -> f32 {
+            0.8
+        }
+
+        fn line_gap(&self) -> f32 {
+            0.2
+        }
+    }
+
+    fn wrap(text: &str, max_width: f32, break_words: bool) -> Vec<String> {
+        wrap_lines(text, max_width, break_words, |line| {
+            line.chars().count() as f32
+        })
+    }
+
+    #[test]
+    fn wrapping() {
+        assert_eq!(wrap("", 10.0, false), Vec::<String>::new());
+        assert_eq!(wrap("   ", 10.0, false), Vec::<String>::new());
+        assert_eq!(wrap("Open 9 Cannons", 14.0, false), ["Open 9 Cannons"]);
+        assert_eq!(wrap("Open 9 Cannons", 13.0, false), ["Open 9", "Cannons"]);
+        assert_eq!(
+            wrap("  Open   9\tCannons  ", 6.0, false),
+            ["Open 9", "Cannons"]
+        );
+        assert_eq!(wrap("Open 9 Cannons", 5.0, false), ["Open", "9", "Cannons"]);
+        assert_eq!(
+            wrap("Supercalifragilistic Star", 10.0, false),
+            ["Supercalifragilistic", "Star"]
+        );
+        assert_eq!(
+            wrap("In Wiggler's Chamber", 5.0, true),
+            ["In", "Wigg-", "ler's", "Cham-", "ber"]
+        );
+        assert_eq!(
+            wrap("Beat Supercalifragilistic", 10.0, true),
+            ["Beat", "Supercali-", "fragilist-", "ic"]
+        );
+    }
+
+    #[test]
+    fn break_words() {
+        let width = |text: &str| 10.0 * text.chars().count() as f32;
+        assert_eq!(break_word("Bowser", 60.0, width), ["Bowser"]);
+        assert_eq!(break_word("Bowser", 40.0, width), ["Bow-", "ser"]);
+        assert_eq!(break_word("Bob-omb", 40.0, width), ["Bob-", "omb"]);
+        assert_eq!(break_word("Bob-omb", 30.0, width), ["Bo-", "b-", "omb"]);
+        assert_eq!(break_word("Ö", 5.0, width), ["Ö"]);
+        assert_eq!(break_word("Öx", 5.0, width), ["Ö-", "x"]);
+    }
+
+    #[test]
+    fn placement() {
+        let text = CellText {
+            font_size: 10.0,
+            lines: vec!["Open 9".to_string(), "Cannons".to_string()],
+        };
+        let mut options = RenderOptions {
+            cell_size: 100,
+            cell_padding: 5,
+            ..RenderOptions::new(&[])
+        };
+        let measure = Monospace(0.5);
+        let positions = |options: &RenderOptions| {
+            text.place(options, &measure)
+                .iter()
+                .map(|line| (line.x, line.anchor, line.baseline))
+                .collect::<Vec<_>>()
+        };
+
+        // The two lines are 22 pixels high, so they start 39 pixels from the
+        // top of the cell.
+        assert_eq!(
+            positions(&options),
+            [(35.0, 50.0, 47.0), (32.5, 50.0, 59.0)]
+        );
+        options.align = Align::Left;
+        options.vertical_align = VerticalAlign::Top;
+        assert_eq!(positions(&options), [(5.0, 5.0, 13.0), (5.0, 5.0, 25.0)]);
+        options.align = Align::Right;
+        assert_eq!(
+            positions(&options),
+            [(65.0, 95.0, 13.0), (60.0, 95.0, 25.0)]
+        );
+    }
+
+    #[test]
+    fn markers() {
+        let markers = [
+            Marker {
+                suffix: " ★",
+                icon: "★",
+            },
+            Marker {
+                suffix: " (100)",
+                icon: "C",
+            },
+        ];
+        assert_eq!(goal_text("WF ★", None, &markers), ("WF", Some("★")));
+        assert_eq!(goal_text("WF (100)", None, &markers), ("WF", Some("C")));
+        assert_eq!(goal_text("WF ★", Some("!"), &markers), ("WF", Some("!")));
+        assert_eq!(goal_text("WF", Some("!"), &markers), ("WF", Some("!")));
+        assert_eq!(goal_text("★ WF", None, &markers), ("★ WF", None));
+        assert_eq!(goal_text("WF ★", None, &[]), ("WF ★", None));
+
+        let options = RenderOptions {
+            cell_size: 100,
+            cell_padding: 5,
+            badge_size: 8.0,
+            ..RenderOptions::new(&[])
+        };
+        assert_eq!(
+            place_badge("★", &options, &Monospace(0.5)),
+            Badge {
+                x: 87.0,
+                y: 5.0,
+                width: 8.0,
+                height: 12.0,
+                icon: PlacedLine {
+                    text: "★",
+                    x: 89.0,
+                    anchor: 91.0,
+                    baseline: 13.4,
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn fit_font_size() {
+        let mut cells = ArrayVec::new();
+        cells.push("Open 9 Cannons");
+        cells.push("One Star in All Even Number Courses");
+        cells.push("Supercalifragilistic");
+        cells.push("WF ★");
+        let board = Bingo {
+            size: 2,
+            cells: cells,
+            icons: [None; 4].iter().cloned().collect(),
+        };
+        let mut options = RenderOptions {
+            font_size: 10.0,
+            min_font_size: 6.0,
+            cell_size: 50,
+            ..RenderOptions::new(&[])
+        };
+        // Every character is as wide as half the font size and the line gap
+        // is a fifth of the font size, so three lines of 10 pixels fit into
+        // the 40 pixels of the cell, but four don't.
+        let measure = Monospace(0.5);
+        let fit = |options: &RenderOptions| {
+            fit_cells(&board, options, &measure)
+                .into_iter()
+                .map(|cell| (cell.font_size, cell.lines))
+                .collect::<Vec<_>>()
+        };
+
+        options.font_fit = FontFit::Fixed;
+        let cells = fit(&options);
+        assert!(cells.iter().all(|cell| cell.0 == 10.0));
+        assert_eq!(cells[1].1.len(), 5);
+        assert_eq!(cells[2].1, ["Superca-", "lifragi-", "listic"]);
+        assert_eq!(cells[3].1, ["WF"]);
+
+        options.font_fit = FontFit::Shrink;
+        let cells = fit(&options);
+        assert_eq!(
+            cells[0],
+            (10.0, vec!["Open 9".to_string(), "Cannons".to_string()])
+        );
+        assert_eq!(cells[1].0, 7.0);
+        assert_eq!(cells[1].1, ["One Star in", "All Even", "Number", "Courses"]);
+        assert_eq!(cells[2].0, 6.0);
+        assert_eq!(cells[2].1, ["Supercalifra-", "gilistic"]);
+        assert_eq!(cells[3].0, 10.0);
+
+        options.font_fit = FontFit::Uniform;
+        let cells = fit(&options);
+        assert!(cells.iter().all(|cell| cell.0 == 6.0));
+        assert_eq!(cells[0].1, ["Open 9", "Cannons"]);
+
+        options.min_font_size = 9.0;
+        let cells = fit(&options);
+        assert!(cells.iter().all(|cell| cell.0 == 9.0));
+        assert_eq!(cells[1].1.len(), 5);
+    }
+}
@@ -0,0 +1,588 @@
+use rusttype::{FontCollection, Scale, Font, GlyphId, Point, point};
+use Bingo;
+use image::{RgbaImage, Rgba, Pixel};
+use imageproc::drawing::{draw_hollow_rect_mut, draw_filled_rect_mut};
+use imageproc::rect::Rect;
+use imageproc::filter::gaussian_blur_f32;
+use std::error::Error;
+use std::fmt;
+use self::layout::PlacedLine;
+
+mod layout;
+mod progress;
+mod svg;
+mod theme;
+
+pub use self::layout::{Align, FontFit, Marker, VerticalAlign};
+pub use self::progress::{CellState, Progress};
+pub use self::svg::{render_svg, render_svg_progress, try_render_svg, try_render_svg_progress};
+pub use self::theme::{Color, Theme};
+
+/// The settings for rendering a board.
+#[derive(Debug, Clone)]
+pub struct RenderOptions<'a> {
+    /// The TrueType font that is used to lay out and draw the text. This can
+    /// also be a font collection.
+    pub font: &'a [u8],
+    /// The index of the font to use if the font is a collection of multiple
+    /// fonts.
+    pub font_index: usize,
+    /// The name of the font family an SVG refers to, as the font itself
+    /// isn't embedded.
+    pub font_family: &'a str,
+    /// The font size of the goals, which is the largest size they are drawn
+    /// with if the font size is fitted to the cells.
+    pub font_size: f32,
+    /// The smallest font size the text gets shrunk to when fitting it into
+    /// the cells.
+    pub min_font_size: f32,
+    pub font_fit: FontFit,
+    pub align: Align,
+    pub vertical_align: VerticalAlign,
+    /// The width and height of each cell in pixels.
+    pub cell_size: u32,
+    /// The space between the text and the sides of its cell in pixels.
+    pub cell_padding: i32,
+    /// The suffixes of goal names that are shown as badges instead of text.
+    pub markers: Vec<Marker<'a>>,
+    /// The font size of the icons in the badges.
+    pub badge_size: f32,
+    pub theme: Theme,
+}
+
+impl<'a> RenderOptions<'a> {
+    /// Creates the options for the font with the default look.
+    pub fn new(font: &'a [u8]) -> Self {
+        RenderOptions {
+            font: font,
+            font_index: 0,
+            font_family: "sans-serif",
+            font_size: 16.0,
+            min_font_size: 8.0,
+            font_fit: FontFit::Shrink,
+            align: Align::Center,
+            vertical_align: VerticalAlign::Middle,
+            cell_size: 120,
+            cell_padding: 5,
+            markers: vec![Marker {
+                suffix: " ★",
+                icon: "★",
+            }],
+            badge_size: 12.0,
+            theme: Theme::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenderError {
+    /// The font data isn't a font that can be read.
+    InvalidFont,
+    /// The font collection doesn't contain a font with the index.
+    MissingFace { index: usize, count: usize },
+    /// The font doesn't contain a glyph for a character of a goal.
+    MissingGlyph { cell: usize, character: char },
+    /// The progress has a different amount of cells than the board.
+    InvalidProgress { cells: usize, expected: usize },
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RenderError::InvalidFont => write!(f, "The font data is not a valid font"),
+            RenderError::MissingFace { index, count } => write!(
+                f,
+                "The font collection contains {} fonts, but font {} is required",
+                count, index
+            ),
+            RenderError::MissingGlyph { cell, character } => write!(
+                f,
+                "The font does not contain the character {:?} of the goal in cell {}",
+                character, cell
+            ),
+            RenderError::InvalidProgress { cells, expected } => write!(
+                f,
+                "The progress has {} cells, but the board has {}",
+                cells, expected
+            ),
+        }
+    }
+}
+
+impl Error for RenderError {
+    fn description(&self) -> &str {
+        match *self {
+            RenderError::InvalidFont => "invalid font",
+            RenderError::MissingFace { .. } => "missing font in collection",
+            RenderError::MissingGlyph { .. } => "missing glyph",
+            RenderError::InvalidProgress { .. } => "invalid progress",
+        }
+    }
+}
+
+/// The smallest sizes of the tables that rusttype reads fields from at fixed
+/// offsets.
+const MIN_TABLE_LENS: [(&[u8], usize); 3] = [(b"head", 54), (b"hhea", 36), (b"maxp", 6)];
+
+/// Reads the big endian number with the amount of bytes at the offset.
+fn read(data: &[u8], offset: usize, bytes: usize) -> Option<usize> {
+    let bytes = data.get(offset..offset.checked_add(bytes)?)?;
+    Some(bytes.iter().fold(0, |n, &b| n << 8 | b as usize))
+}
+
+/// Checks the header and the table directory of every font in the font file
+/// or collection, so rusttype, which panics on data outside of the file,
+/// only gets to see fonts whose tables are all there. Returns the amount of
+/// fonts.
+fn font_count(data: &[u8]) -> Option<usize> {
+    if data.get(..4) != Some(b"ttcf") {
+        return check_table_directory(data, 0).map(|_| 1);
+    }
+    let count = read(data, 8, 4)?;
+    for i in 0..count {
+        check_table_directory(data, read(data, 12 + 4 * i, 4)?)?;
+    }
+    if count > 0 {
+        Some(count)
+    } else {
+        None
+    }
+}
+
+fn check_table_directory(data: &[u8], offset: usize) -> Option<()> {
+    match data.get(offset..offset.checked_add(4)?)? {
+        b"\0\x01\0\0" | b"true" | b"OTTO" | b"typ1" => {}
+        _ => return None,
+    }
+    let count = read(data, offset + 4, 2)?;
+    let mut tables = Vec::with_capacity(count);
+    for i in 0..count {
+        let record = offset + 12 + 16 * i;
+        let tag = data.get(record..record + 4)?;
+        let start = read(data, record + 8, 4)?;
+        let len = read(data, record + 12, 4)?;
+        let table = data.get(start..start.checked_add(len)?)?;
+        for &(name, min_len) in &MIN_TABLE_LENS {
+            if tag == name && len < min_len {
+                return None;
+            }
+        }
+        tables.push((tag, table));
+    }
+
+    let table = |name: &[u8]| tables.iter().find(|t| t.0 == name).map(|t| t.1);
+    if let Some(cmap) = table(b"cmap") {
+        check_cmap(cmap)?;
+    }
+    if let (Some(head), Some(maxp), Some(loca), Some(glyf)) = (
+        table(b"head"),
+        table(b"maxp"),
+        table(b"loca"),
+        table(b"glyf"),
+    ) {
+        check_loca(head, maxp, loca, glyf)?;
+    }
+    if let (Some(hhea), Some(maxp), Some(hmtx)) = (table(b"hhea"), table(b"maxp"), table(b"hmtx")) {
+        check_hmtx(hhea, maxp, hmtx)?;
+    }
+    Some(())
+}
+
+/// Checks that the encoding records and the subtables they point to are all
+/// inside of the cmap table.
+fn check_cmap(cmap: &[u8]) -> Option<()> {
+    let count = read(cmap, 2, 2)?;
+    if cmap.len() < 4 + 8 * count {
+        return None;
+    }
+    for i in 0..count {
+        let offset = read(cmap, 8 + 8 * i, 4)?;
+        let format = read(cmap, offset, 2)?;
+        let len = match format {
+            0..=6 => read(cmap, offset + 2, 2)?,
+            14 => read(cmap, offset + 2, 4)?,
+            _ => read(cmap, offset + 4, 4)?,
+        };
+        // The lengths of the arrays the formats that get looked up in consist
+        // of.
+        let min_len = match format {
+            0 => 262,
+            4 => 16 + 4 * read(cmap, offset + 6, 2)?,
+            6 => 10 + 2 * read(cmap, offset + 8, 2)?,
+            12 | 13 => read(cmap, offset + 12, 4)?
+                .checked_mul(12)?
+                .checked_add(16)?,
+            _ => 0,
+        };
+        if len < min_len || offset.checked_add(len)? > cmap.len() {
+            return None;
+        }
+    }
+    Some(())
+}
+
+/// Checks that there is an offset for every glyph in the loca table and that
+/// all of them are inside of the glyf table.
+fn check_loca(head: &[u8], maxp: &[u8], loca: &[u8], glyf: &[u8]) -> Option<()> {
+    let glyphs = read(maxp, 4, 2)?;
+    // The offsets are either 32 bits or 16 bits that get doubled.
+    let (bytes, scale) = match read(head, 50, 2)? {
+        0 => (2, 2),
+        _ => (4, 1),
+    };
+    for i in 0..glyphs + 1 {
+        if read(loca, bytes * i, bytes)? * scale > glyf.len() {
+            return None;
+        }
+    }
+    Some(())
+}
+
+/// Checks that the hmtx table contains all the metrics the hhea table
+/// declares, plus a left side bearing for every other glyph.
+fn check_hmtx(hhea: &[u8], maxp: &[u8], hmtx: &[u8]) -> Option<()> {
+    let metrics = read(hhea, 34, 2)?;
+    let glyphs = read(maxp, 4, 2)?;
+    if metrics == 0 || hmtx.len() < 4 * metrics + 2 * glyphs.saturating_sub(metrics) {
+        return None;
+    }
+    Some(())
+}
+
+/// Loads the selected font. Unless the glyphs are checked, the characters the
+/// font doesn't cover are drawn as the .notdef glyph, otherwise they are an
+/// error.
+fn load_font<'a>(
+    board: &Bingo,
+    options: &RenderOptions<'a>,
+    check_glyphs: bool,
+) -> Result<Font<'a>, RenderError> {
+    let (data, index) = (options.font, options.font_index);
+    let count = font_count(data).ok_or(RenderError::InvalidFont)?;
+    let font = match FontCollection::from_bytes(data).font_at(index) {
+        Some(font) => font,
+        None if index >= count => {
+            return Err(RenderError::MissingFace {
+                index: index,
+                count: count,
+            })
+        }
+        None => return Err(RenderError::InvalidFont),
+    };
+    if !check_glyphs {
+        return Ok(font);
+    }
+
+    for cell in 0..board.cells.len() {
+        let (text, icon) = layout::cell_text(board, cell, options);
+        let characters = text.chars().chain(icon.unwrap_or("").chars());
+        for character in characters.filter(|c| !c.is_whitespace()) {
+            // The .notdef glyph always has the id 0.
+            let glyph = font.glyph(character);
+            if glyph.map_or(GlyphId(0), |g| g.id()) == GlyphId(0) {
+                return Err(RenderError::MissingGlyph {
+                    cell: cell,
+                    character: character,
+                });
+            }
+        }
+    }
+
+    Ok(font)
+}
+
+/// Makes sure that the progress has a state for every cell of the board.
+fn check_progress(board: &Bingo, progress: &Progress) -> Result<(), RenderError> {
+    let expected = board.size * board.size;
+    if progress.cells.len() != expected {
+        return Err(RenderError::InvalidProgress {
+            cells: progress.cells.len(),
+            expected: expected,
+        });
+    }
+    Ok(())
+}
+
+fn rgba(color: Color) -> Rgba<u8> {
+    Rgba::from_channels(color.r, color.g, color.b, color.a)
+}
+
+/// Blends the color on top of the rectangle, unlike `draw_filled_rect_mut`,
+/// which replaces the pixels. The parts outside of the image are left out.
+fn blend_rect(image: &mut RgbaImage, x: u32, y: u32, width: u32, height: u32, color: Color) {
+    if color.a == 0 {
+        return;
+    }
+    for y in y..(y + height).min(image.height()) {
+        for x in x..(x + width).min(image.width()) {
+            image.get_pixel_mut(x, y).blend(&rgba(color));
+        }
+    }
+}
+
+/// Fills the square with the colors, which are split into diagonal stripes
+/// from the top left to the bottom right if there are multiple.
+fn fill_stripes(image: &mut RgbaImage, x: u32, y: u32, size: u32, colors: &[Color]) {
+    match colors.len() {
+        0 => {}
+        1 => blend_rect(image, x, y, size, size, colors[0]),
+        count => {
+            for v in 0..size {
+                for u in 0..size {
+                    // The stripe is determined by the center of the pixel, so
+                    // the stripes are the same as the SVG's gradient.
+                    let t = (u + v + 1) as usize * count / (2 * size) as usize;
+                    let color = colors[t.min(count - 1)];
+                    image.get_pixel_mut(x + u, y + v).blend(&rgba(color));
+                }
+            }
+        }
+    }
+}
+
+pub fn render(board: &Bingo, options: &RenderOptions) -> RgbaImage {
+    render_progress(board, &Progress::new(board.size), options)
+}
+
+/// Renders the board with the cells filled in the colors of the teams that
+/// completed them and the completed lines highlighted.
+pub fn render_progress(board: &Bingo, progress: &Progress, options: &RenderOptions) -> RgbaImage {
+    match draw_progress(board, progress, options, false) {
+        Ok(image) => image,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+pub fn try_render(board: &Bingo, options: &RenderOptions) -> Result<RgbaImage, RenderError> {
+    try_render_progress(board, &Progress::new(board.size), options)
+}
+
+/// Like `render_progress`, but the font has to contain all the characters of
+/// the goals.
+pub fn try_render_progress(
+    board: &Bingo,
+    progress: &Progress,
+    options: &RenderOptions,
+) -> Result<RgbaImage, RenderError> {
+    draw_progress(board, progress, options, true)
+}
+
+fn draw_progress(
+    board: &Bingo,
+    progress: &Progress,
+    options: &RenderOptions,
+    check_glyphs: bool,
+) -> Result<RgbaImage, RenderError> {
+    let cell_size = options.cell_size;
+    let theme = &options.theme;
+    let total_size = board.size as u32 * cell_size;
+    check_progress(board, progress)?;
+    let font = load_font(board, options, check_glyphs)?;
+    let cells = layout::fit_cells(board, options, &font);
+
+    let mut image = RgbaImage::new(total_size, total_size);
+
+    draw_filled_rect_mut(
+        &mut image,
+        Rect::at(0, 0).of_size(total_size, total_size),
+        rgba(theme.background),
+    );
+
+    for cell_x in 0..board.size as u32 {
+        let begin_x = cell_size * cell_x;
+
+        for cell_y in 0..board.size as u32 {
+            let begin_y = cell_size * cell_y;
+
+            blend_rect(
+                &mut image, begin_x, begin_y, cell_size, cell_size, theme.cell,
+            );
+
+            let index = cell_y as usize * board.size + cell_x as usize;
+            let fills = theme.fills(&progress.cells[index]);
+            fill_stripes(&mut image, begin_x, begin_y, cell_size, &fills);
+            if progress.is_highlighted(board.size, index) {
+                blend_rect(
+                    &mut image,
+                    begin_x,
+                    begin_y,
+                    cell_size,
+                    cell_size,
+                    theme.highlight,
+                );
+            }
+
+            for offset in 1..theme.border_width as i32 + 1 {
+                draw_hollow_rect_mut(
+                    &mut image,
+                    Rect::at(begin_x as i32 - offset, begin_y as i32 - offset)
+                        .of_size(cell_size, cell_size),
+                    rgba(theme.border_dark),
+                );
+
+                draw_hollow_rect_mut(
+                    &mut image,
+                    Rect::at(begin_x as i32 + offset, begin_y as i32 + offset)
+                        .of_size(cell_size, cell_size),
+                    rgba(theme.border_light),
+                );
+            }
+        }
+    }
+
+    if theme.blur > 0.0 {
+        image = gaussian_blur_f32(&image, theme.blur);
+    }
+
+    for (cell_y, row) in cells.chunks(board.size).enumerate() {
+        let begin_y = cell_size * cell_y as u32;
+        for (cell_x, text) in row.iter().enumerate() {
+            let begin_x = cell_size * cell_x as u32;
+            let index = cell_y * board.size + cell_x;
+            if progress.is_hidden(index) {
+                continue;
+            }
+
+            for line in text.place(options, &font) {
+                let position = point(begin_x as f32 + line.x, begin_y as f32 + line.baseline);
+                draw_text(
+                    &mut image,
+                    &font,
+                    &line,
+                    text.font_size,
+                    position,
+                    theme.text,
+                );
+            }
+
+            if let Some(icon) = layout::cell_text(board, index, options).1 {
+                let badge = layout::place_badge(icon, options, &font);
+                let x = (begin_x as f32 + badge.x).max(0.0).round() as u32;
+                let y = (begin_y as f32 + badge.y).max(0.0).round() as u32;
+                let (width, height) = (badge.width.round() as u32, badge.height.round() as u32);
+                blend_rect(&mut image, x, y, width, height, theme.badge);
+                let position = point(
+                    begin_x as f32 + badge.icon.x,
+                    begin_y as f32 + badge.icon.baseline,
+                );
+                draw_text(
+                    &mut image,
+                    &font,
+                    &badge.icon,
+                    options.badge_size,
+                    position,
+                    theme.badge_text,
+                );
+            }
+        }
+    }
+
+    Ok(image)
+}
+
+/// Draws the line with its baseline starting at the position.
+fn draw_text(
+    image: &mut RgbaImage,
+    font: &Font,
+    line: &PlacedLine,
+    size: f32,
+    position: Point<f32>,
+    color: Color,
+) {
+    let (width, height) = image.dimensions();
+    for glyph in font.layout(line.text, Scale::uniform(size), position) {
+        if let Some(bb) = glyph.pixel_bounding_box() {
+            glyph.draw(|x, y, v| {
+                let x = x as i32 + bb.min.x;
+                let y = y as i32 + bb.min.y;
+                if x < width as i32 && y < height as i32 && x >= 0 && y >= 0 {
+                    let pixel = image.get_pixel_mut(x as _, y as _);
+                    let alpha = color.a as f32 * v;
+                    pixel.blend(&Rgba::from_channels(color.r, color.g, color.b, alpha as _));
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {Mode, Template};
+
+    /// A font file with a single table of the length at the offset.
+    fn font_file(tag: &[u8], offset: u32, len: u32) -> Vec<u8> {
+        let mut data = b"\0\x01\0\0\0\x01\0\x10\0\0\0\0".to_vec();
+        data.extend_from_slice(tag);
+        data.extend_from_slice(&[0; 4]);
+        for n in &[offset, len] {
+            data.extend((0..4).rev().map(|i| (n >> (8 * i)) as u8));
+        }
+        data.resize(128, 0);
+        data
+    }
+
+    #[test]
+    fn font_header() {
+        assert_eq!(font_count(&font_file(b"head", 28, 54)), Some(1));
+        assert_eq!(font_count(&font_file(b"head", 28, 53)), None);
+        assert_eq!(font_count(&font_file(b"name", 28, 100)), Some(1));
+        assert_eq!(font_count(&font_file(b"name", 28, 101)), None);
+        assert_eq!(font_count(&font_file(b"name", 0xffff_fff0, 0x20)), None);
+
+        let mut collection = b"ttcf\0\x01\0\0\0\0\0\x02\0\0\0\x14\0\0\0\x14".to_vec();
+        collection.extend(font_file(b"maxp", 48, 6));
+        assert_eq!(font_count(&collection), Some(2));
+        collection[15] = 0x15;
+        assert_eq!(font_count(&collection), None);
+        assert_eq!(font_count(b"ttcf\0\x01\0\0\0\0\0\0"), None);
+    }
+
+    #[test]
+    fn invalid_font() {
+        let template = Template::from_json_str(include_str!("../templates/sm64.json")).unwrap();
+        let board = template.generate(587062, Mode::Normal);
+        for font in &[&[][..], &[0; 64][..], b"\0\x01\0\0\0\x10"] {
+            let options = RenderOptions::new(font);
+            assert_eq!(
+                try_render(&board, &options).err(),
+                Some(RenderError::InvalidFont)
+            );
+            assert_eq!(
+                try_render_svg(&board, &options).err(),
+                Some(RenderError::InvalidFont)
+            );
+        }
+        assert_eq!(
+            RenderError::MissingGlyph {
+                cell: 7,
+                character: '★',
+            }
+            .to_string(),
+            "The font does not contain the character '★' of the goal in cell 7"
+        );
+    }
+
+    #[test]
+    fn invalid_progress() {
+        let template = Template::from_json_str(include_str!("../templates/sm64.json")).unwrap();
+        let board = template.generate(587062, Mode::Normal);
+        let options = RenderOptions::new(&[]);
+        let error = RenderError::InvalidProgress {
+            cells: 16,
+            expected: 25,
+        };
+        assert_eq!(
+            try_render_progress(&board, &Progress::new(4), &options).err(),
+            Some(error.clone())
+        );
+        assert_eq!(
+            try_render_svg_progress(&board, &Progress::new(4), &options).err(),
+            Some(error.clone())
+        );
+        assert_eq!(
+            error.to_string(),
+            "The progress has 16 cells, but the board has 25"
+        );
+    }
+}
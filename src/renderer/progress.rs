@@ -0,0 +1,184 @@
+use {BoardState, Line, Lockout, Visibility};
+
+/// Who completed a cell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CellState {
+    Unmarked,
+    /// The cell is completed, but doesn't belong to any team.
+    Marked,
+    /// The cell is completed by the teams, in the order they completed it.
+    /// Outside of lockout, multiple teams can complete the same cell.
+    Claimed(Vec<usize>),
+    /// The player can't see the cell, so neither its goal nor who completed
+    /// it gets drawn.
+    Hidden,
+}
+
+/// The progress on a board that gets rendered along with the goals.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Progress {
+    /// The state of every cell, row by row.
+    pub cells: Vec<CellState>,
+    /// The lines that get highlighted as completed.
+    pub lines: Vec<Line>,
+}
+
+impl Progress {
+    /// Creates the progress of a board of the size without any completed
+    /// cells.
+    pub fn new(size: usize) -> Self {
+        Progress {
+            cells: vec![CellState::Unmarked; size * size],
+            lines: Vec::new(),
+        }
+    }
+
+    /// The progress of a single player or team, where the completed cells
+    /// are marked without any team color.
+    pub fn from_state(state: &BoardState) -> Self {
+        Progress {
+            cells: state
+                .marks
+                .iter()
+                .map(|m| match *m {
+                    Some(_) => CellState::Marked,
+                    None => CellState::Unmarked,
+                })
+                .collect(),
+            lines: state.completed_lines().collect(),
+        }
+    }
+
+    /// The progress of multiple teams playing on the same board, where each
+    /// team tracks its own board state. Cells that multiple teams completed
+    /// are claimed by all of them, in the order they completed the cell.
+    /// Returns `None` if the states are of boards of different sizes.
+    pub fn from_teams(states: &[BoardState]) -> Option<Self> {
+        let size = states.first().map_or(0, |s| s.size);
+        if states.iter().any(|s| s.size != size) {
+            return None;
+        }
+        let mut progress = Progress::new(size);
+        for (i, cell) in progress.cells.iter_mut().enumerate() {
+            let mut teams = states
+                .iter()
+                .enumerate()
+                .filter_map(|(team, s)| s.marks[i].map(|time| (time, team)))
+                .collect::<Vec<_>>();
+            teams.sort();
+            if !teams.is_empty() {
+                *cell = CellState::Claimed(teams.into_iter().map(|(_, team)| team).collect());
+            }
+        }
+        for state in states {
+            for line in state.completed_lines() {
+                if !progress.lines.contains(&line) {
+                    progress.lines.push(line);
+                }
+            }
+        }
+        Some(progress)
+    }
+
+    /// The progress of a lockout game, where the lines a team claimed
+    /// completely are highlighted.
+    pub fn from_lockout(game: &Lockout) -> Self {
+        let size = game.size();
+        let mut progress = Progress::new(size);
+        for (i, cell) in progress.cells.iter_mut().enumerate() {
+            if let Some(claim) = game.owner(i % size, i / size) {
+                *cell = CellState::Claimed(vec![claim.team]);
+            }
+        }
+        for team in 0..game.teams() {
+            progress.lines.extend(game.lines(team));
+        }
+        progress
+    }
+
+    /// Hides the cells the player can't see. The lines going through any of
+    /// them aren't highlighted anymore either.
+    pub fn hide(&mut self, visibility: &Visibility) {
+        for (cell, &visible) in self.cells.iter_mut().zip(&visibility.cells) {
+            if !visible {
+                *cell = CellState::Hidden;
+            }
+        }
+        let size = visibility.size;
+        self.lines
+            .retain(|line| line.cells(size).all(|i| visibility.cells[i]));
+    }
+
+    pub fn is_hidden(&self, index: usize) -> bool {
+        self.cells[index] == CellState::Hidden
+    }
+
+    /// Whether the cell is part of one of the highlighted lines.
+    pub fn is_highlighted(&self, size: usize, index: usize) -> bool {
+        self.lines
+            .iter()
+            .any(|line| line.cells(size).any(|i| i == index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Rules;
+
+    #[test]
+    fn teams() {
+        let mut red = BoardState::with_size(3);
+        let mut blue = BoardState::with_size(3);
+        for x in 0..3 {
+            red.mark(x, 0, 10);
+        }
+        red.mark(1, 1, 50);
+        blue.mark(1, 1, 20);
+
+        assert_eq!(
+            Progress::from_teams(&[red.clone(), BoardState::with_size(4)]),
+            None
+        );
+        let progress = Progress::from_teams(&[red, blue]).unwrap();
+        assert_eq!(progress.cells[0], CellState::Claimed(vec![0]));
+        assert_eq!(progress.cells[4], CellState::Claimed(vec![1, 0]));
+        assert_eq!(progress.cells[8], CellState::Unmarked);
+        assert_eq!(progress.lines, [Line::Row(0)]);
+        assert!(progress.is_highlighted(3, 2));
+        assert!(!progress.is_highlighted(3, 4));
+    }
+
+    #[test]
+    fn lockout() {
+        let mut game = Lockout::with_size(3, 2, Rules::default());
+        for y in 0..3 {
+            game.claim(1, 2, y, 0).unwrap();
+        }
+        let progress = Progress::from_lockout(&game);
+        assert_eq!(progress.cells[2], CellState::Claimed(vec![1]));
+        assert_eq!(progress.cells[0], CellState::Unmarked);
+        assert_eq!(progress.lines, [Line::Column(2)]);
+    }
+
+    #[test]
+    fn hide() {
+        let mut state = BoardState::with_size(3);
+        for x in 0..3 {
+            state.mark(x, 0, 10);
+        }
+        state.mark(0, 1, 20);
+        state.mark(0, 2, 30);
+        let mut progress = Progress::from_state(&state);
+        assert_eq!(progress.lines, [Line::Row(0), Line::Column(0)]);
+
+        let mut visibility = Visibility::all(3);
+        visibility.cells[6] = false;
+        progress.hide(&visibility);
+        assert_eq!(progress.cells[3], CellState::Marked);
+        assert_eq!(progress.cells[6], CellState::Hidden);
+        assert!(progress.is_hidden(6));
+        assert!(!progress.is_hidden(7));
+        assert_eq!(progress.lines, [Line::Row(0)]);
+    }
+}
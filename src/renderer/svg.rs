@@ -0,0 +1,382 @@
+use super::layout::{cell_text, fit_cells, place_badge, Measure};
+use super::{check_progress, load_font, Align, Color, Progress, RenderError, RenderOptions};
+use std::fmt::Write;
+use Bingo;
+
+/// Renders the board as an SVG with the same layout as the raster image. The
+/// font is only used to lay out the text, the SVG refers to it by its family
+/// name.
+pub fn render_svg(board: &Bingo, options: &RenderOptions) -> String {
+    render_svg_progress(board, &Progress::new(board.size), options)
+}
+
+/// Renders the board and its progress as an SVG with the same layout as the
+/// raster image.
+pub fn render_svg_progress(board: &Bingo, progress: &Progress, options: &RenderOptions) -> String {
+    let font = check_progress(board, progress).and_then(|_| load_font(board, options, false));
+    match font {
+        Ok(font) => write_svg(board, progress, options, &font),
+        Err(e) => panic!("{}", e),
+    }
+}
+
+pub fn try_render_svg(board: &Bingo, options: &RenderOptions) -> Result<String, RenderError> {
+    try_render_svg_progress(board, &Progress::new(board.size), options)
+}
+
+/// Like `render_svg_progress`, but the font has to contain all the characters
+/// of the goals.
+pub fn try_render_svg_progress(
+    board: &Bingo,
+    progress: &Progress,
+    options: &RenderOptions,
+) -> Result<String, RenderError> {
+    check_progress(board, progress)?;
+    let font = load_font(board, options, true)?;
+    Ok(write_svg(board, progress, options, &font))
+}
+
+fn write_svg<M: Measure>(
+    board: &Bingo,
+    progress: &Progress,
+    options: &RenderOptions,
+    measure: &M,
+) -> String {
+    let cell_size = options.cell_size;
+    let font_size = options.font_size;
+    let total_size = board.size as u32 * cell_size;
+
+    let theme = &options.theme;
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{0}" viewBox="0 0 {0} {0}">"#,
+        total_size
+    ).unwrap();
+    if theme.blur > 0.0 {
+        writeln!(
+            svg,
+            r#"<defs><filter id="blur"><feGaussianBlur stdDeviation="{}"/></filter></defs>"#,
+            theme.blur
+        )
+        .unwrap();
+    }
+    writeln!(
+        svg,
+        r#"<rect width="{0}" height="{0}" fill="{1}"/>"#,
+        total_size, theme.background
+    )
+    .unwrap();
+
+    // The borders are one pixel wide lines, which are centered on the pixels
+    // the raster renderer draws them on.
+    svg.push_str(r#"<g fill="none""#);
+    if theme.blur > 0.0 {
+        svg.push_str(r#" filter="url(#blur)""#);
+    }
+    svg.push_str(">\n");
+    for cell_y in 0..board.size as u32 {
+        let begin_y = cell_size * cell_y;
+        for cell_x in 0..board.size as u32 {
+            let begin_x = cell_size * cell_x;
+            fill_rect(&mut svg, begin_x, begin_y, cell_size, theme.cell);
+
+            let index = cell_y as usize * board.size + cell_x as usize;
+            let fills = theme.fills(&progress.cells[index]);
+            if fills.len() > 1 {
+                // The gradient's hard stops split the cell into diagonal
+                // stripes.
+                writeln!(
+                    svg,
+                    r#"<linearGradient id="stripes{}" x1="0" y1="0" x2="1" y2="1">"#,
+                    index
+                )
+                .unwrap();
+                for (i, color) in fills.iter().enumerate() {
+                    for &offset in &[i, i + 1] {
+                        writeln!(
+                            svg,
+                            r#"<stop offset="{}" stop-color="{}"/>"#,
+                            offset as f32 / fills.len() as f32,
+                            color
+                        )
+                        .unwrap();
+                    }
+                }
+                svg.push_str("</linearGradient>\n");
+                writeln!(
+                    svg,
+                    r#"<rect x="{}" y="{}" width="{2}" height="{2}" fill="url(#stripes{3})"/>"#,
+                    begin_x, begin_y, cell_size, index
+                )
+                .unwrap();
+            } else if let Some(&color) = fills.first() {
+                fill_rect(&mut svg, begin_x, begin_y, cell_size, color);
+            }
+            if progress.is_highlighted(board.size, index) {
+                fill_rect(&mut svg, begin_x, begin_y, cell_size, theme.highlight);
+            }
+
+            for offset in 1..theme.border_width as i32 + 1 {
+                for &(offset, color) in
+                    &[(-offset, theme.border_dark), (offset, theme.border_light)]
+                {
+                    writeln!(
+                        svg,
+                        r#"<rect x="{}" y="{}" width="{2}" height="{2}" stroke="{3}"/>"#,
+                        begin_x as f32 + offset as f32 + 0.5,
+                        begin_y as f32 + offset as f32 + 0.5,
+                        cell_size - 1,
+                        color
+                    )
+                    .unwrap();
+                }
+            }
+        }
+    }
+    svg.push_str("</g>\n");
+
+    svg.push_str(r#"<g font-family=""#);
+    escape(&mut svg, options.font_family);
+    let anchor = match options.align {
+        Align::Left => "start",
+        Align::Center => "middle",
+        Align::Right => "end",
+    };
+    writeln!(
+        svg,
+        r#"" font-size="{}" fill="{}" text-anchor="{}">"#,
+        font_size, theme.text, anchor
+    )
+    .unwrap();
+    let cells = fit_cells(board, options, measure);
+    for (cell_y, row) in cells.chunks(board.size).enumerate() {
+        let begin_y = cell_size * cell_y as u32;
+        for (cell_x, text) in row.iter().enumerate() {
+            let begin_x = cell_size * cell_x as u32;
+            let index = cell_y * board.size + cell_x;
+            if progress.is_hidden(index) {
+                continue;
+            }
+
+            for line in text.place(options, measure) {
+                let x = begin_x as f32 + line.anchor;
+                let y = begin_y as f32 + line.baseline;
+                write!(svg, r#"<text x="{}" y="{}""#, x, y).unwrap();
+                // Only the text that got shrunk differs from the group's size.
+                if text.font_size != font_size {
+                    write!(svg, r#" font-size="{}""#, text.font_size).unwrap();
+                }
+                svg.push('>');
+                escape(&mut svg, line.text);
+                svg.push_str("</text>\n");
+            }
+
+            if let Some(icon) = cell_text(board, index, options).1 {
+                let badge = place_badge(icon, options, measure);
+                writeln!(
+                    svg,
+                    r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
+                    begin_x as f32 + badge.x,
+                    begin_y as f32 + badge.y,
+                    badge.width,
+                    badge.height,
+                    theme.badge
+                )
+                .unwrap();
+                write!(
+                    svg,
+                    r#"<text x="{}" y="{}" font-size="{}" fill="{}" text-anchor="middle">"#,
+                    begin_x as f32 + badge.icon.anchor,
+                    begin_y as f32 + badge.icon.baseline,
+                    options.badge_size,
+                    theme.badge_text
+                )
+                .unwrap();
+                escape(&mut svg, icon);
+                svg.push_str("</text>\n");
+            }
+        }
+    }
+    svg.push_str("</g>\n</svg>\n");
+
+    svg
+}
+
+fn fill_rect(svg: &mut String, x: u32, y: u32, size: u32, color: Color) {
+    if color.a != 0 {
+        writeln!(
+            svg,
+            r#"<rect x="{}" y="{}" width="{2}" height="{2}" fill="{3}"/>"#,
+            x, y, size, color
+        )
+        .unwrap();
+    }
+}
+
+fn escape(svg: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => svg.push_str("&amp;"),
+            '<' => svg.push_str("&lt;"),
+            '>' => svg.push_str("&gt;"),
+            '"' => svg.push_str("&quot;"),
+            c => svg.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrayvec::ArrayVec;
+    use renderer::layout::tests::Monospace;
+    use renderer::{CellState, Color, Theme};
+    use {Fog, Line, Visibility};
+
+    #[test]
+    fn snapshot() {
+        let mut cells = ArrayVec::new();
+        cells.push("Red Coin Star in WF");
+        cells.push("Beat the King in BOB ★");
+        cells.push("Mario & <Luigi>");
+        cells.push("Open 9 Cannons");
+        let board = Bingo {
+            size: 2,
+            cells: cells,
+            icons: [None, None, Some("&"), None].iter().cloned().collect(),
+        };
+        let mut options = RenderOptions {
+            font_family: "Fira \"Sans\"",
+            font_size: 10.0,
+            cell_size: 100,
+            ..RenderOptions::new(&[])
+        };
+        let mut progress = Progress::new(2);
+        // Every character is 5 pixels wide, so up to 18 characters fit in a
+        // line.
+        let measure = Monospace(0.5);
+        let svg = write_svg(&board, &progress, &options, &measure);
+
+        assert_eq!(
+            svg,
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="200" viewBox="0 0 200 200">
+<defs><filter id="blur"><feGaussianBlur stdDeviation="0.5"/></filter></defs>
+<rect width="200" height="200" fill="#2c2f34"/>
+<g fill="none" filter="url(#blur)">
+<rect x="-0.5" y="-0.5" width="99" height="99" stroke="#1e2328"/>
+<rect x="1.5" y="1.5" width="99" height="99" stroke="#5a5f64"/>
+<rect x="99.5" y="-0.5" width="99" height="99" stroke="#1e2328"/>
+<rect x="101.5" y="1.5" width="99" height="99" stroke="#5a5f64"/>
+<rect x="-0.5" y="99.5" width="99" height="99" stroke="#1e2328"/>
+<rect x="1.5" y="101.5" width="99" height="99" stroke="#5a5f64"/>
+<rect x="99.5" y="99.5" width="99" height="99" stroke="#1e2328"/>
+<rect x="101.5" y="101.5" width="99" height="99" stroke="#5a5f64"/>
+</g>
+<g font-family="Fira &quot;Sans&quot;" font-size="10" fill="#ffffff" text-anchor="middle">
+<text x="50" y="47">Red Coin Star in</text>
+<text x="50" y="59">WF</text>
+<text x="150" y="47">Beat the King in</text>
+<text x="150" y="59">BOB</text>
+<rect x="183" y="5" width="12" height="18" fill="#00000060"/>
+<text x="189" y="17.6" font-size="12" fill="#ffd700" text-anchor="middle">★</text>
+<text x="50" y="153">Mario &amp; &lt;Luigi&gt;</text>
+<rect x="83" y="105" width="12" height="18" fill="#00000060"/>
+<text x="89" y="117.6" font-size="12" fill="#ffd700" text-anchor="middle">&amp;</text>
+<text x="150" y="153">Open 9 Cannons</text>
+</g>
+</svg>
+"##
+        );
+
+        options.theme = Theme {
+            cell: Color::rgba(255, 0, 0, 128),
+            border_width: 2,
+            blur: 0.0,
+            ..Theme::default()
+        };
+        let svg = write_svg(&board, &progress, &options, &measure);
+        assert!(svg.starts_with(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="200" viewBox="0 0 200 200">
+<rect width="200" height="200" fill="#2c2f34"/>
+<g fill="none">
+<rect x="0" y="0" width="100" height="100" fill="#ff000080"/>
+<rect x="-0.5" y="-0.5" width="99" height="99" stroke="#1e2328"/>
+<rect x="1.5" y="1.5" width="99" height="99" stroke="#5a5f64"/>
+<rect x="-1.5" y="-1.5" width="99" height="99" stroke="#1e2328"/>
+<rect x="2.5" y="2.5" width="99" height="99" stroke="#5a5f64"/>
+<rect x="100" y="0" width="100" height="100" fill="#ff000080"/>
+"##
+        ));
+
+        options.theme = Theme {
+            teams: vec![Color::rgb(255, 0, 0), Color::rgb(0, 0, 255)],
+            blur: 0.0,
+            ..Theme::default()
+        };
+        progress.cells[0] = CellState::Marked;
+        progress.cells[1] = CellState::Claimed(vec![1, 0]);
+        progress.cells[3] = CellState::Claimed(vec![0]);
+        progress.lines.push(Line::TopLeftToBottomRight);
+        let svg = write_svg(&board, &progress, &options, &measure);
+        let cells = &svg[svg.find("<g fill").unwrap()..svg.find("</g>").unwrap()];
+        assert_eq!(
+            cells,
+            r##"<g fill="none">
+<rect x="0" y="0" width="100" height="100" fill="#31d814"/>
+<rect x="0" y="0" width="100" height="100" fill="#ffffff30"/>
+<rect x="-0.5" y="-0.5" width="99" height="99" stroke="#1e2328"/>
+<rect x="1.5" y="1.5" width="99" height="99" stroke="#5a5f64"/>
+<linearGradient id="stripes1" x1="0" y1="0" x2="1" y2="1">
+<stop offset="0" stop-color="#0000ff"/>
+<stop offset="0.5" stop-color="#0000ff"/>
+<stop offset="0.5" stop-color="#ff0000"/>
+<stop offset="1" stop-color="#ff0000"/>
+</linearGradient>
+<rect x="100" y="0" width="100" height="100" fill="url(#stripes1)"/>
+<rect x="99.5" y="-0.5" width="99" height="99" stroke="#1e2328"/>
+<rect x="101.5" y="1.5" width="99" height="99" stroke="#5a5f64"/>
+<rect x="-0.5" y="99.5" width="99" height="99" stroke="#1e2328"/>
+<rect x="1.5" y="101.5" width="99" height="99" stroke="#5a5f64"/>
+<rect x="100" y="100" width="100" height="100" fill="#ff0000"/>
+<rect x="100" y="100" width="100" height="100" fill="#ffffff30"/>
+<rect x="99.5" y="99.5" width="99" height="99" stroke="#1e2328"/>
+<rect x="101.5" y="101.5" width="99" height="99" stroke="#5a5f64"/>
+"##
+        );
+    }
+
+    #[test]
+    fn hidden() {
+        let board = Bingo {
+            size: 2,
+            cells: ["Red Coins", "Secret <1>", "Secret 2", "100 Coins"]
+                .iter()
+                .cloned()
+                .collect(),
+            icons: [None, Some("★"), None, None].iter().cloned().collect(),
+        };
+        let options = RenderOptions {
+            font_size: 10.0,
+            cell_size: 100,
+            ..RenderOptions::new(&[])
+        };
+        let mut progress = Progress::new(2);
+        progress.cells[0] = CellState::Marked;
+        progress.hide(&Fog::RowByRow.visibility(2, |i| i == 0));
+        assert_eq!(progress.cells[1], CellState::Unmarked);
+
+        let mut visibility = Visibility::all(2);
+        visibility.cells[1] = false;
+        visibility.cells[2] = false;
+        progress.hide(&visibility);
+        let svg = write_svg(&board, &progress, &options, &Monospace(0.5));
+        assert!(svg.contains(r##"<rect x="100" y="0" width="100" height="100" fill="#16181b"/>"##));
+        assert!(svg.contains(r##"<rect x="0" y="100" width="100" height="100" fill="#16181b"/>"##));
+        assert!(svg.contains("Red Coins"));
+        assert!(svg.contains("100 Coins"));
+        assert!(!svg.contains("Secret"));
+        assert!(!svg.contains('★'));
+    }
+}
@@ -0,0 +1,258 @@
+use super::CellState;
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::ser::{Serialize, Serializer};
+use serde_json::{self, Result as JsonResult};
+use std::fmt;
+
+/// A color with an alpha channel. In themes, colors are written as hex codes
+/// like `"#2c2f34"` or `"#2c2f3480"`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    pub const TRANSPARENT: Color = Color::rgba(0, 0, 0, 0);
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Color::rgba(r, g, b, 255)
+    }
+
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Color {
+            r: r,
+            g: g,
+            b: b,
+            a: a,
+        }
+    }
+
+    /// Parses a hex code with either 6 or 8 digits, starting with a `#`.
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        if !hex.starts_with('#') || !hex[1..].bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+        match hex.len() {
+            7 => Some(Color::rgb(channel(1), channel(3), channel(5))),
+            9 => Some(Color::rgba(channel(1), channel(3), channel(5), channel(7))),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Color {
+    /// Formats the color as a hex code, which only includes the alpha
+    /// channel if the color isn't opaque.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)?;
+        if self.a != 255 {
+            write!(f, "{:02x}", self.a)?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct HexVisitor;
+
+        impl<'de> Visitor<'de> for HexVisitor {
+            type Value = Color;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a hex color code like #2c2f34")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Color, E> {
+                Color::from_hex(v).ok_or_else(|| E::invalid_value(de::Unexpected::Str(v), &self))
+            }
+        }
+
+        deserializer.deserialize_str(HexVisitor)
+    }
+}
+
+/// The colors and effects a board is rendered with. Themes can be stored as
+/// JSON, where every missing setting falls back to the default theme.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    /// The color behind the whole board.
+    pub background: Color,
+    /// The color every cell is filled with, on top of the background.
+    pub cell: Color,
+    /// The color of the border line above and left of each cell.
+    pub border_dark: Color,
+    /// The color of the border line below and right of each cell.
+    pub border_light: Color,
+    /// The width of each of the two border lines in pixels.
+    pub border_width: u32,
+    /// The standard deviation of the gaussian blur that softens the borders.
+    /// The borders aren't blurred at all if this is 0.
+    pub blur: f32,
+    pub text: Color,
+    /// The color of cells that are completed without belonging to a team.
+    pub marked: Color,
+    /// The colors of the teams, by their index. If there are more teams than
+    /// colors, the colors are reused.
+    pub teams: Vec<Color>,
+    /// Whether cells that multiple teams completed are split diagonally into
+    /// the colors of all of them, instead of only showing the color of the
+    /// first one.
+    pub split_claims: bool,
+    /// The color that is blended over the cells of completed lines.
+    pub highlight: Color,
+    /// The background of the badges that show the icons of goals.
+    pub badge: Color,
+    /// The color of the icons in the badges.
+    pub badge_text: Color,
+    /// The color of the blank tiles that cells the player can't see are
+    /// drawn as.
+    pub hidden: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            background: Color::rgb(44, 47, 52),
+            cell: Color::TRANSPARENT,
+            border_dark: Color::rgb(30, 35, 40),
+            border_light: Color::rgb(90, 95, 100),
+            border_width: 1,
+            blur: 0.5,
+            text: Color::rgb(255, 255, 255),
+            marked: Color::rgb(49, 216, 20),
+            teams: vec![
+                Color::rgb(218, 68, 64),
+                Color::rgb(64, 156, 218),
+                Color::rgb(49, 216, 20),
+                Color::rgb(255, 156, 18),
+                Color::rgb(130, 45, 191),
+                Color::rgb(237, 134, 170),
+                Color::rgb(65, 150, 149),
+                Color::rgb(171, 92, 35),
+            ],
+            split_claims: true,
+            highlight: Color::rgba(255, 255, 255, 48),
+            badge: Color::rgba(0, 0, 0, 96),
+            badge_text: Color::rgb(255, 215, 0),
+            hidden: Color::rgb(22, 24, 27),
+        }
+    }
+}
+
+impl Theme {
+    /// The color of the team.
+    pub fn team(&self, team: usize) -> Color {
+        if self.teams.is_empty() {
+            self.marked
+        } else {
+            self.teams[team % self.teams.len()]
+        }
+    }
+
+    /// The colors a cell with the state is filled with, which are multiple
+    /// colors if the cell gets split between teams.
+    pub fn fills(&self, state: &CellState) -> Vec<Color> {
+        match *state {
+            CellState::Unmarked => Vec::new(),
+            CellState::Marked => vec![self.marked],
+            CellState::Hidden => vec![self.hidden],
+            CellState::Claimed(ref teams) if self.split_claims => {
+                teams.iter().map(|&team| self.team(team)).collect()
+            }
+            CellState::Claimed(ref teams) => {
+                teams.iter().take(1).map(|&team| self.team(team)).collect()
+            }
+        }
+    }
+
+    pub fn from_json_str(json: &str) -> JsonResult<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json_string(&self) -> JsonResult<String> {
+        serde_json::to_string(self)
+    }
+
+    pub fn to_json_string_pretty(&self) -> JsonResult<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex() {
+        assert_eq!(Color::from_hex("#2c2f34"), Some(Color::rgb(44, 47, 52)));
+        assert_eq!(
+            Color::from_hex("#FF000080"),
+            Some(Color::rgba(255, 0, 0, 128))
+        );
+        assert_eq!(Color::from_hex("2c2f34"), None);
+        assert_eq!(Color::from_hex("#2c2f3"), None);
+        assert_eq!(Color::from_hex("#+c2f34"), None);
+        assert_eq!(Color::from_hex("#2c2f3ö"), None);
+        assert_eq!(Color::rgb(44, 47, 52).to_string(), "#2c2f34");
+        assert_eq!(Color::TRANSPARENT.to_string(), "#00000000");
+    }
+
+    #[test]
+    fn json() {
+        let theme = Theme::from_json_str(r##"{ "background": "#000000", "blur": 0 }"##).unwrap();
+        assert_eq!(
+            theme,
+            Theme {
+                background: Color::rgb(0, 0, 0),
+                blur: 0.0,
+                ..Theme::default()
+            }
+        );
+        assert_eq!(
+            Theme::default().to_json_string().unwrap(),
+            concat!(
+                r##"{"background":"#2c2f34","cell":"#00000000","border_dark":"#1e2328","##,
+                r##""border_light":"#5a5f64","border_width":1,"blur":0.5,"text":"#ffffff","##,
+                r##""marked":"#31d814","teams":["#da4440","#409cda","#31d814","#ff9c12","##,
+                r##""#822dbf","#ed86aa","#419695","#ab5c23"],"split_claims":true,"##,
+                r##""highlight":"#ffffff30","badge":"#00000060","badge_text":"#ffd700","##,
+                r##""hidden":"#16181b"}"##
+            )
+        );
+        assert!(Theme::from_json_str(r#"{ "text": "white" }"#).is_err());
+    }
+
+    #[test]
+    fn fills() {
+        let mut theme = Theme {
+            teams: vec![Color::rgb(255, 0, 0), Color::rgb(0, 0, 255)],
+            ..Theme::default()
+        };
+        assert_eq!(theme.fills(&CellState::Unmarked), []);
+        assert_eq!(theme.fills(&CellState::Marked), [theme.marked]);
+        assert_eq!(theme.fills(&CellState::Hidden), [theme.hidden]);
+        assert_eq!(
+            theme.fills(&CellState::Claimed(vec![1, 2])),
+            [Color::rgb(0, 0, 255), Color::rgb(255, 0, 0)]
+        );
+        theme.split_claims = false;
+        assert_eq!(
+            theme.fills(&CellState::Claimed(vec![1, 2])),
+            [Color::rgb(0, 0, 255)]
+        );
+        theme.teams.clear();
+        assert_eq!(theme.fills(&CellState::Claimed(vec![1])), [theme.marked]);
+    }
+}
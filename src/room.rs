@@ -0,0 +1,673 @@
+use core::fmt;
+use {Bingo, BoardState, Fog, Visibility};
+
+/// Identifies a player. The ids are assigned by whatever the players connect
+/// through, like a server that counts its connections.
+pub type PlayerId = u64;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Player {
+    pub id: PlayerId,
+    pub name: String,
+    /// The index of the player's team.
+    pub team: usize,
+}
+
+/// A team and the cells it completed. Every team has its own board state, so
+/// multiple teams can complete the same cell.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Team {
+    pub name: String,
+    pub state: BoardState,
+}
+
+/// What players send to a room.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Command {
+    /// Joins the team with the name. The team is created if it doesn't exist
+    /// yet.
+    Join {
+        name: String,
+        team: String,
+    },
+    /// Starts the countdown, after which the board is revealed.
+    Start {
+        countdown: u64,
+    },
+    /// Marks the cell as completed by the player's team.
+    Mark {
+        x: usize,
+        y: usize,
+    },
+    Unmark {
+        x: usize,
+        y: usize,
+    },
+}
+
+/// The changes to a room, which are sent to all of its players.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Update {
+    /// The whole room as the player sees it, which is what players get when
+    /// they connect or join a team. The goals are left out until the board is
+    /// revealed, afterwards the goals of the cells the player can't see are
+    /// `None`.
+    State {
+        size: usize,
+        goals: Option<Vec<Option<String>>>,
+        starts_at: Option<u64>,
+        teams: Vec<Team>,
+        players: Vec<Player>,
+    },
+    /// A player joined, possibly creating a new team with the name.
+    Joined {
+        player: Player,
+        team_name: String,
+    },
+    Left {
+        player: PlayerId,
+    },
+    /// The countdown started and ends at the time.
+    Starting {
+        at: u64,
+    },
+    /// The countdown ended or a mark revealed cells, so their goals are
+    /// shown. Only the goals that just became visible are sent, the others
+    /// are `None`.
+    Revealed {
+        goals: Vec<Option<String>>,
+    },
+    Marked {
+        team: usize,
+        x: usize,
+        y: usize,
+        time: u64,
+    },
+    Unmarked {
+        team: usize,
+        x: usize,
+        y: usize,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RoomError {
+    /// The name of the player or the team is empty.
+    EmptyName,
+    /// The player already joined a team.
+    AlreadyJoined,
+    /// Only players that joined a team can start the race and mark cells.
+    NotJoined,
+    /// The countdown already started.
+    AlreadyStarted,
+    /// The board is still hidden, as the countdown didn't end yet.
+    Hidden,
+    /// The cell isn't on the board.
+    InvalidCell { x: usize, y: usize },
+    /// The fog hides the cell from the player's team.
+    HiddenCell { x: usize, y: usize },
+    /// The player's team already marked the cell.
+    AlreadyMarked,
+    /// The player's team didn't mark the cell.
+    NotMarked,
+}
+
+impl fmt::Display for RoomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RoomError::EmptyName => write!(f, "The name must not be empty"),
+            RoomError::AlreadyJoined => write!(f, "The player already joined a team"),
+            RoomError::NotJoined => write!(f, "The player has not joined a team"),
+            RoomError::AlreadyStarted => write!(f, "The race already started"),
+            RoomError::Hidden => write!(f, "The board is not revealed yet"),
+            RoomError::InvalidCell { x, y } => write!(f, "There is no cell at ({}, {})", x, y),
+            RoomError::HiddenCell { x, y } => write!(f, "The cell at ({}, {}) is hidden", x, y),
+            RoomError::AlreadyMarked => write!(f, "The team already marked the cell"),
+            RoomError::NotMarked => write!(f, "The team has not marked the cell"),
+        }
+    }
+}
+
+impl ::std::error::Error for RoomError {
+    fn description(&self) -> &str {
+        match *self {
+            RoomError::EmptyName => "empty name",
+            RoomError::AlreadyJoined => "already joined",
+            RoomError::NotJoined => "not joined",
+            RoomError::AlreadyStarted => "already started",
+            RoomError::Hidden => "board hidden",
+            RoomError::InvalidCell { .. } => "invalid cell",
+            RoomError::HiddenCell { .. } => "cell hidden",
+            RoomError::AlreadyMarked => "cell already marked",
+            RoomError::NotMarked => "cell not marked",
+        }
+    }
+}
+
+/// A race of multiple teams on a shared board. The board stays hidden until
+/// the countdown that starts the race ends.
+///
+/// The room doesn't do any networking and doesn't have a clock. The times are
+/// whatever the caller passes in, like milliseconds since the Unix epoch, and
+/// every change returns the update to send to the players. The goals are
+/// only sent through `state` and `tick`, so every player only gets to see
+/// the goals of the cells the fog reveals to the player's team.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Room {
+    size: usize,
+    goals: Vec<String>,
+    teams: Vec<Team>,
+    players: Vec<Player>,
+    starts_at: Option<u64>,
+    /// Which cells the teams can see and mark once the board is revealed.
+    fog: Fog,
+    /// The cells whose goals were already sent to each team.
+    shown: Vec<Visibility>,
+    /// The cells whose goals were already sent to the players without a
+    /// team.
+    shown_to_spectators: Visibility,
+}
+
+impl Room {
+    /// Creates a room for the board without any players.
+    pub fn new(bingo: &Bingo) -> Self {
+        Room::with_fog(bingo, Fog::Clear)
+    }
+
+    /// Creates a room for the board where the teams only see the cells the
+    /// fog reveals to them. Hidden cells can't be marked.
+    pub fn with_fog(bingo: &Bingo, fog: Fog) -> Self {
+        Room {
+            size: bingo.size,
+            goals: bingo.cells.iter().map(|&goal| goal.to_owned()).collect(),
+            teams: Vec::new(),
+            players: Vec::new(),
+            starts_at: None,
+            fog: fog,
+            shown: Vec::new(),
+            shown_to_spectators: Visibility::none(bingo.size),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn fog(&self) -> Fog {
+        self.fog
+    }
+
+    pub fn teams(&self) -> &[Team] {
+        &self.teams
+    }
+
+    pub fn players(&self) -> &[Player] {
+        &self.players
+    }
+
+    pub fn player(&self, id: PlayerId) -> Option<&Player> {
+        self.players.iter().find(|p| p.id == id)
+    }
+
+    /// When the countdown ends, if it started.
+    pub fn starts_at(&self) -> Option<u64> {
+        self.starts_at
+    }
+
+    /// Whether the countdown ended at the time, so the board can be seen.
+    pub fn is_revealed(&self, time: u64) -> bool {
+        match self.starts_at {
+            Some(at) => time >= at,
+            None => false,
+        }
+    }
+
+    /// The goals of all the cells, row by row, if the board is revealed at
+    /// the time.
+    pub fn goals(&self, time: u64) -> Option<&[String]> {
+        if self.is_revealed(time) {
+            Some(&self.goals)
+        } else {
+            None
+        }
+    }
+
+    /// Which cells the player can see at the time. Nothing can be seen
+    /// before the board is revealed, afterwards the cells the player's team
+    /// completed reveal the others, depending on the fog.
+    pub fn visibility(&self, player: PlayerId, time: u64) -> Result<Visibility, RoomError> {
+        let team = self.team(player)?;
+        Ok(self.team_visibility(Some(team), time))
+    }
+
+    /// Which cells the team can see at the time. The players without a team
+    /// see what a team that didn't complete any cells sees.
+    fn team_visibility(&self, team: Option<usize>, time: u64) -> Visibility {
+        if !self.is_revealed(time) {
+            return Visibility::none(self.size);
+        }
+        match team {
+            Some(team) => self.fog.for_state(&self.teams[team].state),
+            None => self.fog.for_state(&BoardState::with_size(self.size)),
+        }
+    }
+
+    /// The goals of the visible cells that aren't already shown, row by row.
+    fn visible_goals(&self, visible: &Visibility, shown: &Visibility) -> Vec<Option<String>> {
+        self.goals
+            .iter()
+            .zip(visible.cells.iter().zip(&shown.cells))
+            .map(|(goal, (&visible, &shown))| {
+                if visible && !shown {
+                    Some(goal.clone())
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// The whole room as the player can see it at the time. The player
+    /// doesn't have to be in a team.
+    pub fn state(&self, player: PlayerId, time: u64) -> Update {
+        let team = self.player(player).map(|p| p.team);
+        let visible = self.team_visibility(team, time);
+        let goals = if self.is_revealed(time) {
+            Some(self.visible_goals(&visible, &Visibility::none(self.size)))
+        } else {
+            None
+        };
+        Update::State {
+            size: self.size,
+            goals: goals,
+            starts_at: self.starts_at,
+            teams: self.teams.clone(),
+            players: self.players.clone(),
+        }
+    }
+
+    /// Applies the player's command.
+    pub fn handle(
+        &mut self,
+        player: PlayerId,
+        command: Command,
+        time: u64,
+    ) -> Result<Update, RoomError> {
+        match command {
+            Command::Join { name, team } => self.join(player, &name, &team),
+            Command::Start { countdown } => self.start(player, time.saturating_add(countdown)),
+            Command::Mark { x, y } => self.mark(player, x, y, time),
+            Command::Unmark { x, y } => self.unmark(player, x, y, time),
+        }
+    }
+
+    pub fn join(&mut self, player: PlayerId, name: &str, team: &str) -> Result<Update, RoomError> {
+        let (name, team_name) = (name.trim(), team.trim());
+        if name.is_empty() || team_name.is_empty() {
+            return Err(RoomError::EmptyName);
+        }
+        if self.player(player).is_some() {
+            return Err(RoomError::AlreadyJoined);
+        }
+
+        let team = match self.teams.iter().position(|t| t.name == team_name) {
+            Some(team) => team,
+            None => {
+                self.teams.push(Team {
+                    name: team_name.to_owned(),
+                    state: BoardState::with_size(self.size),
+                });
+                self.shown.push(Visibility::none(self.size));
+                self.teams.len() - 1
+            }
+        };
+        let player = Player {
+            id: player,
+            name: name.to_owned(),
+            team: team,
+        };
+        self.players.push(player.clone());
+        Ok(Update::Joined {
+            player: player,
+            team_name: team_name.to_owned(),
+        })
+    }
+
+    /// Removes the player from the room. The team and its marks are kept, so
+    /// the player can join it again. Returns `None` if the player never
+    /// joined.
+    pub fn leave(&mut self, player: PlayerId) -> Option<Update> {
+        let i = self.players.iter().position(|p| p.id == player)?;
+        self.players.remove(i);
+        Some(Update::Left { player: player })
+    }
+
+    /// Starts the countdown, which ends at the time.
+    pub fn start(&mut self, player: PlayerId, at: u64) -> Result<Update, RoomError> {
+        self.team(player)?;
+        if self.starts_at.is_some() {
+            return Err(RoomError::AlreadyStarted);
+        }
+        self.starts_at = Some(at);
+        Ok(Update::Starting { at: at })
+    }
+
+    /// Reveals the goals of the cells that became visible, either because
+    /// the countdown ended or because a team's marks revealed them. This
+    /// needs to be called regularly, as there is no command that ends the
+    /// countdown, and after every command. Returns the updates together with
+    /// the team they are for, where `None` stands for the players without a
+    /// team. Every goal is only sent once to each team.
+    pub fn tick(&mut self, time: u64) -> Vec<(Option<usize>, Update)> {
+        let mut updates = Vec::new();
+        let teams = (0..self.teams.len()).map(Some);
+        for team in Some(None).into_iter().chain(teams) {
+            let visible = self.team_visibility(team, time);
+            let shown = match team {
+                Some(team) => &self.shown[team],
+                None => &self.shown_to_spectators,
+            };
+            let goals = self.visible_goals(&visible, shown);
+            if goals.iter().all(Option::is_none) {
+                continue;
+            }
+
+            let shown = match team {
+                Some(team) => &mut self.shown[team],
+                None => &mut self.shown_to_spectators,
+            };
+            for (shown, &visible) in shown.cells.iter_mut().zip(&visible.cells) {
+                *shown |= visible;
+            }
+            updates.push((team, Update::Revealed { goals: goals }));
+        }
+        updates
+    }
+
+    pub fn mark(
+        &mut self,
+        player: PlayerId,
+        x: usize,
+        y: usize,
+        time: u64,
+    ) -> Result<Update, RoomError> {
+        let team = self.check(player, x, y, time)?;
+        if !self.fog.for_state(&self.teams[team].state).is_visible(x, y) {
+            return Err(RoomError::HiddenCell { x: x, y: y });
+        }
+        if self.teams[team].state.mark(x, y, time) != Some(true) {
+            return Err(RoomError::AlreadyMarked);
+        }
+        Ok(Update::Marked {
+            team: team,
+            x: x,
+            y: y,
+            time: time,
+        })
+    }
+
+    pub fn unmark(
+        &mut self,
+        player: PlayerId,
+        x: usize,
+        y: usize,
+        time: u64,
+    ) -> Result<Update, RoomError> {
+        let team = self.check(player, x, y, time)?;
+        if self.teams[team].state.unmark(x, y) != Some(true) {
+            return Err(RoomError::NotMarked);
+        }
+        Ok(Update::Unmarked {
+            team: team,
+            x: x,
+            y: y,
+        })
+    }
+
+    fn team(&self, player: PlayerId) -> Result<usize, RoomError> {
+        self.player(player)
+            .map(|p| p.team)
+            .ok_or(RoomError::NotJoined)
+    }
+
+    /// Makes sure the player can change the cell and returns the player's
+    /// team.
+    fn check(&self, player: PlayerId, x: usize, y: usize, time: u64) -> Result<usize, RoomError> {
+        let team = self.team(player)?;
+        if !self.is_revealed(time) {
+            return Err(RoomError::Hidden);
+        }
+        if x >= self.size || y >= self.size {
+            return Err(RoomError::InvalidCell { x: x, y: y });
+        }
+        Ok(team)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+    use {Mode, Template};
+
+    fn room() -> Room {
+        room_with_fog(Fog::Clear)
+    }
+
+    fn room_with_fog(fog: Fog) -> Room {
+        let template = Template::from_json_str(include_str!("templates/sm64.json")).unwrap();
+        let board = template.generate(587062, Mode::Normal);
+        Room::with_fog(&board, fog)
+    }
+
+    #[test]
+    fn teams() {
+        let mut room = room();
+        assert_eq!(room.join(1, " ", "Red"), Err(RoomError::EmptyName));
+        assert_eq!(
+            room.join(1, "Alice", " Red "),
+            Ok(Update::Joined {
+                player: Player {
+                    id: 1,
+                    name: "Alice".into(),
+                    team: 0,
+                },
+                team_name: "Red".into(),
+            })
+        );
+        assert_eq!(room.join(1, "Alice", "Blue"), Err(RoomError::AlreadyJoined));
+        room.join(2, "Bob", "Blue").unwrap();
+        room.join(3, "Carol", "Red").unwrap();
+        assert_eq!(room.teams().len(), 2);
+        assert_eq!(room.player(3).unwrap().team, 0);
+
+        assert_eq!(room.leave(1), Some(Update::Left { player: 1 }));
+        assert_eq!(room.leave(1), None);
+        assert_eq!(room.players().len(), 2);
+        assert_eq!(room.teams().len(), 2);
+    }
+
+    #[test]
+    fn reveal_on_start() {
+        let mut room = room();
+        assert_eq!(room.start(1, 100), Err(RoomError::NotJoined));
+        room.join(1, "Alice", "Red").unwrap();
+        match room.state(1, 0) {
+            Update::State { size, goals, .. } => {
+                assert_eq!(size, 5);
+                assert_eq!(goals, None);
+            }
+            update => panic!("{:?}", update),
+        }
+        assert_eq!(room.tick(1000), []);
+
+        assert_eq!(
+            room.handle(1, Command::Start { countdown: 10 }, 1000),
+            Ok(Update::Starting { at: 1010 })
+        );
+        assert_eq!(room.start(1, 2000), Err(RoomError::AlreadyStarted));
+        assert_eq!(room.mark(1, 0, 0, 1009), Err(RoomError::Hidden));
+        assert_eq!(room.goals(1009), None);
+        assert_eq!(room.tick(1009), []);
+
+        // Without fog, the players with and without a team see every goal.
+        let goals = room
+            .goals(1010)
+            .unwrap()
+            .iter()
+            .cloned()
+            .map(Some)
+            .collect::<Vec<_>>();
+        assert_eq!(goals.len(), 25);
+        let revealed = Update::Revealed {
+            goals: goals.clone(),
+        };
+        assert_eq!(
+            room.tick(1010),
+            [(None, revealed.clone()), (Some(0), revealed)]
+        );
+        assert_eq!(room.tick(1020), []);
+        for &player in &[1, 2] {
+            match room.state(player, 1020) {
+                Update::State { goals: g, .. } => assert_eq!(g, Some(goals.clone())),
+                update => panic!("{:?}", update),
+            }
+        }
+    }
+
+    #[test]
+    fn marking() {
+        let mut room = room();
+        room.join(1, "Alice", "Red").unwrap();
+        room.join(2, "Bob", "Blue").unwrap();
+        room.start(1, 0).unwrap();
+
+        assert_eq!(
+            room.handle(1, Command::Mark { x: 1, y: 2 }, 10),
+            Ok(Update::Marked {
+                team: 0,
+                x: 1,
+                y: 2,
+                time: 10,
+            })
+        );
+        assert_eq!(room.mark(1, 1, 2, 20), Err(RoomError::AlreadyMarked));
+        assert_eq!(room.mark(2, 1, 2, 30).map(|_| ()), Ok(()));
+        assert_eq!(room.mark(3, 1, 2, 30), Err(RoomError::NotJoined));
+        assert_eq!(
+            room.mark(1, 5, 0, 30),
+            Err(RoomError::InvalidCell { x: 5, y: 0 })
+        );
+        assert_eq!(room.teams()[0].state.marked_at(1, 2), Some(10));
+
+        assert_eq!(
+            room.handle(1, Command::Unmark { x: 1, y: 2 }, 40),
+            Ok(Update::Unmarked {
+                team: 0,
+                x: 1,
+                y: 2,
+            })
+        );
+        assert_eq!(room.unmark(1, 1, 2, 50), Err(RoomError::NotMarked));
+        assert!(room.teams()[1].state.is_marked(1, 2));
+    }
+
+    #[test]
+    fn visibility() {
+        let mut room = room();
+        room.join(1, "Alice", "Red").unwrap();
+        room.join(2, "Bob", "Blue").unwrap();
+        assert_eq!(room.visibility(3, 0), Err(RoomError::NotJoined));
+        assert_eq!(room.visibility(1, 0), Ok(Visibility::none(5)));
+        room.start(1, 100).unwrap();
+        assert_eq!(room.visibility(1, 100), Ok(Visibility::all(5)));
+
+        let mut room = room_with_fog(Fog::RowByRow);
+        room.join(1, "Alice", "Red").unwrap();
+        room.join(2, "Bob", "Blue").unwrap();
+        room.start(1, 100).unwrap();
+        room.mark(1, 2, 0, 110).unwrap();
+        let red = room.visibility(1, 120).unwrap();
+        let blue = room.visibility(2, 120).unwrap();
+        assert_eq!(red.visible_count(), 10);
+        assert_eq!(blue.visible_count(), 5);
+    }
+
+    #[test]
+    fn fog() {
+        let mut room = room_with_fog(Fog::exploration(5));
+        assert_eq!(room.fog(), Fog::Exploration { x: 2, y: 2 });
+        room.join(1, "Alice", "Red").unwrap();
+        room.start(1, 100).unwrap();
+        assert_eq!(
+            room.mark(1, 0, 0, 110),
+            Err(RoomError::HiddenCell { x: 0, y: 0 })
+        );
+        assert_eq!(
+            room.mark(1, 2, 1, 110),
+            Err(RoomError::HiddenCell { x: 2, y: 1 })
+        );
+        assert!(room.mark(1, 2, 2, 110).is_ok());
+        assert!(room.mark(1, 2, 1, 120).is_ok());
+        assert!(room.mark(1, 2, 0, 130).is_ok());
+        assert_eq!(room.visibility(1, 140).unwrap().visible_count(), 10);
+    }
+
+    /// The indices of the goals that are sent.
+    fn sent(goals: &[Option<String>]) -> Vec<usize> {
+        (0..goals.len()).filter(|&i| goals[i].is_some()).collect()
+    }
+
+    #[test]
+    fn hidden_goals() {
+        let mut room = room_with_fog(Fog::exploration(5));
+        room.join(1, "Alice", "Red").unwrap();
+        room.join(2, "Bob", "Blue").unwrap();
+        room.start(1, 100).unwrap();
+        let all = room.goals(100).unwrap().to_vec();
+
+        // Everyone only gets the goal of the starting cell.
+        let updates = room.tick(100);
+        assert_eq!(updates.len(), 3);
+        for (_, update) in &updates {
+            match *update {
+                Update::Revealed { ref goals } => assert_eq!(sent(goals), [12]),
+                ref update => panic!("{:?}", update),
+            }
+        }
+
+        // Only the team that marked the cell gets the goals it revealed.
+        room.mark(1, 2, 2, 110).unwrap();
+        match &room.tick(110)[..] {
+            [(Some(0), Update::Revealed { goals })] => {
+                assert_eq!(sent(goals), [7, 11, 13, 17]);
+                assert_eq!(goals[7].as_ref(), Some(&all[7]));
+            }
+            updates => panic!("{:?}", updates),
+        }
+        assert_eq!(room.tick(120), []);
+
+        match room.state(1, 120) {
+            Update::State { goals, .. } => {
+                assert_eq!(sent(&goals.unwrap()), [7, 11, 12, 13, 17])
+            }
+            update => panic!("{:?}", update),
+        }
+        for &player in &[2, 3] {
+            match room.state(player, 120) {
+                Update::State { goals, .. } => assert_eq!(sent(&goals.unwrap()), [12]),
+                update => panic!("{:?}", update),
+            }
+        }
+    }
+
+    #[test]
+    fn to_json() {
+        let command = serde_json::from_str::<Command>(r#"{"Mark":{"x":1,"y":2}}"#).unwrap();
+        assert_eq!(command, Command::Mark { x: 1, y: 2 });
+        assert_eq!(
+            serde_json::to_string(&Update::Starting { at: 1010 }).unwrap(),
+            r#"{"Starting":{"at":1010}}"#
+        );
+    }
+}
@@ -0,0 +1,296 @@
+use difficulty::DifficultyMapping;
+use generator::{self, GenerateError};
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use {Constraints, Goal, Line, Template};
+
+/// How many seeds a thread checks before it looks for more work.
+const BLOCK_LEN: u64 = 1024;
+
+/// A condition the boards found by a search have to meet.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Criterion<'c> {
+    /// The board contains the goal.
+    Contains(&'c str),
+    /// The board doesn't contain the goal.
+    Excludes(&'c str),
+    /// The synergy of the goals in each of the board's lines is at most the
+    /// amount.
+    MaxLineSynergy(usize),
+    /// Each of the board's lines contains at most `max` goals of the type.
+    MaxTypePerLine { goal_type: &'c str, max: usize },
+    /// The board contains at least `min` goals of the type.
+    MinTypeCount { goal_type: &'c str, min: usize },
+    /// The board contains at most `max` goals of the type.
+    MaxTypeCount { goal_type: &'c str, max: usize },
+}
+
+impl<'c> Criterion<'c> {
+    /// Whether the goals of a board with the size, row by row, meet the
+    /// criterion. A board without exactly `size * size` goals never does.
+    pub fn matches(&self, size: usize, goals: &[&Goal]) -> bool {
+        if goals.len() != size * size {
+            return false;
+        }
+        let has_type = |goal: &Goal, goal_type: &str| goal.types.iter().any(|t| &**t == goal_type);
+        let count = |goal_type: &str| goals.iter().filter(|g| has_type(g, goal_type)).count();
+        match *self {
+            Criterion::Contains(name) => goals.iter().any(|g| &*g.name == name),
+            Criterion::Excludes(name) => goals.iter().all(|g| &*g.name != name),
+            Criterion::MaxLineSynergy(max) => {
+                Line::all(size).all(|line| generator::line_synergy(size, line, goals) <= max)
+            }
+            Criterion::MaxTypePerLine { goal_type, max } => Line::all(size).all(|line| {
+                line.cells(size)
+                    .filter(|&i| has_type(goals[i], goal_type))
+                    .count()
+                    <= max
+            }),
+            Criterion::MinTypeCount { goal_type, min } => count(goal_type) >= min,
+            Criterion::MaxTypeCount { goal_type, max } => count(goal_type) <= max,
+        }
+    }
+}
+
+/// Looks for the seeds whose boards meet all the criteria, like the seeds
+/// that get hand-picked for a tournament. The seeds are checked on multiple
+/// threads.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Search<'c> {
+    /// The width and height of the boards.
+    pub size: usize,
+    pub criteria: &'c [Criterion<'c>],
+    /// The most seeds that are returned. Only the lowest matching seeds are
+    /// returned if there are more.
+    pub limit: Option<usize>,
+    /// How many threads check the seeds. If this is 0, there is one thread
+    /// per core.
+    pub threads: usize,
+}
+
+impl<'c> Search<'c> {
+    /// Searches 5x5 boards without a limit, using all cores.
+    pub fn new(criteria: &'c [Criterion<'c>]) -> Self {
+        Search {
+            size: 5,
+            criteria: criteria,
+            limit: None,
+            threads: 0,
+        }
+    }
+
+    /// Whether the goals of a board meet all the criteria. A board of a
+    /// different size than the searched one never does.
+    pub fn matches(&self, goals: &[&Goal]) -> bool {
+        self.criteria.iter().all(|c| c.matches(self.size, goals))
+    }
+
+    /// Returns the seeds in the range whose boards meet all the criteria, in
+    /// ascending order.
+    pub fn run<M>(
+        &self,
+        template: &Template,
+        mode: M,
+        seeds: Range<u32>,
+    ) -> Result<Vec<u32>, GenerateError>
+    where
+        M: DifficultyMapping + Sync,
+    {
+        let threads = match self.threads {
+            0 => thread::available_parallelism().map_or(1, |n| n.get()),
+            threads => threads,
+        };
+        let limit = self.limit.unwrap_or(usize::MAX);
+
+        // The threads take the blocks of seeds in order, so once enough seeds
+        // are found, the blocks that nobody took yet only contain higher
+        // seeds and can be skipped.
+        let next = AtomicU64::new(u64::from(seeds.start));
+        let found = AtomicUsize::new(0);
+        let failed = AtomicBool::new(false);
+        let results = Mutex::new((Vec::new(), None::<(u32, GenerateError)>));
+        thread::scope(|scope| {
+            for _ in 0..threads {
+                scope.spawn(|| {
+                    let mut matches = Vec::new();
+                    let mut error = None;
+                    while found.load(Ordering::Relaxed) < limit && !failed.load(Ordering::Relaxed) {
+                        let start = next.fetch_add(BLOCK_LEN, Ordering::Relaxed);
+                        if start >= u64::from(seeds.end) {
+                            break;
+                        }
+                        let end = u64::from(seeds.end).min(start + BLOCK_LEN);
+                        for seed in start as u32..end as u32 {
+                            let goals = match generator::generate_goals(
+                                seed.into(),
+                                &mode,
+                                self.size,
+                                &Constraints::default(),
+                                template,
+                                &mut (),
+                            ) {
+                                Ok(goals) => goals,
+                                Err(e) => {
+                                    error = Some((seed, e));
+                                    failed.store(true, Ordering::Relaxed);
+                                    break;
+                                }
+                            };
+                            if self.matches(&goals) {
+                                matches.push(seed);
+                                found.fetch_add(1, Ordering::Relaxed);
+                            }
+                        }
+                    }
+                    let mut results = results.lock().unwrap();
+                    results.0.extend(matches);
+                    // The error of the lowest seed is returned, so it doesn't
+                    // depend on the order the threads ran in.
+                    results.1 = match (results.1, error) {
+                        (Some(first), Some(error)) if error.0 < first.0 => Some(error),
+                        (None, error) => error,
+                        (first, _) => first,
+                    };
+                });
+            }
+        });
+
+        let (mut matches, error) = results.into_inner().unwrap();
+        if let Some((_, e)) = error {
+            return Err(e);
+        }
+        matches.sort_unstable();
+        matches.truncate(limit);
+        Ok(matches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Mode;
+
+    fn sm64() -> Template {
+        Template::from_json_str(include_str!("templates/sm64.json")).unwrap()
+    }
+
+    /// The seeds a search on a single thread without any shortcuts finds.
+    fn expected(template: &Template, criteria: &[Criterion], seeds: Range<u32>) -> Vec<u32> {
+        seeds
+            .filter(|&seed| {
+                let board = template.generate(seed, Mode::Normal);
+                let goals = board
+                    .cells
+                    .iter()
+                    .map(|name| {
+                        template
+                            .0
+                            .iter()
+                            .flat_map(|tier| tier.iter())
+                            .find(|g| &*g.name == *name)
+                            .unwrap()
+                    })
+                    .collect::<Vec<_>>();
+                criteria.iter().all(|c| c.matches(5, &goals))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn criteria() {
+        let template = Template::from_json_str(
+            r#"[[
+                { "name": "A", "types": ["WF", "coins"] },
+                { "name": "B", "types": ["coins"] },
+                { "name": "C", "types": ["WF"] }
+            ]]"#,
+        )
+        .unwrap();
+        let goals = &template.0[0];
+        let board = [&goals[0], &goals[1], &goals[2], &goals[0]];
+        let matches = |criterion: Criterion| criterion.matches(2, &board);
+        assert!(matches(Criterion::Contains("B")));
+        assert!(!matches(Criterion::Contains("D")));
+        assert!(matches(Criterion::Excludes("D")));
+        assert!(!matches(Criterion::Excludes("C")));
+        assert!(matches(Criterion::MaxLineSynergy(4)));
+        assert!(!matches(Criterion::MaxLineSynergy(3)));
+        assert!(matches(Criterion::MaxTypePerLine {
+            goal_type: "WF",
+            max: 2,
+        }));
+        assert!(!matches(Criterion::MaxTypePerLine {
+            goal_type: "WF",
+            max: 1,
+        }));
+        assert!(matches(Criterion::MinTypeCount {
+            goal_type: "coins",
+            min: 3,
+        }));
+        assert!(!matches(Criterion::MaxTypeCount {
+            goal_type: "coins",
+            max: 2,
+        }));
+
+        assert!(!Criterion::Contains("B").matches(3, &board));
+        assert!(!Criterion::MaxTypePerLine {
+            goal_type: "WF",
+            max: 2,
+        }
+        .matches(3, &board));
+        let criteria = [Criterion::Contains("B")];
+        let mut search = Search::new(&criteria);
+        search.size = 2;
+        assert!(search.matches(&board));
+        assert!(!search.matches(&board[..3]));
+    }
+
+    #[test]
+    fn search() {
+        let template = sm64();
+        let criteria = [
+            Criterion::Contains("Open 9 Cannons"),
+            Criterion::MaxTypePerLine {
+                goal_type: "RR",
+                max: 1,
+            },
+        ];
+        let seeds = 0..3000;
+        let all = expected(&template, &criteria, seeds.clone());
+        assert!(all.len() > 3);
+
+        let mut search = Search::new(&criteria);
+        for &threads in &[1, 4] {
+            search.threads = threads;
+            search.limit = None;
+            assert_eq!(
+                search.run(&template, Mode::Normal, seeds.clone()),
+                Ok(all.clone())
+            );
+            search.limit = Some(3);
+            assert_eq!(
+                search.run(&template, Mode::Normal, seeds.clone()),
+                Ok(all[..3].to_vec())
+            );
+        }
+        assert_eq!(search.run(&template, Mode::Normal, 5..5), Ok(Vec::new()));
+        let end = u32::MAX - 10..u32::MAX;
+        assert_eq!(
+            search.run(&template, Mode::Normal, end.clone()),
+            Ok(expected(&template, &criteria, end))
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let mut template = sm64();
+        template.0.truncate(20);
+        let search = Search::new(&[]);
+        assert_eq!(
+            search.run(&template, Mode::Normal, 0..100).err(),
+            template.try_generate(0, Mode::Normal).err()
+        );
+    }
+}
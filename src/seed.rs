@@ -0,0 +1,114 @@
+use arrayvec::ArrayString;
+use core::fmt::{self, Write};
+use SeedRandom;
+
+/// The seed of a board, which is either a number or an arbitrary string like
+/// the seeds some bingo sites pass as a URL parameter.
+///
+/// The random number generator is always seeded with the textual form of the
+/// seed, so a number seed uses its decimal representation. The digits for the
+/// magic square are taken from the number itself for number seeds. For string
+/// seeds they are taken from the 32-bit FNV-1a hash of the string's UTF-8
+/// bytes instead. A string that is exactly the decimal representation of a
+/// number (no sign, no leading zeros, at most `u32::MAX`) is the same seed as
+/// that number.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Seed<'a> {
+    Number(u32),
+    Text(&'a str),
+}
+
+impl<'a> Seed<'a> {
+    /// The number that the digits of the magic square are derived from.
+    pub fn magic_number(&self) -> u32 {
+        match *self {
+            Seed::Number(number) => number,
+            Seed::Text(text) => parse_number(text).unwrap_or_else(|| fnv1a(text.as_bytes())),
+        }
+    }
+
+    /// Creates the random number generator that picks the goals.
+    pub fn random(&self) -> SeedRandom {
+        match *self {
+            Seed::Number(number) => {
+                let mut text = ArrayString::<[_; 10]>::new();
+                write!(text, "{}", number).unwrap();
+                SeedRandom::new(&text)
+            }
+            Seed::Text(text) => SeedRandom::new(text),
+        }
+    }
+}
+
+impl<'a> From<u32> for Seed<'a> {
+    fn from(number: u32) -> Self {
+        Seed::Number(number)
+    }
+}
+
+impl<'a> From<&'a str> for Seed<'a> {
+    fn from(text: &'a str) -> Self {
+        match parse_number(text) {
+            Some(number) => Seed::Number(number),
+            None => Seed::Text(text),
+        }
+    }
+}
+
+impl<'a> fmt::Display for Seed<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Seed::Number(number) => fmt::Display::fmt(&number, f),
+            Seed::Text(text) => fmt::Display::fmt(text, f),
+        }
+    }
+}
+
+/// Parses the text as a number, as long as it's written exactly like the
+/// number's decimal representation.
+fn parse_number(text: &str) -> Option<u32> {
+    if text.starts_with('0') && text != "0" {
+        return None;
+    }
+    if !text.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok()
+}
+
+fn fnv1a(bytes: &[u8]) -> u32 {
+    let mut hash = 0x811c9dc5u32;
+    for &b in bytes {
+        hash ^= b as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers() {
+        assert_eq!(Seed::from("587062"), Seed::Number(587062));
+        assert_eq!(Seed::from("0"), Seed::Number(0));
+        assert_eq!(Seed::from("4294967295"), Seed::Number(4294967295));
+        assert_eq!(Seed::from("4294967296"), Seed::Text("4294967296"));
+        assert_eq!(Seed::from("007"), Seed::Text("007"));
+        assert_eq!(Seed::from("+7"), Seed::Text("+7"));
+        assert_eq!(Seed::from(""), Seed::Text(""));
+        assert_eq!(Seed::Text("587062").magic_number(), 587062);
+    }
+
+    #[test]
+    fn text() {
+        assert_eq!(Seed::from("hello").magic_number(), 0x4f9f2cab);
+        assert_eq!(Seed::from("Wörd").magic_number(), 179775773);
+        assert_eq!(Seed::from("hello.").random().next_f64(), 0.9282578795792454);
+        assert_eq!(
+            Seed::from(587062).random().next_f64(),
+            SeedRandom::new("587062").next_f64()
+        );
+    }
+}
@@ -1,117 +1,104 @@
-use arrayvec::ArrayVec;
-use super::low_bits;
-
+/// An ARC4 implementation with a width of 256, which keeps its entire state
+/// in bytes. The output matches the floating point implementation of
+/// seedrandom.js exactly.
 #[derive(Debug)]
 pub struct Arc4 {
-    i: f64,
-    j: f64,
-    width: usize,
-    s: ArrayVec<[f64; 256]>,
+    i: u8,
+    j: u8,
+    s: [u8; 256],
 }
 
 impl Arc4 {
-    /// An ARC4 implementation. The constructor takes a key in the form of
-    /// an array of at most (width) integers that should be 0 <= x < (width).
-    pub fn new(mut key: ArrayVec<[f64; 10]>, width: usize) -> Self {
-        let mut s = ArrayVec::<[_; 256]>::new();
-        let mut j = 0.0;
+    /// The constructor takes a key in the form of an array of at most 256
+    /// bytes.
+    pub fn new(key: &[u8]) -> Self {
+        let mut s = [0; 256];
+        let mut j = 0u8;
 
         // The empty key [] is treated as [0].
-        if key.is_empty() {
-            key.push(0.0);
-        }
+        let key = if key.is_empty() { &[0][..] } else { key };
 
         // Set up S using the standard key scheduling algorithm.
-        s.extend((0..width).map(|i| i as f64));
-        for i in 0..width {
-            let i = i as f64;
-            let t = s[i as usize];
-            j = low_bits(j + t + key[i as usize % key.len()], width);
-            let u = s[j as usize];
-            s[i as usize] = u;
+        for (i, s) in s.iter_mut().enumerate() {
+            *s = i as u8;
+        }
+        for i in 0..256 {
+            let t = s[i];
+            j = j.wrapping_add(t).wrapping_add(key[i % key.len()]);
+            s[i] = s[j as usize];
             s[j as usize] = t;
         }
 
-        let mut me = Arc4 {
-            i: 0.0,
-            j: 0.0,
-            width: width,
-            s: s,
-        };
+        let mut me = Arc4 { i: 0, j: 0, s: s };
 
         // For robust unpredictability discard an initial batch of values.
         // See http://www.rsa.com/rsalabs/node.asp?id=2009
-        me.generate(width);
+        for _ in 0..256 {
+            me.next_byte();
+        }
 
         me
     }
 
-    /// The generate(count) method returns a pseudorandom integer that concatenates
-    /// the next (count) outputs from ARC4.  Its return value is a number x
-    /// that is in the range 0 <= x < (width ^ count).
-    pub fn generate(&mut self, count: usize) -> f64 {
-        let width = self.width;
-
-        let mut i = low_bits(self.i + 1.0, width);
-        let mut t = self.s[i as usize];
-        let mut j = low_bits(self.j + t, width);
-        let mut u = self.s[j as usize];
-        self.s[i as usize] = u;
-        self.s[j as usize] = t;
-        let mut r = self.s[low_bits(t + u, width) as usize];
+    fn next_byte(&mut self) -> u8 {
+        self.i = self.i.wrapping_add(1);
+        let t = self.s[self.i as usize];
+        self.j = self.j.wrapping_add(t);
+        let u = self.s[self.j as usize];
+        self.s[self.i as usize] = u;
+        self.s[self.j as usize] = t;
+        self.s[t.wrapping_add(u) as usize]
+    }
 
-        for _ in 1..count {
-            i = low_bits(i + 1.0, width);
-            t = self.s[i as usize];
-            j = low_bits(j + t, width);
-            u = self.s[j as usize];
-            self.s[i as usize] = u;
-            self.s[j as usize] = t;
-            r = r * width as f64 + self.s[low_bits(t + u, width) as usize];
+    /// Returns a pseudorandom integer that concatenates the next (count)
+    /// outputs from ARC4, so it is in the range 0 <= x < 256 ^ count. At most
+    /// 8 outputs can be concatenated.
+    pub fn generate(&mut self, count: usize) -> u64 {
+        debug_assert!(count <= 8);
+        let mut r = 0;
+        for _ in 0..count {
+            r = r << 8 | self.next_byte() as u64;
         }
-
-        self.i = i;
-        self.j = j;
-
         r
     }
 }
 
-#[test]
-fn test() {
-    let arc4 = Arc4::new(
-        [57.0, 48.0, 49.0, 54.0, 50.0, 51.0]
-            .iter()
-            .cloned()
-            .collect(),
-        256,
-    );
-    assert_eq!(
-        &arc4.s,
-        &[
-            250.0, 175.0, 217.0, 198.0, 168.0, 238.0, 130.0, 229.0, 71.0, 241.0, 225.0, 255.0,
-            206.0, 98.0, 13.0, 179.0, 74.0, 149.0, 143.0, 81.0, 139.0, 224.0, 62.0, 254.0, 107.0,
-            39.0, 97.0, 135.0, 210.0, 32.0, 22.0, 14.0, 248.0, 21.0, 239.0, 142.0, 104.0, 153.0,
-            63.0, 146.0, 126.0, 128.0, 54.0, 59.0, 35.0, 159.0, 243.0, 83.0, 144.0, 23.0, 44.0,
-            110.0, 174.0, 69.0, 29.0, 184.0, 176.0, 158.0, 47.0, 53.0, 90.0, 82.0, 77.0, 154.0,
-            228.0, 96.0, 150.0, 165.0, 191.0, 193.0, 246.0, 185.0, 131.0, 37.0, 132.0, 189.0, 18.0,
-            94.0, 127.0, 67.0, 252.0, 109.0, 207.0, 95.0, 43.0, 186.0, 78.0, 202.0, 45.0, 220.0,
-            115.0, 169.0, 218.0, 27.0, 232.0, 155.0, 88.0, 25.0, 61.0, 51.0, 24.0, 253.0, 1.0,
-            177.0, 162.0, 26.0, 10.0, 221.0, 65.0, 233.0, 197.0, 160.0, 211.0, 148.0, 19.0, 234.0,
-            157.0, 227.0, 70.0, 151.0, 214.0, 216.0, 15.0, 7.0, 201.0, 12.0, 108.0, 0.0, 73.0,
-            36.0, 145.0, 208.0, 124.0, 101.0, 34.0, 125.0, 163.0, 240.0, 68.0, 41.0, 230.0, 9.0,
-            87.0, 156.0, 209.0, 161.0, 56.0, 170.0, 212.0, 133.0, 84.0, 121.0, 48.0, 5.0, 66.0,
-            89.0, 192.0, 181.0, 93.0, 231.0, 190.0, 55.0, 100.0, 236.0, 178.0, 105.0, 122.0, 247.0,
-            242.0, 111.0, 3.0, 50.0, 30.0, 134.0, 245.0, 123.0, 113.0, 147.0, 152.0, 136.0, 180.0,
-            183.0, 85.0, 120.0, 164.0, 118.0, 52.0, 140.0, 119.0, 114.0, 141.0, 106.0, 86.0, 28.0,
-            219.0, 187.0, 172.0, 215.0, 188.0, 203.0, 200.0, 235.0, 244.0, 103.0, 38.0, 112.0,
-            33.0, 205.0, 75.0, 72.0, 204.0, 76.0, 92.0, 129.0, 116.0, 58.0, 226.0, 237.0, 173.0,
-            8.0, 60.0, 137.0, 42.0, 223.0, 40.0, 195.0, 31.0, 49.0, 167.0, 196.0, 194.0, 251.0,
-            2.0, 4.0, 11.0, 79.0, 171.0, 213.0, 182.0, 80.0, 102.0, 222.0, 64.0, 166.0, 117.0, 6.0,
-            57.0, 249.0, 16.0, 46.0, 199.0, 138.0, 99.0, 20.0, 91.0, 17.0,
-        ][..]
-    );
-    assert_eq!(arc4.i, 0.0);
-    assert_eq!(arc4.j, 139.0);
-    assert_eq!(arc4.width, 256);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::float::FloatArc4;
+
+    #[test]
+    fn state() {
+        let arc4 = Arc4::new(&[57, 48, 49, 54, 50, 51]);
+        let expected: &[u8] = &[
+            250, 175, 217, 198, 168, 238, 130, 229, 71, 241, 225, 255, 206, 98, 13, 179, 74, 149,
+            143, 81, 139, 224, 62, 254, 107, 39, 97, 135, 210, 32, 22, 14, 248, 21, 239, 142, 104,
+            153, 63, 146, 126, 128, 54, 59, 35, 159, 243, 83, 144, 23, 44, 110, 174, 69, 29, 184,
+            176, 158, 47, 53, 90, 82, 77, 154, 228, 96, 150, 165, 191, 193, 246, 185, 131, 37, 132,
+            189, 18, 94, 127, 67, 252, 109, 207, 95, 43, 186, 78, 202, 45, 220, 115, 169, 218, 27,
+            232, 155, 88, 25, 61, 51, 24, 253, 1, 177, 162, 26, 10, 221, 65, 233, 197, 160, 211,
+            148, 19, 234, 157, 227, 70, 151, 214, 216, 15, 7, 201, 12, 108, 0, 73, 36, 145, 208,
+            124, 101, 34, 125, 163, 240, 68, 41, 230, 9, 87, 156, 209, 161, 56, 170, 212, 133, 84,
+            121, 48, 5, 66, 89, 192, 181, 93, 231, 190, 55, 100, 236, 178, 105, 122, 247, 242, 111,
+            3, 50, 30, 134, 245, 123, 113, 147, 152, 136, 180, 183, 85, 120, 164, 118, 52, 140,
+            119, 114, 141, 106, 86, 28, 219, 187, 172, 215, 188, 203, 200, 235, 244, 103, 38, 112,
+            33, 205, 75, 72, 204, 76, 92, 129, 116, 58, 226, 237, 173, 8, 60, 137, 42, 223, 40,
+            195, 31, 49, 167, 196, 194, 251, 2, 4, 11, 79, 171, 213, 182, 80, 102, 222, 64, 166,
+            117, 6, 57, 249, 16, 46, 199, 138, 99, 20, 91, 17,
+        ];
+        assert_eq!(&arc4.s[..], expected);
+        assert_eq!(arc4.i, 0);
+        assert_eq!(arc4.j, 139);
+    }
+
+    #[test]
+    fn float_state() {
+        let key = [57, 48, 49, 54, 50, 51];
+        let mut arc4 = Arc4::new(&key);
+        let mut float = FloatArc4::new(&key, 256);
+        for count in (1..7).cycle().take(100) {
+            assert_eq!(arc4.generate(count) as f64, float.generate(count));
+        }
+    }
 }
@@ -0,0 +1,139 @@
+//! The reference implementation of seedrandom.js, which does all the
+//! calculations with floating point numbers, just like JavaScript. It is only
+//! kept around to verify and benchmark the integer implementation.
+
+use arrayvec::ArrayVec;
+use super::{mix_key, CHUNKS, WIDTH};
+
+#[cfg(not(feature = "std"))]
+trait Float {
+    fn powi(&self, exp: usize) -> Self;
+}
+
+#[cfg(not(feature = "std"))]
+impl Float for f64 {
+    fn powi(&self, mut n: usize) -> Self {
+        let mut x = *self;
+        if n == 0 {
+            return 1.0;
+        }
+        let mut y = 1.0;
+        while n > 1 {
+            if n & 1 != 0 {
+                y *= x;
+            }
+            x *= x;
+            n >>= 1;
+        }
+        x * y
+    }
+}
+
+fn low_bits(n: f64, width: usize) -> f64 {
+    (n as i64 & (width as i64 - 1)) as f64
+}
+
+#[derive(Debug)]
+pub struct FloatArc4 {
+    i: f64,
+    j: f64,
+    width: usize,
+    s: ArrayVec<[f64; 256]>,
+}
+
+impl FloatArc4 {
+    /// An ARC4 implementation. The constructor takes a key in the form of
+    /// an array of at most (width) integers that should be 0 <= x < (width).
+    pub fn new(key: &[u8], width: usize) -> Self {
+        let mut s = ArrayVec::<[_; 256]>::new();
+        let mut j = 0.0;
+
+        // The empty key [] is treated as [0].
+        let key = if key.is_empty() { &[0][..] } else { key };
+
+        // Set up S using the standard key scheduling algorithm.
+        s.extend((0..width).map(|i| i as f64));
+        for i in 0..width {
+            let i = i as f64;
+            let t = s[i as usize];
+            j = low_bits(j + t + key[i as usize % key.len()] as f64, width);
+            let u = s[j as usize];
+            s[i as usize] = u;
+            s[j as usize] = t;
+        }
+
+        let mut me = FloatArc4 {
+            i: 0.0,
+            j: 0.0,
+            width: width,
+            s: s,
+        };
+
+        // For robust unpredictability discard an initial batch of values.
+        // See http://www.rsa.com/rsalabs/node.asp?id=2009
+        me.generate(width);
+
+        me
+    }
+
+    /// The generate(count) method returns a pseudorandom integer that concatenates
+    /// the next (count) outputs from ARC4.  Its return value is a number x
+    /// that is in the range 0 <= x < (width ^ count).
+    pub fn generate(&mut self, count: usize) -> f64 {
+        let width = self.width;
+
+        let mut i = low_bits(self.i + 1.0, width);
+        let mut t = self.s[i as usize];
+        let mut j = low_bits(self.j + t, width);
+        let mut u = self.s[j as usize];
+        self.s[i as usize] = u;
+        self.s[j as usize] = t;
+        let mut r = self.s[low_bits(t + u, width) as usize];
+
+        for _ in 1..count {
+            i = low_bits(i + 1.0, width);
+            t = self.s[i as usize];
+            j = low_bits(j + t, width);
+            u = self.s[j as usize];
+            self.s[i as usize] = u;
+            self.s[j as usize] = t;
+            r = r * width as f64 + self.s[low_bits(t + u, width) as usize];
+        }
+
+        self.i = i;
+        self.j = j;
+
+        r
+    }
+}
+
+/// The floating point version of `SeedRandom`.
+#[derive(Debug)]
+pub struct FloatSeedRandom(FloatArc4);
+
+impl FloatSeedRandom {
+    pub fn new(seed: &str) -> Self {
+        FloatSeedRandom(FloatArc4::new(&mix_key(seed.encode_utf16()), WIDTH))
+    }
+
+    pub fn next_f64(&mut self) -> f64 {
+        let start_denom = (WIDTH as f64).powi(CHUNKS as _);
+        let significance = 2.0f64.powi(52);
+        let overflow = 2.0 * significance;
+
+        let mut n = self.0.generate(CHUNKS);
+        let mut d = start_denom;
+        let mut x = 0.0;
+        while n < significance {
+            n = (n + x) * WIDTH as f64;
+            d *= WIDTH as f64;
+            x = self.0.generate(1);
+        }
+        while n >= overflow {
+            n /= 2.0;
+            d /= 2.0;
+            x = (((x as i64) as u64 >> 1) as i64) as f64;
+        }
+        (n + x) / d
+    }
+}
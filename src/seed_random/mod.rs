@@ -1,68 +1,184 @@
 use arrayvec::ArrayVec;
 
-#[cfg(not(feature = "std"))]
-trait Float {
-    fn powi(&self, exp: usize) -> Self;
-}
-
-#[cfg(not(feature = "std"))]
-impl Float for f64 {
-    fn powi(&self, mut n: usize) -> Self {
-        let mut x = *self;
-        if n == 0 {
-            return 1.0;
-        }
-        let mut y = 1.0;
-        while n > 1 {
-            if n & 1 != 0 {
-                y *= x;
-            }
-            x *= x;
-            n >>= 1;
-        }
-        x * y
-    }
-}
-
 mod arc4;
+#[cfg(any(test, feature = "bench"))]
+mod float;
 
 use self::arc4::Arc4;
+#[cfg(any(test, feature = "bench"))]
+pub use self::float::FloatSeedRandom;
 
+/// A port of David Bau's seedrandom.js, which produces exactly the same
+/// sequence of numbers for the same seed.
+#[derive(Debug)]
 pub struct SeedRandom(Arc4);
 
 const WIDTH: usize = 256;
 const CHUNKS: usize = 6;
+const MASK: u32 = WIDTH as u32 - 1;
+const SIGNIFICANCE: u64 = 1 << 52;
+const OVERFLOW: u64 = 2 * SIGNIFICANCE;
+
+/// Mixes the characters of a seed into a key the same way seedrandom.js does.
+fn mix_key<I>(seed: I) -> ArrayVec<[u8; WIDTH]>
+where
+    I: IntoIterator<Item = u16>,
+{
+    let mut key = ArrayVec::<[u8; WIDTH]>::new();
+    let mut smear = 0;
+    for (j, c) in seed.into_iter().enumerate() {
+        let index = j & MASK as usize;
+        // JavaScript reads the key slots that aren't filled in yet as
+        // undefined, which doesn't change the smear.
+        if let Some(&k) = key.get(index) {
+            smear ^= k as u32 * 19;
+        }
+        let k = (MASK & (smear + c as u32)) as u8;
+        if index < key.len() {
+            key[index] = k;
+        } else {
+            key.push(k);
+        }
+    }
+    key
+}
 
 impl SeedRandom {
-    pub fn new(seed: ArrayVec<[u8; 10]>) -> Self {
-        let seed = seed.into_iter().map(|b| b as f64).collect();
+    /// Creates a generator that behaves like `Math.seedrandom(seed)` in
+    /// JavaScript.
+    pub fn new(seed: &str) -> Self {
+        Self::from_key(&mix_key(seed.encode_utf16()))
+    }
 
-        // Use the seed to initialize an ARC4 generator.
-        SeedRandom(Arc4::new(seed, WIDTH))
+    /// Creates a generator seeded by a string where every byte is a single
+    /// character, like the strings JavaScript's `String.fromCharCode` creates
+    /// from the bytes.
+    pub fn from_bytes(seed: &[u8]) -> Self {
+        Self::from_key(&mix_key(seed.iter().map(|&b| b as u16)))
     }
 
-    pub fn next(&mut self) -> f64 {
-        let start_denom = (WIDTH as f64).powi(CHUNKS as _);
-        let significance = 2.0f64.powi(52);
-        let overflow = 2.0 * significance;
+    fn from_key(key: &[u8]) -> Self {
+        // Use the seed to initialize an ARC4 generator.
+        SeedRandom(Arc4::new(key))
+    }
 
+    /// The next number between 0 (inclusive) and 1 (exclusive), just like
+    /// `Math.random()` in JavaScript.
+    pub fn next_f64(&mut self) -> f64 {
+        // This follows seedrandom.js, but keeps the numerator as an integer
+        // and the denominator as an exponent of 2. JavaScript's floating
+        // point numbers represent all the intermediate values exactly, so
+        // only the final addition can round, which the conversion to f64
+        // does in the exact same way.
         let mut n = self.0.generate(CHUNKS);
-        let mut d = start_denom;
-        let mut x = 0.0;
-        while n < significance {
-            n = (n + x) * WIDTH as f64;
-            d *= WIDTH as f64;
+        let mut d = 8 * CHUNKS as u64;
+        let mut x = 0;
+        while n < SIGNIFICANCE {
+            n = (n + x) * WIDTH as u64;
+            d += 8;
             x = self.0.generate(1);
         }
-        while n >= overflow {
-            n /= 2.0;
-            d /= 2.0;
-            x = (((x as i64) as u64 >> 1) as i64) as f64;
+        while n >= OVERFLOW {
+            n /= 2;
+            d -= 1;
+            x >>= 1;
+        }
+        (n + x) as f64 * f64::from_bits((1023 - d) << 52)
+    }
+
+    /// The next integer between 0 (inclusive) and `range` (exclusive),
+    /// calculated as `Math.floor(Math.random() * range)`.
+    pub fn next_int(&mut self, range: u32) -> u32 {
+        (self.next_f64() * range as f64) as u32
+    }
+
+    /// Shuffles the slice with the Fisher-Yates shuffle, walking from the
+    /// back of the slice to the front, like most JavaScript implementations.
+    pub fn shuffle<T>(&mut self, slice: &mut [T]) {
+        for i in (1..slice.len()).rev() {
+            let j = self.next_int(i as u32 + 1) as usize;
+            slice.swap(i, j);
+        }
+    }
+
+    /// Picks a random element of the slice, calculated as
+    /// `slice[Math.floor(Math.random() * slice.length)]`.
+    pub fn choose<'a, T>(&mut self, slice: &'a [T]) -> Option<&'a T> {
+        if slice.is_empty() {
+            None
+        } else {
+            Some(&slice[self.next_int(slice.len() as u32) as usize])
         }
-        (n + x) / d
     }
 }
 
-fn low_bits(n: f64, width: usize) -> f64 {
-    (n as i64 & (width as i64 - 1)) as f64
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sequence(random: &mut SeedRandom) -> [f64; 2] {
+        [random.next_f64(), random.next_f64()]
+    }
+
+    #[test]
+    fn known_outputs() {
+        // The example from the seedrandom.js documentation.
+        assert_eq!(SeedRandom::new("hello.").next_f64(), 0.9282578795792454);
+
+        assert_eq!(
+            sequence(&mut SeedRandom::new("587062")),
+            [0.7174430703109935, 0.7115574275370463]
+        );
+        assert_eq!(
+            sequence(&mut SeedRandom::new("")),
+            [0.23144008215179881, 0.27404636548159655]
+        );
+        assert_eq!(
+            sequence(&mut SeedRandom::new("Grüße, 世界 🎲")),
+            [0.6644908610699545, 0.027749935799755023]
+        );
+    }
+
+    #[test]
+    fn long_seed() {
+        // Seeds longer than 256 characters are smeared into the key.
+        let seed = (0..600u16).map(|i| 33 + i * 7 % 90).collect::<Vec<_>>();
+        let seed = String::from_utf16(&seed).unwrap();
+        assert_eq!(
+            sequence(&mut SeedRandom::new(&seed)),
+            [0.05516240379273158, 0.620853817689074]
+        );
+        assert_eq!(
+            sequence(&mut SeedRandom::from_bytes(seed.as_bytes())),
+            [0.05516240379273158, 0.620853817689074]
+        );
+    }
+
+    #[test]
+    fn float_implementation() {
+        let seeds = ["", "hello.", "587062", "Grüße, 世界 🎲", "4294967295"];
+        for seed in &seeds {
+            let mut random = SeedRandom::new(seed);
+            let mut float = FloatSeedRandom::new(seed);
+            for _ in 0..10000 {
+                assert_eq!(random.next_f64().to_bits(), float.next_f64().to_bits());
+            }
+        }
+    }
+
+    #[test]
+    fn helpers() {
+        let mut random = SeedRandom::new("ints");
+        let ints = (0..8).map(|_| random.next_int(100)).collect::<Vec<_>>();
+        assert_eq!(ints, [73, 91, 61, 80, 56, 86, 54, 66]);
+
+        let mut random = SeedRandom::new("shuffle");
+        let mut list = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9];
+        random.shuffle(&mut list);
+        assert_eq!(list, [8, 9, 0, 7, 1, 4, 5, 3, 6, 2]);
+
+        let mut random = SeedRandom::new("ints");
+        assert_eq!(random.choose(&[0; 0]), None);
+        assert_eq!(random.choose(&(0..100).collect::<Vec<_>>()), Some(&73));
+    }
 }
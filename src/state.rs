@@ -0,0 +1,246 @@
+use arrayvec::ArrayVec;
+use bingo::{self, MAX_CELLS};
+use serde::de::{Deserialize, Deserializer};
+use Bingo;
+
+/// A row, column or diagonal of a board.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Line {
+    /// The row with the index, counting from the top.
+    Row(usize),
+    /// The column with the index, counting from the left.
+    Column(usize),
+    /// The diagonal from the top left to the bottom right.
+    TopLeftToBottomRight,
+    /// The diagonal from the bottom left to the top right.
+    BottomLeftToTopRight,
+}
+
+impl Line {
+    /// All the lines of a board with the size, rows first, then columns, then
+    /// both diagonals.
+    pub fn all(size: usize) -> impl Iterator<Item = Line> {
+        (0..size)
+            .map(Line::Row)
+            .chain((0..size).map(Line::Column))
+            .chain(Some(Line::TopLeftToBottomRight))
+            .chain(Some(Line::BottomLeftToTopRight))
+    }
+
+    /// The indices of the line's cells on a board with the size.
+    pub fn cells(self, size: usize) -> impl Iterator<Item = usize> {
+        (0..size).map(move |n| match self {
+            Line::Row(y) => y * size + n,
+            Line::Column(x) => n * size + x,
+            Line::TopLeftToBottomRight => n * size + n,
+            Line::BottomLeftToTopRight => (size - 1 - n) * size + n,
+        })
+    }
+}
+
+/// Tracks which cells of a board are marked as completed and when. The
+/// timestamps are whatever the caller passes in, like milliseconds since the
+/// start of the race, so the state works without a clock.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BoardState {
+    /// The width and height of the board.
+    pub size: usize,
+    /// The time each cell got marked at, row by row.
+    pub marks: ArrayVec<[Option<u64>; MAX_CELLS]>,
+}
+
+impl BoardState {
+    /// Creates the state of a board without any marked cells.
+    pub fn new(bingo: &Bingo) -> Self {
+        Self::with_size(bingo.size)
+    }
+
+    /// Creates the state of a board with the size without any marked cells.
+    /// Panics if the size isn't between 1 and `MAX_SIZE`.
+    pub fn with_size(size: usize) -> Self {
+        bingo::check_size(size);
+        BoardState {
+            size: size,
+            marks: (0..size * size).map(|_| None).collect(),
+        }
+    }
+
+    /// Marks the cell as completed at the time. Returns whether the cell
+    /// wasn't marked before, in which case the original time is kept, or
+    /// `None` if the cell isn't on the board.
+    pub fn mark(&mut self, x: usize, y: usize, time: u64) -> Option<bool> {
+        let mark = &mut self.marks[bingo::cell_index(self.size, x, y)?];
+        if mark.is_some() {
+            return Some(false);
+        }
+        *mark = Some(time);
+        Some(true)
+    }
+
+    /// Removes the mark of the cell. Returns whether the cell was marked, or
+    /// `None` if the cell isn't on the board.
+    pub fn unmark(&mut self, x: usize, y: usize) -> Option<bool> {
+        let i = bingo::cell_index(self.size, x, y)?;
+        Some(self.marks[i].take().is_some())
+    }
+
+    pub fn is_marked(&self, x: usize, y: usize) -> bool {
+        self.marked_at(x, y).is_some()
+    }
+
+    /// The time the cell got marked at. Cells that aren't on the board are
+    /// never marked.
+    pub fn marked_at(&self, x: usize, y: usize) -> Option<u64> {
+        bingo::cell_index(self.size, x, y).and_then(|i| self.marks[i])
+    }
+
+    pub fn marked_count(&self) -> usize {
+        self.marks.iter().filter(|m| m.is_some()).count()
+    }
+
+    pub fn is_complete(&self, line: Line) -> bool {
+        line.cells(self.size).all(|i| self.marks[i].is_some())
+    }
+
+    /// The time the line got completed at, which is the time its last cell
+    /// got marked at.
+    pub fn completed_at(&self, line: Line) -> Option<u64> {
+        line.cells(self.size)
+            .map(|i| self.marks[i])
+            .try_fold(0, |max, mark| mark.map(|mark| max.max(mark)))
+    }
+
+    /// All the completed rows, columns and diagonals.
+    pub fn completed_lines<'s>(&'s self) -> impl Iterator<Item = Line> + 's {
+        Line::all(self.size).filter(move |&line| self.is_complete(line))
+    }
+
+    /// The amount of completed lines.
+    pub fn bingos(&self) -> usize {
+        self.completed_lines().count()
+    }
+
+    /// Whether every cell of the board is marked.
+    pub fn is_blackout(&self) -> bool {
+        self.marks.iter().all(|m| m.is_some())
+    }
+}
+
+impl<'de> Deserialize<'de> for BoardState {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename = "BoardState")]
+        struct Fields {
+            size: usize,
+            marks: ArrayVec<[Option<u64>; MAX_CELLS]>,
+        }
+
+        let fields = Fields::deserialize(deserializer)?;
+        bingo::check_cells(fields.size, fields.marks.len())?;
+        Ok(BoardState {
+            size: fields.size,
+            marks: fields.marks,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn lines() {
+        assert_eq!(Line::all(5).count(), 12);
+        assert_eq!(Line::Row(1).cells(3).collect::<Vec<_>>(), [3, 4, 5]);
+        assert_eq!(Line::Column(1).cells(3).collect::<Vec<_>>(), [1, 4, 7]);
+        assert_eq!(
+            Line::TopLeftToBottomRight.cells(3).collect::<Vec<_>>(),
+            [0, 4, 8]
+        );
+        assert_eq!(
+            Line::BottomLeftToTopRight.cells(3).collect::<Vec<_>>(),
+            [6, 4, 2]
+        );
+    }
+
+    #[test]
+    fn marking() {
+        let mut state = BoardState::with_size(5);
+        assert_eq!(state.mark(0, 4, 10), Some(true));
+        assert_eq!(state.mark(0, 4, 20), Some(false));
+        assert_eq!(state.marked_at(0, 4), Some(10));
+        assert_eq!(state.unmark(0, 4), Some(true));
+        assert_eq!(state.unmark(0, 4), Some(false));
+        assert!(!state.is_marked(0, 4));
+        assert_eq!(state.marked_count(), 0);
+
+        // Cells outside of the board don't alias cells of the next row.
+        assert_eq!(state.mark(5, 0, 30), None);
+        assert_eq!(state.mark(0, 5, 30), None);
+        assert_eq!(state.unmark(5, 0), None);
+        assert!(!state.is_marked(0, 1));
+        assert_eq!(state.marked_at(5, 0), None);
+        assert_eq!(state.marked_count(), 0);
+    }
+
+    #[test]
+    fn bingos() {
+        let mut state = BoardState::with_size(5);
+        for n in 0..5 {
+            state.mark(4 - n, n, 100 + n as u64);
+        }
+        assert_eq!(
+            state.completed_lines().collect::<Vec<_>>(),
+            [Line::BottomLeftToTopRight]
+        );
+        assert_eq!(state.completed_at(Line::BottomLeftToTopRight), Some(104));
+        assert_eq!(state.completed_at(Line::Row(0)), None);
+
+        for x in 0..5 {
+            state.mark(x, 2, 200);
+        }
+        for y in 0..5 {
+            state.mark(0, y, 300);
+        }
+        assert_eq!(
+            state.completed_lines().collect::<Vec<_>>(),
+            [Line::Row(2), Line::Column(0), Line::BottomLeftToTopRight]
+        );
+        assert_eq!(state.bingos(), 3);
+        assert!(!state.is_blackout());
+
+        for y in 0..5 {
+            for x in 0..5 {
+                state.mark(x, y, 400);
+            }
+        }
+        assert_eq!(state.bingos(), 12);
+        assert!(state.is_blackout());
+    }
+
+    #[test]
+    fn to_json() {
+        let mut state = BoardState::with_size(2);
+        state.mark(1, 0, 1234);
+        let json = serde_json::to_string(&state).unwrap();
+        assert_eq!(json, r#"{"size":2,"marks":[null,1234,null,null]}"#);
+        assert_eq!(serde_json::from_str::<BoardState>(&json).unwrap(), state);
+
+        assert!(serde_json::from_str::<BoardState>(r#"{"size":2,"marks":[null]}"#).is_err());
+        assert!(serde_json::from_str::<BoardState>(r#"{"size":11,"marks":[]}"#).is_err());
+        assert!(serde_json::from_str::<BoardState>(r#"{"size":0,"marks":[]}"#).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "The size 0 is not between 1 and 10")]
+    fn empty() {
+        BoardState::with_size(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "The size 11 is not between 1 and 10")]
+    fn too_large() {
+        BoardState::with_size(11);
+    }
+}
@@ -4,33 +4,249 @@ use arrayvec::{ArrayString, ArrayVec};
 use serde_json::{de, ser, Result as DeResult, Result as SerResult};
 #[cfg(feature = "json_core")]
 use serde_json::de::{self, Result as DeResult};
-use {generator, Bingo, Mode};
+#[cfg(feature = "std")]
+use core::fmt;
+#[cfg(feature = "std")]
+use serde::de::{Error, MapAccess, SeqAccess, Visitor};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use {generator, Bingo, Constraints, Curve, DifficultyMapping, GenerateError, Seed};
+#[cfg(feature = "std")]
+use Trace;
+use validation::{self, Diagnostic};
+#[cfg(feature = "std")]
+use validation::Report;
 
+/// The maximum amount of difficulty tiers a template can have without `std`.
+pub const MAX_TIERS: usize = 32;
+/// The maximum amount of goals per difficulty tier without `std`.
+pub const MAX_GOALS_PER_TIER: usize = 10;
+/// The maximum length of a goal's name in bytes without `std`.
+pub const MAX_NAME_LEN: usize = 256;
+/// The maximum length of a goal's icon in bytes without `std`.
+pub const MAX_ICON_LEN: usize = 16;
+/// The maximum amount of types a goal can have without `std`.
+pub const MAX_TYPES: usize = 8;
+/// The maximum length of a type's name in bytes without `std`.
+pub const MAX_TYPE_LEN: usize = 32;
+
+/// The goals by difficulty tier, along with the difficulty curve the
+/// template's boards are meant to be generated with, if it comes with one.
+/// Templates without a curve are stored as the list of tiers, while the others
+/// are stored as an object with the `curve` and the `tiers`.
 #[cfg(feature = "std")]
-#[derive(Deserialize, Serialize)]
-pub struct Template(pub Vec<Vec<Goal>>);
+#[derive(Debug, Clone)]
+pub struct Template(pub Vec<Vec<Goal>>, pub Option<Curve>);
 
 #[cfg(feature = "std")]
-#[derive(Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Goal {
     pub name: String,
     pub types: Vec<String>,
+    /// Special mode only draws goals that are marked as special, unless a
+    /// difficulty tier doesn't contain any.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub special: bool,
+    /// A short symbol, like `★`, that is shown as a badge in the corner of
+    /// the goal's cell instead of being part of its name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
 }
 
+/// The goals by difficulty tier, along with the difficulty curve the
+/// template's boards are meant to be generated with, if it comes with one.
+/// Without `std`, only templates without a curve can be loaded.
 #[cfg(not(feature = "std"))]
-#[derive(Deserialize, Serialize)]
-pub struct Template(pub ArrayVec<[ArrayVec<[Goal; 10]>; 32]>);
+#[derive(Debug, Clone)]
+pub struct Template(
+    pub ArrayVec<[ArrayVec<[Goal; MAX_GOALS_PER_TIER]>; MAX_TIERS]>,
+    pub Option<Curve>,
+);
 
 #[cfg(not(feature = "std"))]
-#[derive(Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Goal {
-    pub name: ArrayString<[u8; 256]>,
-    pub types: ArrayVec<[ArrayString<[u8; 32]>; 8]>,
+    pub name: ArrayString<[u8; MAX_NAME_LEN]>,
+    pub types: ArrayVec<[ArrayString<[u8; MAX_TYPE_LEN]>; MAX_TYPES]>,
+    /// Special mode only draws goals that are marked as special, unless a
+    /// difficulty tier doesn't contain any.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub special: bool,
+    /// A short symbol, like `★`, that is shown as a badge in the corner of
+    /// the goal's cell instead of being part of its name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<ArrayString<[u8; MAX_ICON_LEN]>>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+impl Serialize for Template {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.1 {
+            None => self.0.serialize(serializer),
+            Some(ref curve) => {
+                let mut state = serializer.serialize_struct("Template", 2)?;
+                state.serialize_field("curve", curve)?;
+                state.serialize_field("tiers", &self.0)?;
+                state.end()
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'de> Deserialize<'de> for Template {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct TemplateVisitor;
+
+        impl<'de> Visitor<'de> for TemplateVisitor {
+            type Value = Template;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a list of difficulty tiers or an object with the curve and the tiers")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Template, A::Error> {
+                let mut tiers = Vec::new();
+                while let Some(tier) = seq.next_element()? {
+                    tiers.push(tier);
+                }
+                Ok(Template(tiers, None))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Template, A::Error> {
+                let (mut curve, mut tiers) = (None, None);
+                while let Some(key) = map.next_key::<String>()? {
+                    match &*key {
+                        "curve" if curve.is_some() => {
+                            return Err(A::Error::duplicate_field("curve"))
+                        }
+                        "tiers" if tiers.is_some() => {
+                            return Err(A::Error::duplicate_field("tiers"))
+                        }
+                        "curve" => curve = Some(map.next_value()?),
+                        "tiers" => tiers = Some(map.next_value()?),
+                        _ => return Err(A::Error::unknown_field(&key, &["curve", "tiers"])),
+                    }
+                }
+                let tiers = tiers.ok_or_else(|| A::Error::missing_field("tiers"))?;
+                Ok(Template(tiers, curve.unwrap_or(None)))
+            }
+        }
+
+        deserializer.deserialize_any(TemplateVisitor)
+    }
+}
+
+/// Formats like JSON Core can only deserialize values of a known type, so
+/// without `std` templates are always a list of tiers.
+#[cfg(not(feature = "std"))]
+impl<'de> Deserialize<'de> for Template {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Deserialize::deserialize(deserializer).map(|tiers| Template(tiers, None))
+    }
 }
 
 impl Template {
-    pub fn generate(&self, seed: u32, mode: Mode) -> Bingo {
-        generator::generate(seed, mode, self)
+    pub fn generate<'s, S, M>(&self, seed: S, mode: M) -> Bingo<'_>
+    where
+        S: Into<Seed<'s>>,
+        M: DifficultyMapping,
+    {
+        Bingo::new(seed, mode, self)
+    }
+
+    pub fn generate_sized<'s, S, M>(&self, seed: S, mode: M, size: usize) -> Bingo<'_>
+    where
+        S: Into<Seed<'s>>,
+        M: DifficultyMapping,
+    {
+        Bingo::new_sized(seed, mode, size, self)
+    }
+
+    pub fn try_generate<'s, S, M>(&self, seed: S, mode: M) -> Result<Bingo<'_>, GenerateError>
+    where
+        S: Into<Seed<'s>>,
+        M: DifficultyMapping,
+    {
+        Bingo::try_new(seed, mode, self)
+    }
+
+    pub fn try_generate_sized<'s, S, M>(
+        &self,
+        seed: S,
+        mode: M,
+        size: usize,
+    ) -> Result<Bingo<'_>, GenerateError>
+    where
+        S: Into<Seed<'s>>,
+        M: DifficultyMapping,
+    {
+        Bingo::try_new_sized(seed, mode, size, self)
+    }
+
+    /// Generates a board that respects the constraints. Goals that are banned
+    /// or would exceed the maximum amount per type are skipped like goals with
+    /// too much synergy, while the required goals get placed before any other
+    /// goal gets picked.
+    pub fn generate_constrained<'s, S, M>(
+        &self,
+        seed: S,
+        mode: M,
+        size: usize,
+        constraints: &Constraints,
+    ) -> Result<Bingo<'_>, GenerateError>
+    where
+        S: Into<Seed<'s>>,
+        M: DifficultyMapping,
+    {
+        generator::generate_observed(seed, mode, size, constraints, self, &mut ())
+    }
+
+    /// Generates a board and records every decision the generator made along
+    /// the way.
+    #[cfg(feature = "std")]
+    pub fn generate_with_trace<'s, S, M>(
+        &self,
+        seed: S,
+        mode: M,
+        size: usize,
+    ) -> Result<(Bingo<'_>, Trace<'_>), GenerateError>
+    where
+        S: Into<Seed<'s>>,
+        M: DifficultyMapping,
+    {
+        let seed = seed.into();
+        let mut trace = Trace::new(seed, size);
+        let bingo = generator::generate_observed(
+            seed,
+            mode,
+            size,
+            &Constraints::default(),
+            self,
+            &mut trace,
+        )?;
+        Ok((bingo, trace))
+    }
+
+    /// Checks the template for problems that either break generation or
+    /// result in worse boards, without stopping at the first one found.
+    #[cfg(feature = "std")]
+    pub fn validate(&self) -> Report {
+        let mut diagnostics = Vec::new();
+        self.validate_with(|d| diagnostics.push(d));
+        Report { diagnostics }
+    }
+
+    /// Checks the template the same way as `validate`, but hands every
+    /// diagnostic to the callback instead of collecting them.
+    pub fn validate_with<'a, F>(&'a self, on_diagnostic: F)
+    where
+        F: FnMut(Diagnostic<'a>),
+    {
+        validation::validate(self, on_diagnostic)
     }
 
     #[cfg(any(feature = "std", feature = "json_core"))]
@@ -55,14 +271,15 @@ mod tests {
 
     #[test]
     fn to_json() {
-        let template = Template(vec![
-            vec![
-                Goal {
-                    name: "Red Coin Star in WF".into(),
-                    types: vec!["WF".into()],
-                },
-            ],
-        ]);
+        let template = Template(
+            vec![vec![Goal {
+                name: "Red Coin Star in WF".into(),
+                types: vec!["WF".into()],
+                special: false,
+                icon: None,
+            }]],
+            None,
+        );
 
         assert_eq!(
             r#"[[{"name":"Red Coin Star in WF","types":["WF"]}]]"#,
@@ -70,9 +287,59 @@ mod tests {
         );
     }
 
+    #[test]
+    fn special_to_json() {
+        let template = Template(
+            vec![vec![Goal {
+                name: "Red Coin Star in WF".into(),
+                types: vec!["WF".into()],
+                special: true,
+                icon: None,
+            }]],
+            None,
+        );
+
+        assert_eq!(
+            r#"[[{"name":"Red Coin Star in WF","types":["WF"],"special":true}]]"#,
+            template.to_json_string().unwrap()
+        );
+    }
+
+    #[test]
+    fn icon_to_json() {
+        let json = r#"[[{"name":"Beat the King in BOB","types":["BOB"],"icon":"★"}]]"#;
+        let template = Template::from_json_str(json).unwrap();
+        assert_eq!(template.0[0][0].icon.as_deref(), Some("★"));
+        assert_eq!(template.to_json_string().unwrap(), json);
+    }
+
     #[test]
     fn from_json() {
         let sm64 = include_str!("templates/sm64.json");
-        Template::from_json_str(sm64).unwrap();
+        let template = Template::from_json_str(sm64).unwrap();
+        assert_eq!(template.1, None);
+    }
+
+    #[test]
+    fn curve_json() {
+        let tiers = r#"[[{"name":"A","types":["a"]}],[{"name":"B","types":["b"]}]]"#;
+        let curve = "[0,0,0,0,0,0,0,0,0,0,0,0,0,1,1,1,1,1,1,1,1,1,1,1,1]";
+        let json = format!(r#"{{"curve":{},"tiers":{}}}"#, curve, tiers);
+        let template = Template::from_json_str(&json).unwrap();
+        assert_eq!(template.1, Some(Curve::linear(0, 1)));
+        assert_eq!(template.0[1][0].name, "B");
+        assert_eq!(template.to_json_string().unwrap(), json);
+        let board = template
+            .try_generate_sized(0, template.1.unwrap(), 1)
+            .unwrap();
+        assert_eq!(board.cell(0, 0), Some("A"));
+
+        let template = Template::from_json_str(&format!(r#"{{"tiers":{}}}"#, tiers)).unwrap();
+        assert_eq!(template.1, None);
+        assert_eq!(template.to_json_string().unwrap(), tiers);
+
+        assert!(Template::from_json_str(&format!(r#"{{"curve":{}}}"#, curve)).is_err());
+        assert!(Template::from_json_str(r#"{"tiers":[],"tiers":[]}"#).is_err());
+        assert!(Template::from_json_str(r#"{"tiers":[],"goals":[]}"#).is_err());
     }
 }
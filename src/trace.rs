@@ -0,0 +1,150 @@
+use generator::Observer;
+use {Goal, Seed};
+
+/// A record of every decision the generator made while generating a board,
+/// which explains why each goal ended up in its cell.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Trace<'a> {
+    pub seed: String,
+    pub size: usize,
+    pub cells: Vec<CellTrace<'a>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CellTrace<'a> {
+    /// The index of the cell, counting row by row.
+    pub index: usize,
+    /// The cell's value in the magic square, scaled to the 25 difficulty
+    /// levels.
+    pub level: usize,
+    /// The difficulty tier the goal is drawn from.
+    pub tier: usize,
+    /// The random position in the tier at which the generator started
+    /// looking for a goal.
+    pub offset: usize,
+    /// Every goal the generator tried, in order. The generator stops as soon
+    /// as it finds a goal without any synergy.
+    pub candidates: Vec<Candidate<'a>>,
+    /// The index of the candidate that got picked, which is the first one
+    /// with the lowest synergy.
+    pub chosen: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Candidate<'a> {
+    pub name: &'a str,
+    /// The synergy with the goals already placed on the cell's row, column
+    /// and diagonals.
+    pub synergy: usize,
+}
+
+impl<'a> Trace<'a> {
+    pub fn new(seed: Seed, size: usize) -> Self {
+        Trace {
+            seed: seed.to_string(),
+            size: size,
+            cells: Vec::new(),
+        }
+    }
+
+    /// The goal that got picked for the cell.
+    pub fn chosen(&self, index: usize) -> &Candidate<'a> {
+        let cell = &self.cells[index];
+        &cell.candidates[cell.chosen]
+    }
+}
+
+impl<'a> Observer<'a> for Trace<'a> {
+    fn cell(&mut self, index: usize, level: usize, tier: usize, offset: usize) {
+        self.cells.push(CellTrace {
+            index: index,
+            level: level,
+            tier: tier,
+            offset: offset,
+            candidates: Vec::new(),
+            chosen: 0,
+        });
+    }
+
+    fn candidate(&mut self, goal: &'a Goal, synergy: usize) {
+        if let Some(cell) = self.cells.last_mut() {
+            cell.candidates.push(Candidate {
+                name: &goal.name,
+                synergy: synergy,
+            });
+        }
+    }
+
+    fn chosen(&mut self, candidate: usize) {
+        if let Some(cell) = self.cells.last_mut() {
+            cell.chosen = candidate;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+    use {Mode, Template};
+
+    #[test]
+    fn explains_board() {
+        let template = Template::from_json_str(include_str!("templates/sm64.json")).unwrap();
+        let (bingo, trace) = template
+            .generate_with_trace(587062, Mode::Normal, 5)
+            .unwrap();
+
+        assert_eq!(bingo, template.generate(587062, Mode::Normal));
+        assert_eq!(trace.seed, "587062");
+        assert_eq!(trace.cells.len(), 25);
+
+        for (i, cell) in trace.cells.iter().enumerate() {
+            assert_eq!(cell.index, i);
+            assert_eq!(trace.chosen(i).name, bingo.cells[i]);
+
+            let goals = &template.0[cell.tier];
+            assert!(cell.offset < goals.len());
+            assert_eq!(cell.candidates[0].name, goals[cell.offset].name);
+
+            let lowest = cell.candidates.iter().map(|c| c.synergy).min().unwrap();
+            assert_eq!(trace.chosen(i).synergy, lowest);
+
+            let last = cell.candidates.last().unwrap();
+            assert!(last.synergy == 0 || cell.candidates.len() == goals.len());
+        }
+
+        // The first cell doesn't have any neighbours yet.
+        assert_eq!(trace.cells[0].candidates.len(), 1);
+        assert_eq!(trace.cells[0].candidates[0].synergy, 0);
+    }
+
+    #[test]
+    fn special_offsets() {
+        let mut template = Template::from_json_str(include_str!("templates/sm64.json")).unwrap();
+        for tier in &mut template.0 {
+            for (i, goal) in tier.iter_mut().enumerate() {
+                goal.special = i % 3 == 2;
+            }
+        }
+        let (_, trace) = template
+            .generate_with_trace(587062, Mode::Special, 5)
+            .unwrap();
+
+        for cell in &trace.cells {
+            let goals = &template.0[cell.tier];
+            assert!(goals[cell.offset].special);
+            assert_eq!(cell.candidates[0].name, goals[cell.offset].name);
+        }
+    }
+
+    #[test]
+    fn to_json() {
+        let template = Template::from_json_str(include_str!("templates/sm64.json")).unwrap();
+        let (_, trace) = template
+            .generate_with_trace("mario", Mode::Short, 3)
+            .unwrap();
+        let json = serde_json::to_string(&trace).unwrap();
+        assert!(json.starts_with(r#"{"seed":"mario","size":3,"cells":[{"index":0,"level":"#));
+        assert!(json.contains(r#""candidates":[{"name":"#));
+    }
+}
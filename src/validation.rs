@@ -0,0 +1,516 @@
+use core::fmt;
+use difficulty::DifficultyMapping;
+use template::{
+    MAX_GOALS_PER_TIER, MAX_ICON_LEN, MAX_NAME_LEN, MAX_TIERS, MAX_TYPES, MAX_TYPE_LEN,
+};
+use {Goal, Mode, Template};
+
+static MODES: [Mode; 4] = [Mode::Short, Mode::Normal, Mode::Long, Mode::Special];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Severity {
+    /// Generating a board fails or the template can't be loaded everywhere.
+    Error,
+    /// Boards can be generated, but they likely aren't what the author intended.
+    Warning,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Diagnostic<'a> {
+    /// The template has fewer difficulty tiers than the mode needs.
+    TooFewTiers {
+        mode: Mode,
+        required: usize,
+        actual: usize,
+    },
+    /// The template has fewer difficulty tiers than its own curve needs.
+    CurveTooFewTiers { required: usize, actual: usize },
+    /// A difficulty tier doesn't contain any goals.
+    EmptyTier { tier: usize },
+    /// A goal with the same name already appeared earlier in the template.
+    DuplicateName {
+        name: &'a str,
+        tier: usize,
+        index: usize,
+        first_tier: usize,
+        first_index: usize,
+    },
+    /// A goal doesn't have any types, so it can't be balanced against the
+    /// other goals on its lines.
+    NoTypes { tier: usize, index: usize },
+    /// A goal's name starts or ends with whitespace.
+    UntrimmedName {
+        name: &'a str,
+        tier: usize,
+        index: usize,
+    },
+    /// A type is only used by a single goal, so it never contributes any
+    /// synergy.
+    SingleUseType {
+        ty: &'a str,
+        tier: usize,
+        index: usize,
+    },
+    /// The template has more difficulty tiers than fit without `std`.
+    TooManyTiers { count: usize, capacity: usize },
+    /// A difficulty tier has more goals than fit without `std`.
+    TooManyGoals {
+        tier: usize,
+        count: usize,
+        capacity: usize,
+    },
+    /// A goal's name is longer than fits without `std`.
+    NameTooLong {
+        tier: usize,
+        index: usize,
+        len: usize,
+        capacity: usize,
+    },
+    /// A goal has more types than fit without `std`.
+    TooManyTypes {
+        tier: usize,
+        index: usize,
+        count: usize,
+        capacity: usize,
+    },
+    /// A type's name is longer than fits without `std`.
+    TypeTooLong {
+        ty: &'a str,
+        tier: usize,
+        index: usize,
+        len: usize,
+        capacity: usize,
+    },
+    /// A goal's icon is longer than fits without `std`.
+    IconTooLong {
+        tier: usize,
+        index: usize,
+        len: usize,
+        capacity: usize,
+    },
+}
+
+impl<'a> Diagnostic<'a> {
+    pub fn severity(&self) -> Severity {
+        match *self {
+            Diagnostic::TooFewTiers { .. }
+            | Diagnostic::CurveTooFewTiers { .. }
+            | Diagnostic::EmptyTier { .. }
+            | Diagnostic::TooManyTiers { .. }
+            | Diagnostic::TooManyGoals { .. }
+            | Diagnostic::NameTooLong { .. }
+            | Diagnostic::TooManyTypes { .. }
+            | Diagnostic::TypeTooLong { .. }
+            | Diagnostic::IconTooLong { .. } => Severity::Error,
+            Diagnostic::DuplicateName { .. }
+            | Diagnostic::NoTypes { .. }
+            | Diagnostic::UntrimmedName { .. }
+            | Diagnostic::SingleUseType { .. } => Severity::Warning,
+        }
+    }
+}
+
+impl<'a> fmt::Display for Diagnostic<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Diagnostic::TooFewTiers {
+                mode,
+                required,
+                actual,
+            } => write!(
+                f,
+                "{:?} mode requires {} difficulty tiers, but the template only has {}",
+                mode, required, actual
+            ),
+            Diagnostic::CurveTooFewTiers { required, actual } => write!(
+                f,
+                "The template's curve requires {} difficulty tiers, but the template only has {}",
+                required, actual
+            ),
+            Diagnostic::EmptyTier { tier } => write!(f, "Tier {} doesn't contain any goals", tier),
+            Diagnostic::DuplicateName {
+                name,
+                tier,
+                index,
+                first_tier,
+                first_index,
+            } => write!(
+                f,
+                "Goal {} in tier {} is named {:?}, just like goal {} in tier {}",
+                index, tier, name, first_index, first_tier
+            ),
+            Diagnostic::NoTypes { tier, index } => {
+                write!(f, "Goal {} in tier {} doesn't have any types", index, tier)
+            }
+            Diagnostic::UntrimmedName { name, tier, index } => write!(
+                f,
+                "The name {:?} of goal {} in tier {} has leading or trailing whitespace",
+                name, index, tier
+            ),
+            Diagnostic::SingleUseType { ty, tier, index } => write!(
+                f,
+                "The type {:?} of goal {} in tier {} isn't used by any other goal",
+                ty, index, tier
+            ),
+            Diagnostic::TooManyTiers { count, capacity } => write!(
+                f,
+                "The template has {} difficulty tiers, but only {} are supported without std",
+                count, capacity
+            ),
+            Diagnostic::TooManyGoals {
+                tier,
+                count,
+                capacity,
+            } => write!(
+                f,
+                "Tier {} has {} goals, but only {} are supported without std",
+                tier, count, capacity
+            ),
+            Diagnostic::NameTooLong {
+                tier,
+                index,
+                len,
+                capacity,
+            } => write!(
+                f,
+                "The name of goal {} in tier {} is {} bytes long, but only {} are supported \
+                 without std",
+                index, tier, len, capacity
+            ),
+            Diagnostic::TooManyTypes {
+                tier,
+                index,
+                count,
+                capacity,
+            } => write!(
+                f,
+                "Goal {} in tier {} has {} types, but only {} are supported without std",
+                index, tier, count, capacity
+            ),
+            Diagnostic::TypeTooLong {
+                ty,
+                tier,
+                index,
+                len,
+                capacity,
+            } => write!(
+                f,
+                "The type {:?} of goal {} in tier {} is {} bytes long, but only {} are \
+                 supported without std",
+                ty, index, tier, len, capacity
+            ),
+            Diagnostic::IconTooLong {
+                tier,
+                index,
+                len,
+                capacity,
+            } => write!(
+                f,
+                "The icon of goal {} in tier {} is {} bytes long, but only {} are supported \
+                 without std",
+                index, tier, len, capacity
+            ),
+        }
+    }
+}
+
+/// All the diagnostics found for a template.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report<'a> {
+    pub diagnostics: Vec<Diagnostic<'a>>,
+}
+
+#[cfg(feature = "std")]
+impl<'a> Report<'a> {
+    /// Whether boards can be generated from the template in every mode and
+    /// it can be loaded without `std`.
+    pub fn is_valid(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    pub fn errors<'r>(&'r self) -> impl Iterator<Item = &'r Diagnostic<'a>> + 'r {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.severity() == Severity::Error)
+    }
+
+    pub fn warnings<'r>(&'r self) -> impl Iterator<Item = &'r Diagnostic<'a>> + 'r {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.severity() == Severity::Warning)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> fmt::Display for Report<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for diagnostic in &self.diagnostics {
+            let severity = match diagnostic.severity() {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            };
+            writeln!(f, "{}: {}", severity, diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+fn goals<'a>(template: &'a Template) -> impl Iterator<Item = (usize, usize, &'a Goal)> + 'a {
+    template.0.iter().enumerate().flat_map(|(tier, goals)| {
+        goals
+            .iter()
+            .enumerate()
+            .map(move |(index, goal)| (tier, index, goal))
+    })
+}
+
+pub fn validate<'a, F>(template: &'a Template, mut on_diagnostic: F)
+where
+    F: FnMut(Diagnostic<'a>),
+{
+    let tier_count = template.0.len();
+
+    if tier_count > MAX_TIERS {
+        on_diagnostic(Diagnostic::TooManyTiers {
+            count: tier_count,
+            capacity: MAX_TIERS,
+        });
+    }
+
+    for &mode in &MODES {
+        let required = mode.required_tiers();
+        if tier_count < required {
+            on_diagnostic(Diagnostic::TooFewTiers {
+                mode,
+                required,
+                actual: tier_count,
+            });
+        }
+    }
+
+    if let Some(ref curve) = template.1 {
+        let required = curve.required_tiers();
+        if tier_count < required {
+            on_diagnostic(Diagnostic::CurveTooFewTiers {
+                required,
+                actual: tier_count,
+            });
+        }
+    }
+
+    for (tier, goals) in template.0.iter().enumerate() {
+        if goals.is_empty() {
+            on_diagnostic(Diagnostic::EmptyTier { tier });
+        } else if goals.len() > MAX_GOALS_PER_TIER {
+            on_diagnostic(Diagnostic::TooManyGoals {
+                tier,
+                count: goals.len(),
+                capacity: MAX_GOALS_PER_TIER,
+            });
+        }
+    }
+
+    for (tier, index, goal) in goals(template) {
+        let name: &str = &goal.name;
+
+        if let Some((first_tier, first_index, _)) =
+            goals(template).find(|&(_, _, other)| &*other.name == name)
+        {
+            if (first_tier, first_index) != (tier, index) {
+                on_diagnostic(Diagnostic::DuplicateName {
+                    name,
+                    tier,
+                    index,
+                    first_tier,
+                    first_index,
+                });
+            }
+        }
+
+        if name.trim() != name {
+            on_diagnostic(Diagnostic::UntrimmedName { name, tier, index });
+        }
+
+        if name.len() > MAX_NAME_LEN {
+            on_diagnostic(Diagnostic::NameTooLong {
+                tier,
+                index,
+                len: name.len(),
+                capacity: MAX_NAME_LEN,
+            });
+        }
+
+        if let Some(ref icon) = goal.icon {
+            if icon.len() > MAX_ICON_LEN {
+                on_diagnostic(Diagnostic::IconTooLong {
+                    tier,
+                    index,
+                    len: icon.len(),
+                    capacity: MAX_ICON_LEN,
+                });
+            }
+        }
+
+        if goal.types.is_empty() {
+            on_diagnostic(Diagnostic::NoTypes { tier, index });
+        } else if goal.types.len() > MAX_TYPES {
+            on_diagnostic(Diagnostic::TooManyTypes {
+                tier,
+                index,
+                count: goal.types.len(),
+                capacity: MAX_TYPES,
+            });
+        }
+
+        for (type_index, ty) in goal.types.iter().enumerate() {
+            let ty: &str = ty;
+
+            if ty.len() > MAX_TYPE_LEN {
+                on_diagnostic(Diagnostic::TypeTooLong {
+                    ty,
+                    tier,
+                    index,
+                    len: ty.len(),
+                    capacity: MAX_TYPE_LEN,
+                });
+            }
+
+            // Only report a type once, even if the goal lists it multiple times.
+            if goal.types.iter().take(type_index).any(|t| &**t == ty) {
+                continue;
+            }
+
+            let users = goals(template)
+                .filter(|&(_, _, other)| other.types.iter().any(|t| &**t == ty))
+                .count();
+            if users == 1 {
+                on_diagnostic(Diagnostic::SingleUseType { ty, tier, index });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Curve;
+
+    fn goal(name: &str, types: &[&str]) -> Goal {
+        Goal {
+            name: name.into(),
+            types: types.iter().map(|&t| t.into()).collect(),
+            special: false,
+            icon: None,
+        }
+    }
+
+    #[test]
+    fn sm64() {
+        let template = Template::from_json_str(include_str!("templates/sm64.json")).unwrap();
+        let report = template.validate();
+        assert!(report.is_valid());
+        assert!(report.diagnostics.contains(&Diagnostic::UntrimmedName {
+            name: "Beat the King in BOB ",
+            tier: 1,
+            index: 1,
+        }));
+    }
+
+    #[test]
+    fn problems() {
+        let template = Template(
+            vec![
+                vec![goal("A", &["x", "y"]), goal("B", &["x"])],
+                vec![],
+                vec![goal("A", &[]), goal(" C", &["x"])],
+            ],
+            None,
+        );
+        assert_eq!(
+            template.validate().diagnostics,
+            vec![
+                Diagnostic::TooFewTiers {
+                    mode: Mode::Short,
+                    required: 13,
+                    actual: 3,
+                },
+                Diagnostic::TooFewTiers {
+                    mode: Mode::Normal,
+                    required: 25,
+                    actual: 3,
+                },
+                Diagnostic::TooFewTiers {
+                    mode: Mode::Long,
+                    required: 25,
+                    actual: 3,
+                },
+                Diagnostic::TooFewTiers {
+                    mode: Mode::Special,
+                    required: 25,
+                    actual: 3,
+                },
+                Diagnostic::EmptyTier { tier: 1 },
+                Diagnostic::SingleUseType {
+                    ty: "y",
+                    tier: 0,
+                    index: 0,
+                },
+                Diagnostic::DuplicateName {
+                    name: "A",
+                    tier: 2,
+                    index: 0,
+                    first_tier: 0,
+                    first_index: 0,
+                },
+                Diagnostic::NoTypes { tier: 2, index: 0 },
+                Diagnostic::UntrimmedName {
+                    name: " C",
+                    tier: 2,
+                    index: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn capacity() {
+        let long = "x".repeat(MAX_NAME_LEN + 1);
+        let mut template = Template(vec![vec![goal(&long, &["a", "a"]); 11]], None);
+        template.0[0][0].icon = Some("★".repeat(6));
+        let report = template.validate();
+        assert!(report.diagnostics.contains(&Diagnostic::TooManyGoals {
+            tier: 0,
+            count: 11,
+            capacity: MAX_GOALS_PER_TIER,
+        }));
+        assert!(report.diagnostics.contains(&Diagnostic::NameTooLong {
+            tier: 0,
+            index: 10,
+            len: MAX_NAME_LEN + 1,
+            capacity: MAX_NAME_LEN,
+        }));
+        assert!(report.diagnostics.contains(&Diagnostic::IconTooLong {
+            tier: 0,
+            index: 0,
+            len: 18,
+            capacity: MAX_ICON_LEN,
+        }));
+    }
+
+    #[test]
+    fn curve() {
+        let mut template = Template::from_json_str(include_str!("templates/sm64.json")).unwrap();
+        template.1 = Some(Curve::linear(5, 24));
+        assert!(template.validate().is_valid());
+
+        template.1 = Some(Curve::linear(5, 25));
+        assert!(template
+            .validate()
+            .diagnostics
+            .contains(&Diagnostic::CurveTooFewTiers {
+                required: 26,
+                actual: 25,
+            }));
+    }
+}
@@ -0,0 +1,295 @@
+use arrayvec::ArrayVec;
+use bingo::{self, cell_index, MAX_CELLS};
+use serde::de::{Deserialize, Deserializer};
+use {Bingo, BoardState, Lockout};
+
+/// Which cells of a board players can see, depending on the cells they
+/// completed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Fog {
+    /// Every cell can be seen.
+    Clear,
+    /// No cell can be seen, like before the race starts.
+    Hidden,
+    /// Exploration bingo, where only the starting cell can be seen at first.
+    /// Completing a visible cell reveals its neighbors in the same row and
+    /// column. Completed cells that aren't connected to the starting cell
+    /// don't reveal anything.
+    Exploration { x: usize, y: usize },
+    /// Only the top row can be seen at first. Every further row is revealed
+    /// once a cell of the row above it is completed.
+    RowByRow,
+}
+
+impl Fog {
+    /// Exploration bingo starting in the center of a board with the size.
+    pub fn exploration(size: usize) -> Self {
+        Fog::Exploration {
+            x: size / 2,
+            y: size / 2,
+        }
+    }
+
+    /// Which cells of a board with the size can be seen, given which of them
+    /// are completed. The cells are passed by their index, row by row.
+    pub fn visibility<F>(self, size: usize, is_completed: F) -> Visibility
+    where
+        F: Fn(usize) -> bool,
+    {
+        match self {
+            Fog::Clear => Visibility::all(size),
+            Fog::Hidden => Visibility::none(size),
+            Fog::Exploration { x, y } => {
+                let mut visibility = Visibility::none(size);
+                let start = match cell_index(size, x, y) {
+                    Some(start) => start,
+                    None => return visibility,
+                };
+                // Only the completed cells that can be reached from the
+                // starting cell reveal their neighbors. Every cell is put on
+                // the stack once, when it gets revealed.
+                let mut stack = ArrayVec::<[usize; MAX_CELLS]>::new();
+                visibility.cells[start] = true;
+                stack.push(start);
+                while let Some(i) = stack.pop() {
+                    if !is_completed(i) {
+                        continue;
+                    }
+                    let (x, y) = (i % size, i / size);
+                    let neighbors = [
+                        if x > 0 { Some(i - 1) } else { None },
+                        if x + 1 < size { Some(i + 1) } else { None },
+                        if y > 0 { Some(i - size) } else { None },
+                        if y + 1 < size { Some(i + size) } else { None },
+                    ];
+                    for &neighbor in neighbors.iter().flatten() {
+                        if !visibility.cells[neighbor] {
+                            visibility.cells[neighbor] = true;
+                            stack.push(neighbor);
+                        }
+                    }
+                }
+                visibility
+            }
+            Fog::RowByRow => {
+                let mut rows = 1;
+                while rows < size && (0..size).any(|x| is_completed((rows - 1) * size + x)) {
+                    rows += 1;
+                }
+                let mut visibility = Visibility::none(size);
+                for visible in &mut visibility.cells[..rows.min(size) * size] {
+                    *visible = true;
+                }
+                visibility
+            }
+        }
+    }
+
+    /// Which cells a player can see, given the cells the player completed.
+    pub fn for_state(self, state: &BoardState) -> Visibility {
+        self.visibility(state.size, |i| state.marks[i].is_some())
+    }
+
+    /// Which cells a team of a lockout game can see. Only the cells the team
+    /// claimed itself reveal other cells.
+    pub fn for_team(self, game: &Lockout, team: usize) -> Visibility {
+        let size = game.size();
+        self.visibility(size, |i| {
+            game.owner(i % size, i / size).map(|claim| claim.team) == Some(team)
+        })
+    }
+}
+
+/// Whether each cell of a board can be seen by a player.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Visibility {
+    /// The width and height of the board.
+    pub size: usize,
+    /// Whether each cell can be seen, row by row.
+    pub cells: ArrayVec<[bool; MAX_CELLS]>,
+}
+
+impl Visibility {
+    /// Every cell of a board with the size can be seen.
+    pub fn all(size: usize) -> Self {
+        Visibility {
+            size: size,
+            cells: (0..size * size).map(|_| true).collect(),
+        }
+    }
+
+    /// No cell of a board with the size can be seen.
+    pub fn none(size: usize) -> Self {
+        Visibility {
+            size: size,
+            cells: (0..size * size).map(|_| false).collect(),
+        }
+    }
+
+    /// Whether the cell can be seen. Cells outside of the board can't.
+    pub fn is_visible(&self, x: usize, y: usize) -> bool {
+        match cell_index(self.size, x, y) {
+            Some(i) => self.cells[i],
+            None => false,
+        }
+    }
+
+    pub fn visible_count(&self) -> usize {
+        self.cells.iter().filter(|&&visible| visible).count()
+    }
+
+    /// The goals of the board as the player sees them, row by row, where the
+    /// hidden goals are `None`. Use this to avoid sending hidden goals to
+    /// players.
+    pub fn goals<'a, 'b>(
+        &'b self,
+        bingo: &'b Bingo<'a>,
+    ) -> impl Iterator<Item = Option<&'a str>> + 'b {
+        bingo
+            .cells
+            .iter()
+            .zip(&self.cells)
+            .map(|(&goal, &visible)| if visible { Some(goal) } else { None })
+    }
+}
+
+impl<'de> Deserialize<'de> for Visibility {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename = "Visibility")]
+        struct Fields {
+            size: usize,
+            cells: ArrayVec<[bool; MAX_CELLS]>,
+        }
+
+        let fields = Fields::deserialize(deserializer)?;
+        bingo::check_cells(fields.size, fields.cells.len())?;
+        Ok(Visibility {
+            size: fields.size,
+            cells: fields.cells,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+    use Rules;
+
+    fn visible(visibility: &Visibility) -> Vec<usize> {
+        (0..visibility.cells.len())
+            .filter(|&i| visibility.cells[i])
+            .collect()
+    }
+
+    #[test]
+    fn clear_and_hidden() {
+        let state = BoardState::with_size(5);
+        assert_eq!(Fog::Clear.for_state(&state).visible_count(), 25);
+        assert_eq!(Fog::Hidden.for_state(&state).visible_count(), 0);
+    }
+
+    #[test]
+    fn exploration() {
+        let mut state = BoardState::with_size(5);
+        let fog = Fog::exploration(5);
+        assert_eq!(fog, Fog::Exploration { x: 2, y: 2 });
+        assert_eq!(visible(&fog.for_state(&state)), [12]);
+
+        state.mark(2, 2, 10);
+        assert_eq!(visible(&fog.for_state(&state)), [7, 11, 12, 13, 17]);
+
+        state.mark(3, 2, 20);
+        state.mark(4, 2, 30);
+        state.mark(4, 3, 40);
+        let visibility = fog.for_state(&state);
+        assert_eq!(
+            visible(&visibility),
+            [7, 8, 9, 11, 12, 13, 14, 17, 18, 19, 24]
+        );
+        assert!(visibility.is_visible(4, 4));
+        assert!(!visibility.is_visible(0, 0));
+
+        assert!(!visibility.is_visible(5, 0));
+        assert!(!visibility.is_visible(0, 5));
+
+        // Completed cells that aren't connected to the start reveal nothing,
+        // until a path to them is completed.
+        state.mark(0, 0, 50);
+        state.mark(0, 2, 60);
+        let visibility = fog.for_state(&state);
+        assert_eq!(visible(&visibility).len(), 11);
+        assert!(!visibility.is_visible(0, 0));
+        state.mark(1, 2, 70);
+        assert_eq!(
+            visible(&fog.for_state(&state)),
+            [5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 24]
+        );
+
+        let corner = Fog::Exploration { x: 0, y: 0 };
+        assert_eq!(visible(&corner.for_state(&BoardState::with_size(5))), [0]);
+        let outside = Fog::Exploration { x: 5, y: 0 };
+        assert_eq!(
+            outside.for_state(&BoardState::with_size(5)).visible_count(),
+            0
+        );
+    }
+
+    #[test]
+    fn row_by_row() {
+        let mut state = BoardState::with_size(5);
+        assert_eq!(visible(&Fog::RowByRow.for_state(&state)), [0, 1, 2, 3, 4]);
+
+        // Completing a cell of a row that can't be seen yet doesn't count.
+        state.mark(0, 2, 10);
+        assert_eq!(Fog::RowByRow.for_state(&state).visible_count(), 5);
+
+        state.mark(3, 0, 20);
+        assert_eq!(Fog::RowByRow.for_state(&state).visible_count(), 10);
+        state.mark(1, 1, 30);
+        assert_eq!(Fog::RowByRow.for_state(&state).visible_count(), 20);
+        for y in 2..5 {
+            state.mark(0, y, 40);
+        }
+        assert_eq!(Fog::RowByRow.for_state(&state).visible_count(), 25);
+    }
+
+    #[test]
+    fn lockout() {
+        let mut game = Lockout::with_size(3, 2, Rules::default());
+        game.claim(0, 1, 0, 10).unwrap();
+        game.claim(1, 0, 1, 20).unwrap();
+        assert_eq!(Fog::RowByRow.for_team(&game, 0).visible_count(), 6);
+        assert_eq!(Fog::RowByRow.for_team(&game, 1).visible_count(), 3);
+        let fog = Fog::Exploration { x: 0, y: 1 };
+        assert_eq!(visible(&fog.for_team(&game, 1)), [0, 3, 4, 6]);
+        assert_eq!(visible(&fog.for_team(&game, 0)), [3]);
+    }
+
+    #[test]
+    fn to_json() {
+        let visibility = Fog::RowByRow.for_state(&BoardState::with_size(2));
+        let json = serde_json::to_string(&visibility).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Visibility>(&json).unwrap(),
+            visibility
+        );
+        assert!(serde_json::from_str::<Visibility>(r#"{"size":2,"cells":[true]}"#).is_err());
+        assert!(serde_json::from_str::<Visibility>(r#"{"size":11,"cells":[]}"#).is_err());
+    }
+
+    #[test]
+    fn goals() {
+        let bingo = Bingo {
+            size: 2,
+            cells: ["A", "B", "C", "D"].iter().cloned().collect(),
+            icons: [None; 4].iter().cloned().collect(),
+        };
+        let visibility = Fog::RowByRow.visibility(2, |_| false);
+        assert_eq!(
+            visibility.goals(&bingo).collect::<Vec<_>>(),
+            [Some("A"), Some("B"), None, None]
+        );
+    }
+}